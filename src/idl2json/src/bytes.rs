@@ -1,69 +1,94 @@
-use crate::{BytesFormat, Idl2JsonOptions};
-use candid::types::value::IDLValue;
+use crate::{path::ValuePath, BytesFormat, Idl2JsonOptions};
+use candid::{types::value::IDLValue, Principal};
 use serde_json::value::Value as JsonValue;
 use sha2::{Digest, Sha256};
 
 /// Converts supposedly binary data.  Returns an error if the data is not binary.
-pub fn convert_bytes(bytes: &[IDLValue], options: &Idl2JsonOptions) -> Result<JsonValue, ()> {
-    if let Some((len, bytes_format)) = options.long_bytes_as {
-        if bytes.len() >= len {
-            return format_bytes(bytes, &bytes_format);
-        }
-    }
-    format_bytes(bytes, &(options.bytes_as.unwrap_or_default()))
-}
-/// Formats supposedly binary data.  Returns an error if the data is not binary.
-fn format_bytes(bytes: &[IDLValue], bytes_format: &BytesFormat) -> Result<JsonValue, ()> {
-    match bytes_format {
-        BytesFormat::Numbers => Ok(JsonValue::Array(
-            bytes
-                .iter()
-                .map(|item| {
-                    if let IDLValue::Nat8(value) = item {
-                        Ok(JsonValue::Number(serde_json::Number::from(*value)))
-                    } else {
-                        Err(())
-                    }
-                })
-                .collect::<Result<Vec<JsonValue>, ()>>()?,
-        )),
-        BytesFormat::Hex => {
-            let mut ans = String::with_capacity(bytes.len() * 2);
-            for byte in bytes {
-                if let IDLValue::Nat8(value) = byte {
-                    ans.push_str(nybble2hex(value >> 4));
-                    ans.push_str(nybble2hex(value & 0xf));
-                } else {
-                    return Err(());
-                }
+pub fn convert_bytes(
+    bytes: &[IDLValue],
+    path: &ValuePath,
+    options: &Idl2JsonOptions,
+) -> Result<JsonValue, ()> {
+    let bytes = bytes
+        .iter()
+        .map(|item| {
+            if let IDLValue::Nat8(value) = item {
+                Ok(*value)
+            } else {
+                Err(())
             }
-            Ok(JsonValue::String(ans))
+        })
+        .collect::<Result<Vec<u8>, ()>>()?;
+    Ok(convert_blob(&bytes, path, options))
+}
+
+// Converts binary data according to the args.
+/// Converts binary data.
+pub fn convert_blob(bytes: &[u8], path: &ValuePath, options: &Idl2JsonOptions) -> JsonValue {
+    match recognize_bytes(bytes, path, options) {
+        Some(RecognizedBytes::Principal(principal)) => {
+            return JsonValue::String(principal.to_text())
         }
-        #[cfg(feature = "crypto")]
-        BytesFormat::Sha256 => {
-            let mut hasher = Sha256::new();
-            for byte in bytes {
-                if let IDLValue::Nat8(value) = byte {
-                    hasher.update([*value]);
-                } else {
-                    return Err(());
-                }
-            }
-            let digest = hasher.finalize();
-            Ok(JsonValue::String(format!("Bytes with sha256: {digest:x}")))
+        Some(RecognizedBytes::Text(text)) => return JsonValue::String(text.to_string()),
+        None => {}
+    }
+    let bytes_format = match options.long_bytes_as {
+        Some((len, bytes_format)) if bytes.len() >= len => bytes_format,
+        _ => options.bytes_as.unwrap_or_default(),
+    };
+    match (format_blob(bytes, &bytes_format), bytes_format) {
+        // yaml2candid reads hex with a prefix, as it cannot otherwise be told from base64.
+        (JsonValue::String(hex), BytesFormat::Hex) if options.output_format.is_yaml() => {
+            JsonValue::String(format!("0x{hex}"))
         }
+        (json, _) => json,
     }
 }
 
-// Converts binary data according to the args.
-/// Converts supposedly binary data.  Returns an error if the data is not binary.
-pub fn convert_blob(bytes: &[u8], options: &Idl2JsonOptions) -> JsonValue {
-    if let Some((len, bytes_format)) = options.long_bytes_as {
-        if bytes.len() >= len {
-            return format_blob(bytes, &bytes_format);
+/// Bytes that are recognized as something else.
+pub(crate) enum RecognizedBytes<'a> {
+    /// The bytes of a principal.
+    Principal(Principal),
+    /// UTF-8 text.
+    Text(&'a str),
+}
+
+/// Whether any bytes may be recognized as a principal or as text.
+pub(crate) fn may_recognize_bytes(options: &Idl2JsonOptions) -> bool {
+    options.utf8_bytes_as_text
+        || !options.principal_bytes_lengths.is_empty()
+        || !options.principal_bytes_paths.is_empty()
+}
+
+/// Recognizes bytes as a principal or as text, as configured in the options.
+///
+/// - Bytes at `options.principal_bytes_paths` or of `options.principal_bytes_lengths` are
+///   principals, if they are short enough to be a principal.
+/// - With `options.utf8_bytes_as_text`, bytes that are UTF-8 are text, if they are not empty
+///   and contain no control characters other than whitespace.
+pub(crate) fn recognize_bytes<'a>(
+    bytes: &'a [u8],
+    path: &ValuePath,
+    options: &Idl2JsonOptions,
+) -> Option<RecognizedBytes<'a>> {
+    let is_principal = options.principal_bytes_lengths.contains(&bytes.len())
+        || options
+            .principal_bytes_paths
+            .iter()
+            .any(|pattern| pattern.matches(path));
+    if is_principal {
+        if let Ok(principal) = Principal::try_from_slice(bytes) {
+            return Some(RecognizedBytes::Principal(principal));
+        }
+    }
+    if options.utf8_bytes_as_text && !bytes.is_empty() {
+        if let Ok(text) = std::str::from_utf8(bytes) {
+            if !text.chars().any(|c| c.is_control() && !c.is_whitespace()) {
+                return Some(RecognizedBytes::Text(text));
+            }
         }
     }
-    format_blob(bytes, &(options.bytes_as.unwrap_or_default()))
+    None
 }
 
 /// Formats binary data.  Returns an error if the data is not binary.
@@ -0,0 +1,107 @@
+//! Canonical JSON, as defined by the JSON Canonicalization Scheme (RFC 8785).
+use crate::JsonValue;
+
+/// Serializes JSON canonically, as defined by RFC 8785, so that equal values always produce identical bytes.
+///
+/// - There is no whitespace.
+/// - Object keys are sorted by their UTF-16 code units.
+/// - Strings are escaped minimally.
+/// - Numbers are formatted as in ECMAScript, so integers have no fractional part and
+///   very large and small numbers use exponents: `1e+21`, `1e-7`.
+pub fn to_canonical_json(json: &JsonValue) -> String {
+    let mut canonical = String::new();
+    write_canonical(json, &mut canonical);
+    canonical
+}
+
+/// Appends canonical JSON to a string.
+fn write_canonical(json: &JsonValue, canonical: &mut String) {
+    match json {
+        JsonValue::Null | JsonValue::Bool(_) => canonical.push_str(&json.to_string()),
+        JsonValue::Number(number) => canonical.push_str(
+            &number
+                .as_f64()
+                .map_or_else(|| number.to_string(), es6_number),
+        ),
+        JsonValue::String(string) => write_string(string, canonical),
+        JsonValue::Array(items) => {
+            canonical.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    canonical.push(',');
+                }
+                write_canonical(item, canonical);
+            }
+            canonical.push(']');
+        }
+        JsonValue::Object(object) => {
+            let mut entries: Vec<(Vec<u16>, &String, &JsonValue)> = object
+                .iter()
+                .map(|(key, value)| (key.encode_utf16().collect(), key, value))
+                .collect();
+            entries.sort_by(|(left, _, _), (right, _, _)| left.cmp(right));
+            canonical.push('{');
+            for (index, (_, key, value)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    canonical.push(',');
+                }
+                write_string(key, canonical);
+                canonical.push(':');
+                write_canonical(value, canonical);
+            }
+            canonical.push('}');
+        }
+    }
+}
+
+/// Appends a quoted string.
+///
+/// serde_json escapes only `"`, `\` and control characters, using the short forms such as `\n`
+/// where they exist and lower case hex otherwise, which is exactly what RFC 8785 requires.
+fn write_string(string: &str, canonical: &mut String) {
+    canonical.push_str(&JsonValue::String(string.to_string()).to_string());
+}
+
+/// Formats a number as ECMAScript's `Number.prototype.toString()` does.
+///
+/// ECMAScript uses the fewest digits that round trip, as Rust's formatting does.  If there is
+/// a choice of such digits, ECMAScript takes the closest, with ties going to the even digit.
+/// Rust may choose differently, so the digits are rounded correctly to the same length.
+fn es6_number(number: f64) -> String {
+    if number == 0.0 {
+        // Negative zero is formatted as zero.
+        return "0".to_string();
+    }
+    let shortest = format!("{:e}", number.abs());
+    let shortest_digit_count = shortest.split('e').next().map_or(1, |mantissa| {
+        mantissa.chars().filter(char::is_ascii_digit).count()
+    });
+    let scientific = format!("{:.*e}", shortest_digit_count - 1, number.abs());
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .unwrap_or((scientific.as_str(), "0"));
+    let digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+    let exponent: i32 = exponent.parse().unwrap_or_default();
+    let digit_count = digits.len() as i32;
+    // The position of the decimal point relative to the start of the digits.
+    let point = exponent + 1;
+    let sign = if number < 0.0 { "-" } else { "" };
+    let unsigned = if digit_count <= point && point <= 21 {
+        format!("{digits}{}", "0".repeat((point - digit_count) as usize))
+    } else if 0 < point && point <= 21 {
+        let (integer, fraction) = digits.split_at(point as usize);
+        format!("{integer}.{fraction}")
+    } else if -6 < point && point <= 0 {
+        format!("0.{}{digits}", "0".repeat(-point as usize))
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let exponent_sign = if exponent < 0 { "-" } else { "+" };
+        let exponent = exponent.abs();
+        if rest.is_empty() {
+            format!("{first}e{exponent_sign}{exponent}")
+        } else {
+            format!("{first}.{rest}e{exponent_sign}{exponent}")
+        }
+    };
+    format!("{sign}{unsigned}")
+}
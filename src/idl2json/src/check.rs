@@ -0,0 +1,276 @@
+//! Checks that candid values conform to candid types.
+//!
+//! Values are checked as candid text is annotated with a type, as by `IDLValue::annotate_type()`, however
+//! every value that does not conform is reported, rather than only the first:
+//! - Numbers must be in the range of their type, and `int` accepts `nat`.
+//! - Record fields that are absent must be of an `opt` or `reserved` type.  Record fields that are not in the type
+//!   are ignored, as they would be by a decoder.
+//! - Variant tags must be in the type.
+//! - `reserved` accepts any value and `empty` none.
+//!
+//! A value in an `opt` that does not conform is reported, even though a decoder would read it as `null`, as
+//! that is rarely what was intended.
+//!
+//! Paths are as for `PathPattern`, starting with the index of the argument for argument lists.  The top level
+//! value has the path `.`.
+use crate::{
+    polyfill::idl_type,
+    typed_conversion::{resolve_type, typed_field},
+    Idl2JsonOptions, JsonValue,
+};
+use candid::{
+    types::value::{IDLField, IDLValue},
+    IDLArgs, Int, Nat,
+};
+use candid_parser::types::{IDLType, IDLTypes, PrimType, TypeField};
+use serde_json::json;
+use std::str::FromStr;
+
+/// A value that does not conform to its type.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Nonconformance {
+    /// The path to the value, such as `accounts.0.owner`.
+    pub path: String,
+    /// Why the value does not conform, such as `expected nat8, found 300`.
+    pub reason: String,
+}
+
+/// Checks that a candid value conforms to the given type.
+///
+/// Returns the values that do not conform, if any.
+pub fn idl_check(
+    idl: &IDLValue,
+    idl_type: &IDLType,
+    options: &Idl2JsonOptions,
+) -> Vec<Nonconformance> {
+    let mut nonconformances = Vec::new();
+    check(
+        idl,
+        idl_type,
+        &mut Vec::new(),
+        &mut nonconformances,
+        options,
+    );
+    nonconformances
+}
+
+/// Checks that candid arguments conform to the given types.
+///
+/// - Arguments that are absent must be of an `opt`, `null` or `reserved` type.
+/// - Arguments beyond those in the types are ignored, as they would be by a decoder.
+pub fn idl_args_check(
+    idl_args: &IDLArgs,
+    idl_types: &IDLTypes,
+    options: &Idl2JsonOptions,
+) -> Vec<Nonconformance> {
+    let mut nonconformances = Vec::new();
+    for (index, idl_type) in idl_types.args.iter().enumerate() {
+        let mut path = vec![index.to_string()];
+        match idl_args.args.get(index) {
+            Some(idl) => check(idl, idl_type, &mut path, &mut nonconformances, options),
+            None if !may_be_absent(idl_type, true, options) => report(
+                &mut nonconformances,
+                &path,
+                format!("missing argument of type {}", idl_type::to_string(idl_type)),
+            ),
+            None => {}
+        }
+    }
+    nonconformances
+}
+
+/// The values that do not conform to their types, as JSON: `[{"path": "owner", "reason": "..."}]`.
+pub fn check2json(nonconformances: &[Nonconformance]) -> JsonValue {
+    JsonValue::Array(
+        nonconformances
+            .iter()
+            .map(|nonconformance| {
+                json!({ "path": nonconformance.path, "reason": nonconformance.reason })
+            })
+            .collect(),
+    )
+}
+
+/// Checks a value, adding those parts of it that do not conform to the list.
+///
+/// The path is that of the value, as a list of steps.
+fn check(
+    idl: &IDLValue,
+    idl_type: &IDLType,
+    path: &mut Vec<String>,
+    nonconformances: &mut Vec<Nonconformance>,
+    options: &Idl2JsonOptions,
+) {
+    let expected = || idl_type::to_string(idl_type);
+    let mismatch = || format!("expected {}, found {}", expected(), describe(idl));
+    let Some(resolved_type) = resolve_type(idl_type, options) else {
+        report(
+            nonconformances,
+            path,
+            format!("type {} is not defined", expected()),
+        );
+        return;
+    };
+    match (idl, &resolved_type) {
+        (_, IDLType::PrimT(PrimType::Reserved)) => {}
+        (_, IDLType::PrimT(PrimType::Empty)) => report(
+            nonconformances,
+            path,
+            format!("no value may be of type {}", expected()),
+        ),
+        (IDLValue::Number(number), IDLType::PrimT(prim_type)) => {
+            if let Some(is_in_range) = number_is_in_range(number, prim_type) {
+                if !is_in_range {
+                    report(
+                        nonconformances,
+                        path,
+                        format!("{number} is out of range for {}", expected()),
+                    );
+                }
+            } else {
+                report(nonconformances, path, mismatch());
+            }
+        }
+        (IDLValue::Null, IDLType::PrimT(PrimType::Null))
+        | (IDLValue::Bool(_), IDLType::PrimT(PrimType::Bool))
+        | (IDLValue::Text(_), IDLType::PrimT(PrimType::Text))
+        | (IDLValue::Nat(_), IDLType::PrimT(PrimType::Nat | PrimType::Int))
+        | (IDLValue::Int(_), IDLType::PrimT(PrimType::Int))
+        | (IDLValue::Nat8(_), IDLType::PrimT(PrimType::Nat8))
+        | (IDLValue::Nat16(_), IDLType::PrimT(PrimType::Nat16))
+        | (IDLValue::Nat32(_), IDLType::PrimT(PrimType::Nat32))
+        | (IDLValue::Nat64(_), IDLType::PrimT(PrimType::Nat64))
+        | (IDLValue::Int8(_), IDLType::PrimT(PrimType::Int8))
+        | (IDLValue::Int16(_), IDLType::PrimT(PrimType::Int16))
+        | (IDLValue::Int32(_), IDLType::PrimT(PrimType::Int32))
+        | (IDLValue::Int64(_), IDLType::PrimT(PrimType::Int64))
+        | (IDLValue::Float32(_), IDLType::PrimT(PrimType::Float32))
+        | (IDLValue::Float64(_), IDLType::PrimT(PrimType::Float32 | PrimType::Float64))
+        | (IDLValue::Principal(_), IDLType::PrincipalT)
+        | (IDLValue::Func(..), IDLType::FuncT(_))
+        | (IDLValue::Service(_), IDLType::ServT(_)) => {}
+        (IDLValue::Null | IDLValue::None | IDLValue::Reserved, IDLType::OptT(_)) => {}
+        (IDLValue::Opt(inner), IDLType::OptT(inner_type)) => {
+            check(inner, inner_type, path, nonconformances, options)
+        }
+        (IDLValue::Blob(_), IDLType::VecT(element_type))
+            if matches!(
+                resolve_type(element_type, options),
+                Some(IDLType::PrimT(PrimType::Nat8))
+            ) => {}
+        (IDLValue::Vec(elements), IDLType::VecT(element_type)) => {
+            for (index, element) in elements.iter().enumerate() {
+                path.push(index.to_string());
+                check(element, element_type, path, nonconformances, options);
+                path.pop();
+            }
+        }
+        (IDLValue::Record(fields), IDLType::RecordT(field_types)) => {
+            check_record(fields, field_types, path, nonconformances, options)
+        }
+        (IDLValue::Variant(variant), IDLType::VariantT(field_types)) => {
+            let field = &variant.0;
+            match typed_field(field, field_types) {
+                (tag, Some(field_type)) => {
+                    path.push(tag);
+                    check(&field.val, field_type, path, nonconformances, options);
+                    path.pop();
+                }
+                (tag, None) => report(
+                    nonconformances,
+                    path,
+                    format!("variant tag {tag} is not in {}", expected()),
+                ),
+            }
+        }
+        _ => report(nonconformances, path, mismatch()),
+    }
+}
+
+/// Checks the fields of a record, adding those that do not conform, or are missing, to the list.
+fn check_record(
+    fields: &[IDLField],
+    field_types: &[TypeField],
+    path: &mut Vec<String>,
+    nonconformances: &mut Vec<Nonconformance>,
+    options: &Idl2JsonOptions,
+) {
+    for field_type in field_types {
+        let field_id = field_type.label.get_id();
+        path.push(field_type.label.to_string());
+        match fields.iter().find(|field| field.id.get_id() == field_id) {
+            Some(field) => check(&field.val, &field_type.typ, path, nonconformances, options),
+            None if !may_be_absent(&field_type.typ, false, options) => report(
+                nonconformances,
+                path,
+                format!(
+                    "missing field of type {}",
+                    idl_type::to_string(&field_type.typ)
+                ),
+            ),
+            None => {}
+        }
+        path.pop();
+    }
+}
+
+/// Whether a value of the given type may be absent: a record field of an `opt` or `reserved` type, or an
+/// argument of those types or `null`.
+fn may_be_absent(idl_type: &IDLType, is_argument: bool, options: &Idl2JsonOptions) -> bool {
+    match resolve_type(idl_type, options) {
+        Some(IDLType::OptT(_) | IDLType::PrimT(PrimType::Reserved)) => true,
+        Some(IDLType::PrimT(PrimType::Null)) => is_argument,
+        _ => false,
+    }
+}
+
+/// Whether a number, as written in candid text, is in the range of a numeric type.
+///
+/// Returns None for types that are not integer types, as numbers without a type are integers.
+fn number_is_in_range(number: &str, prim_type: &PrimType) -> Option<bool> {
+    Some(match prim_type {
+        PrimType::Nat => Nat::from_str(number).is_ok(),
+        PrimType::Int => Int::from_str(number).is_ok(),
+        PrimType::Nat8 => number.parse::<u8>().is_ok(),
+        PrimType::Nat16 => number.parse::<u16>().is_ok(),
+        PrimType::Nat32 => number.parse::<u32>().is_ok(),
+        PrimType::Nat64 => number.parse::<u64>().is_ok(),
+        PrimType::Int8 => number.parse::<i8>().is_ok(),
+        PrimType::Int16 => number.parse::<i16>().is_ok(),
+        PrimType::Int32 => number.parse::<i32>().is_ok(),
+        PrimType::Int64 => number.parse::<i64>().is_ok(),
+        _ => return None,
+    })
+}
+
+/// A short description of a value: the value itself if it is simple, otherwise its kind.
+fn describe(idl: &IDLValue) -> String {
+    match idl {
+        IDLValue::Record(_) => "a record".to_string(),
+        IDLValue::Variant(variant) => format!("variant {}", variant.0.id),
+        IDLValue::Vec(_) => "a vec".to_string(),
+        IDLValue::Blob(_) => "a blob".to_string(),
+        IDLValue::Opt(_) => "an opt".to_string(),
+        IDLValue::Func(..) => "a func".to_string(),
+        IDLValue::Service(_) => "a service".to_string(),
+        IDLValue::None => "null".to_string(),
+        idl => idl.to_string(),
+    }
+}
+
+/// Adds a value that does not conform to the list.
+fn report(nonconformances: &mut Vec<Nonconformance>, path: &[String], reason: String) {
+    nonconformances.push(Nonconformance {
+        path: path_string(path),
+        reason,
+    });
+}
+
+/// The path as text, with `.` for the top level value.
+fn path_string(path: &[String]) -> String {
+    if path.is_empty() {
+        ".".to_string()
+    } else {
+        path.join(".")
+    }
+}
@@ -0,0 +1,274 @@
+//! Structural differences between candid values.
+//!
+//! Both values are converted to JSON, as by `idl2json_with_weak_names`, and the JSON is compared.
+//! The changes may be given as a JSON Patch (RFC 6902), which turns the JSON of the first value
+//! into that of the second, or as text with one line per change.
+use crate::{idl2json_with_weak_names, typed_conversion::resolve_type, Idl2JsonOptions, JsonValue};
+use candid::{types::value::IDLValue, IDLArgs};
+use candid_parser::types::{IDLType, IDLTypes, PrimType};
+
+/// A step from a JSON value to a value inside it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DiffStep {
+    /// A record field or variant tag.
+    Key(String),
+    /// A vector element, or an argument.
+    Index(usize),
+    /// The content of an `opt` value.
+    ///
+    /// In JSON this is the first element of an array, however it does not appear in candid paths.
+    Opt,
+}
+
+/// A change from one value to another.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueChange {
+    /// The path to the changed value.
+    pub path: Vec<DiffStep>,
+    /// The value before the change, if it was present.
+    pub before: Option<JsonValue>,
+    /// The value after the change, if it is present.
+    pub after: Option<JsonValue>,
+}
+
+/// Compares two candid values of the given type.
+///
+/// Values that are absent from a type, such as additional record fields, are compared without type information.
+pub fn idl_diff(
+    before: &IDLValue,
+    after: &IDLValue,
+    idl_type: &IDLType,
+    options: &Idl2JsonOptions,
+) -> Vec<ValueChange> {
+    let mut changes = Vec::new();
+    diff_json(
+        &idl2json_with_weak_names(before, idl_type, options),
+        &idl2json_with_weak_names(after, idl_type, options),
+        Some(idl_type),
+        &mut Vec::new(),
+        &mut changes,
+        options,
+    );
+    changes
+}
+
+/// Compares two candid argument lists of the given types.
+///
+/// - Paths start with the index of the argument.
+/// - If fewer types than arguments are given, the remaining arguments are compared without type information.
+pub fn idl_args_diff(
+    before: &IDLArgs,
+    after: &IDLArgs,
+    idl_types: &IDLTypes,
+    options: &Idl2JsonOptions,
+) -> Vec<ValueChange> {
+    let untyped = IDLType::PrimT(PrimType::Null);
+    let mut changes = Vec::new();
+    let mut path = Vec::new();
+    for index in 0..before.args.len().max(after.args.len()) {
+        let idl_type = idl_types.args.get(index).unwrap_or(&untyped);
+        let json = |args: &IDLArgs| {
+            args.args
+                .get(index)
+                .map(|value| idl2json_with_weak_names(value, idl_type, options))
+        };
+        path.push(DiffStep::Index(index));
+        match (json(before), json(after)) {
+            (Some(before), Some(after)) => diff_json(
+                &before,
+                &after,
+                Some(idl_type),
+                &mut path,
+                &mut changes,
+                options,
+            ),
+            (before, after) => changes.push(ValueChange {
+                path: path.clone(),
+                before,
+                after,
+            }),
+        }
+        path.pop();
+    }
+    changes
+}
+
+/// Appends the changes from one JSON value to another to a list.
+///
+/// The type is used only to recognize `opt` values; it is dropped wherever the JSON does not match it.
+fn diff_json(
+    before: &JsonValue,
+    after: &JsonValue,
+    idl_type: Option<&IDLType>,
+    path: &mut Vec<DiffStep>,
+    changes: &mut Vec<ValueChange>,
+    options: &Idl2JsonOptions,
+) {
+    if before == after {
+        return;
+    }
+    let idl_type = idl_type.and_then(|idl_type| resolve_type(idl_type, options));
+    match (before, after) {
+        (JsonValue::Object(before), JsonValue::Object(after)) => {
+            for (key, before_value) in before {
+                path.push(DiffStep::Key(key.clone()));
+                if let Some(after_value) = after.get(key) {
+                    let field_type = idl_type
+                        .as_ref()
+                        .and_then(|idl_type| field_type(idl_type, key));
+                    diff_json(
+                        before_value,
+                        after_value,
+                        field_type,
+                        path,
+                        changes,
+                        options,
+                    );
+                } else {
+                    changes.push(ValueChange {
+                        path: path.clone(),
+                        before: Some(before_value.clone()),
+                        after: None,
+                    });
+                }
+                path.pop();
+            }
+            for (key, after_value) in after {
+                if !before.contains_key(key) {
+                    path.push(DiffStep::Key(key.clone()));
+                    changes.push(ValueChange {
+                        path: path.clone(),
+                        before: None,
+                        after: Some(after_value.clone()),
+                    });
+                    path.pop();
+                }
+            }
+        }
+        (JsonValue::Array(before), JsonValue::Array(after)) => {
+            let (step, item_type): (fn(usize) -> DiffStep, _) = match &idl_type {
+                Some(IDLType::OptT(item_type)) if before.len() <= 1 && after.len() <= 1 => {
+                    (|_| DiffStep::Opt, Some(&**item_type))
+                }
+                Some(IDLType::VecT(item_type)) => (DiffStep::Index, Some(&**item_type)),
+                _ => (DiffStep::Index, None),
+            };
+            for (index, (before_item, after_item)) in before.iter().zip(after).enumerate() {
+                path.push(step(index));
+                diff_json(before_item, after_item, item_type, path, changes, options);
+                path.pop();
+            }
+            // Elements are removed from the end, so that each operation leaves the indices of the next unchanged.
+            for (index, before_item) in before.iter().enumerate().skip(after.len()).rev() {
+                path.push(step(index));
+                changes.push(ValueChange {
+                    path: path.clone(),
+                    before: Some(before_item.clone()),
+                    after: None,
+                });
+                path.pop();
+            }
+            for (index, after_item) in after.iter().enumerate().skip(before.len()) {
+                path.push(step(index));
+                changes.push(ValueChange {
+                    path: path.clone(),
+                    before: None,
+                    after: Some(after_item.clone()),
+                });
+                path.pop();
+            }
+        }
+        (before, after) => changes.push(ValueChange {
+            path: path.clone(),
+            before: Some(before.clone()),
+            after: Some(after.clone()),
+        }),
+    }
+}
+
+/// The type of a record field or variant case, given its JSON key.
+fn field_type<'a>(idl_type: &'a IDLType, key: &str) -> Option<&'a IDLType> {
+    match idl_type {
+        IDLType::RecordT(fields) | IDLType::VariantT(fields) => fields
+            .iter()
+            .find(|field| field.label.to_string() == key)
+            .map(|field| &field.typ),
+        _ => None,
+    }
+}
+
+/// Expresses changes as a JSON Patch (RFC 6902).
+///
+/// Applied in order, the operations turn the JSON of the first value into the JSON of the second.
+pub fn diff2json_patch(changes: &[ValueChange]) -> JsonValue {
+    JsonValue::Array(
+        changes
+            .iter()
+            .map(|change| {
+                let path = json_pointer(&change.path);
+                match (&change.before, &change.after) {
+                    (_, None) => serde_json::json!({ "op": "remove", "path": path }),
+                    (None, Some(value)) => {
+                        serde_json::json!({ "op": "add", "path": path, "value": value })
+                    }
+                    (Some(_), Some(value)) => {
+                        serde_json::json!({ "op": "replace", "path": path, "value": value })
+                    }
+                }
+            })
+            .collect(),
+    )
+}
+
+/// A JSON Pointer (RFC 6901) to the value at a path.
+fn json_pointer(path: &[DiffStep]) -> String {
+    path.iter()
+        .map(|step| match step {
+            DiffStep::Key(key) => format!("/{}", key.replace('~', "~0").replace('/', "~1")),
+            DiffStep::Index(index) => format!("/{index}"),
+            DiffStep::Opt => "/0".to_string(),
+        })
+        .collect()
+}
+
+/// Describes changes as text, with one line per change:
+///
+/// ```text
+/// + path: added value
+/// - path: removed value
+/// ~ path: value before -> value after
+/// ```
+///
+/// Paths are candid paths, as for `PathPattern`, with `.` for the top level value.
+pub fn diff2text(changes: &[ValueChange]) -> String {
+    changes
+        .iter()
+        .map(|change| {
+            let path = candid_path(&change.path);
+            match (&change.before, &change.after) {
+                (None, Some(after)) => format!("+ {path}: {after}"),
+                (Some(before), None) => format!("- {path}: {before}"),
+                (Some(before), Some(after)) => format!("~ {path}: {before} -> {after}"),
+                (None, None) => format!("~ {path}"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The candid path to the value at a path, with `.` for the top level value.
+fn candid_path(path: &[DiffStep]) -> String {
+    let steps: Vec<String> = path
+        .iter()
+        .filter_map(|step| match step {
+            DiffStep::Key(key) => Some(key.clone()),
+            DiffStep::Index(index) => Some(index.to_string()),
+            DiffStep::Opt => None,
+        })
+        .collect();
+    if steps.is_empty() {
+        ".".to_string()
+    } else {
+        steps.join(".")
+    }
+}
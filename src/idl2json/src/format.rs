@@ -0,0 +1,51 @@
+//! Serialization of converted values in formats other than JSON.
+//!
+//! Values are converted to JSON as usual and the JSON is then written in the requested format,
+//! so every format has the same structure: the same keys, the same representation of numbers,
+//! bytes and so on.  The exception is YAML, which is converted as yaml2candid reads it: see
+//! `OutputFormat::Yaml`.
+use crate::{table::json2table, to_canonical_json, Idl2JsonOptions, JsonValue, OutputFormat};
+use anyhow::Context;
+
+/// Serializes a converted value in the format given by `options.output_format`.
+///
+/// - JSON is canonical, compact or pretty, as given by `options.canonical` and `options.compact`.
+/// - CBOR (RFC 8949) and MessagePack are binary.  Integers are written as integers and other
+///   numbers as floats.
+/// - TOML needs a table at the top level and cannot represent `null`.
+/// - CSV and TSV have a row for each element of a top level array, with nested objects flattened.
+pub fn serialize(json: &JsonValue, options: &Idl2JsonOptions) -> anyhow::Result<Vec<u8>> {
+    match options.output_format {
+        OutputFormat::Json => {
+            if options.canonical {
+                Ok(to_canonical_json(json).into_bytes())
+            } else if options.compact {
+                serde_json::to_vec(json).context("Failed to serialize to json")
+            } else {
+                serde_json::to_vec_pretty(json).context("Failed to serialize to json")
+            }
+        }
+        #[cfg(feature = "formats")]
+        OutputFormat::Yaml => serde_yaml::to_string(json)
+            .map(String::into_bytes)
+            .context("Failed to serialize to yaml"),
+        #[cfg(feature = "formats")]
+        OutputFormat::Toml => if options.compact {
+            toml::to_string(json)
+        } else {
+            toml::to_string_pretty(json)
+        }
+        .map(String::into_bytes)
+        .context("Failed to serialize to toml"),
+        OutputFormat::Csv => json2table(json, ',').map(String::into_bytes),
+        OutputFormat::Tsv => json2table(json, '\t').map(String::into_bytes),
+        #[cfg(feature = "formats")]
+        OutputFormat::Cbor => {
+            let mut cbor = Vec::new();
+            ciborium::into_writer(json, &mut cbor).context("Failed to serialize to cbor")?;
+            Ok(cbor)
+        }
+        #[cfg(feature = "formats")]
+        OutputFormat::Msgpack => rmp_serde::to_vec(json).context("Failed to serialize to msgpack"),
+    }
+}
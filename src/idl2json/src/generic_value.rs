@@ -0,0 +1,236 @@
+//! Conversion of generic values, such as the ICRC-3 `Value` type, to plain JSON.
+//!
+//! ICRC-3 blocks are given as a generic value:
+//! ```candid
+//! type Value = variant {
+//!     Blob : blob;
+//!     Text : text;
+//!     Nat : nat;
+//!     Int : int;
+//!     Array : vec Value;
+//!     Map : vec record { text; Value };
+//! };
+//! ```
+//! ICRC-16 extends this with further cases.  Rather than as nested variants, such values are
+//! rendered as the JSON they describe: maps become objects, arrays become arrays and the
+//! contents are converted as usual, so numbers become strings or numbers, blobs become bytes
+//! and so on.
+use crate::{
+    bytes::{convert_blob, convert_bytes},
+    path::ValuePath,
+    polyfill::idl_type::to_string as idl_type_to_string,
+    redact::{is_redacted, redact},
+    typed_conversion::resolve_type,
+    untyped_conversion::{convert_non_bytes_array, idl2json_at},
+    Idl2JsonOptions, JsonValue, KeyOrder,
+};
+use candid::{
+    idl_hash,
+    types::value::{IDLField, IDLValue, VariantValue},
+};
+use candid_parser::types::IDLType;
+
+/// The cases of the ICRC-3 and ICRC-16 generic value variants.
+const GENERIC_VALUE_CASES: [&str; 25] = [
+    "Array",
+    "Blob",
+    "Bool",
+    "Bytes",
+    "Class",
+    "Float",
+    "Floats",
+    "Int",
+    "Int16",
+    "Int32",
+    "Int64",
+    "Int8",
+    "Ints",
+    "Map",
+    "Nat",
+    "Nat16",
+    "Nat32",
+    "Nat64",
+    "Nat8",
+    "Nats",
+    "Option",
+    "Principal",
+    "Set",
+    "Text",
+    "ValueMap",
+];
+
+/// Determines whether values of the given type should be rendered as plain JSON.
+///
+/// - Types named in `options.generic_value_types`, or aliases of them, are always rendered as plain JSON.
+/// - With `options.flatten_generic_values`, variant types with a `Map` case and no cases other
+///   than those of the ICRC-3 and ICRC-16 generic values are recognized.
+pub(crate) fn is_generic_value_type(idl_type: &IDLType, options: &Idl2JsonOptions) -> bool {
+    if let IDLType::VarT(type_name) = idl_type {
+        if options.generic_value_types.contains(type_name) {
+            return true;
+        }
+    }
+    let Some(resolved_type) = resolve_type(idl_type, options) else {
+        return false;
+    };
+    (options.flatten_generic_values && is_generic_value_shape(&resolved_type))
+        || is_named_generic_value_type(&resolved_type, options)
+}
+
+/// Determines whether a resolved type is the definition of a type named in `options.generic_value_types`, once
+/// every alias on both sides is followed.
+fn is_named_generic_value_type(resolved_type: &IDLType, options: &Idl2JsonOptions) -> bool {
+    if options.generic_value_types.is_empty() {
+        return false;
+    }
+    let definition = idl_type_to_string(resolved_type);
+    options.generic_value_types.iter().any(|type_name| {
+        resolve_type(&IDLType::VarT(type_name.clone()), options)
+            .is_some_and(|generic_type| idl_type_to_string(&generic_type) == definition)
+    })
+}
+
+/// Determines whether a type has the shape of a generic value.
+fn is_generic_value_shape(idl_type: &IDLType) -> bool {
+    match idl_type {
+        IDLType::VariantT(fields) => {
+            fields
+                .iter()
+                .any(|field| field.label.get_id() == idl_hash("Map"))
+                && fields.iter().all(|field| {
+                    GENERIC_VALUE_CASES
+                        .iter()
+                        .any(|case| field.label.get_id() == idl_hash(case))
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Determines whether a value, of unknown type, should be rendered as plain JSON.
+///
+/// Without a type, only maps and arrays are recognized, as other variants with cases such
+/// as `Text` are common.  Everything inside a recognized map or array is rendered as plain JSON.
+pub(crate) fn is_generic_value(idl: &IDLValue, options: &Idl2JsonOptions) -> bool {
+    options.flatten_generic_values
+        && matches!(idl, IDLValue::Variant(VariantValue(field, _))
+            if [idl_hash("Map"), idl_hash("Array")].contains(&field.id.get_id()))
+}
+
+/// Renders a generic value as plain JSON.
+///
+/// Returns None if the value does not have the shape of a generic value, in which case it should be converted as usual.
+pub(crate) fn generic_value2json(
+    idl: &IDLValue,
+    path: &ValuePath,
+    options: &Idl2JsonOptions,
+) -> Option<JsonValue> {
+    if is_redacted(None, path, options) {
+        return Some(redact(idl, options));
+    }
+    let IDLValue::Variant(VariantValue(field, _)) = idl else {
+        return None;
+    };
+    let case = GENERIC_VALUE_CASES
+        .iter()
+        .find(|case| field.id.get_id() == idl_hash(case))?;
+    match (*case, &field.val) {
+        ("Map", IDLValue::Vec(entries)) => entries
+            .iter()
+            .map(|entry| match tuple(entry)? {
+                [IDLValue::Text(key), value] => Some((
+                    key.clone(),
+                    generic_value2json(value, &path.key(key), options)?,
+                )),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|entries| map_object(entries, options)),
+        ("Array" | "Set", IDLValue::Vec(items)) => items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| generic_value2json(item, &path.index(index), options))
+            .collect::<Option<Vec<_>>>()
+            .map(JsonValue::Array),
+        ("ValueMap", IDLValue::Vec(entries)) => entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let [key, value] = tuple(entry)?;
+                let path = path.index(index);
+                Some(JsonValue::Array(vec![
+                    generic_value2json(key, &path.index(0), options)?,
+                    generic_value2json(value, &path.index(1), options)?,
+                ]))
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(JsonValue::Array),
+        ("Class", IDLValue::Vec(properties)) => properties
+            .iter()
+            .map(|property| {
+                let IDLValue::Record(fields) = property else {
+                    return None;
+                };
+                let field = |name: &str| {
+                    fields
+                        .iter()
+                        .find(|field| field.id.get_id() == idl_hash(name))
+                        .map(|field| &field.val)
+                };
+                match (field("name")?, field("value")?) {
+                    (IDLValue::Text(name), value) => Some((
+                        name.clone(),
+                        generic_value2json(value, &path.key(name), options)?,
+                    )),
+                    _ => None,
+                }
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|properties| map_object(properties, options)),
+        ("Option", IDLValue::Opt(value)) => generic_value2json(value, path, options),
+        ("Option", IDLValue::None) => Some(JsonValue::Null),
+        ("Blob" | "Bytes", IDLValue::Blob(bytes)) => Some(convert_blob(bytes, path, options)),
+        ("Blob" | "Bytes", IDLValue::Vec(bytes)) => convert_bytes(bytes, path, options).ok(),
+        ("Ints" | "Nats" | "Floats", IDLValue::Vec(items)) => {
+            Some(convert_non_bytes_array(items, path, options))
+        }
+        (
+            _,
+            IDLValue::Vec(_)
+            | IDLValue::Blob(_)
+            | IDLValue::Record(_)
+            | IDLValue::Variant(_)
+            | IDLValue::Opt(_)
+            | IDLValue::None,
+        ) => None,
+        (_, value) => Some(idl2json_at(value, path, options)),
+    }
+}
+
+/// A JSON object with the entries of a map.
+///
+/// The keys are sorted for `KeyOrder::Alphabetical`, else they are in the order of the map.
+/// If a key is repeated, the last value is used.
+fn map_object(mut entries: Vec<(String, JsonValue)>, options: &Idl2JsonOptions) -> JsonValue {
+    if options.key_order == KeyOrder::Alphabetical {
+        entries.sort_by(|(left, _), (right, _)| left.cmp(right));
+    }
+    JsonValue::Object(entries.into_iter().collect())
+}
+
+/// The values of a record with two unnamed fields, such as `record { text; Value }`.
+fn tuple(idl: &IDLValue) -> Option<[&IDLValue; 2]> {
+    match idl {
+        IDLValue::Record(fields) => match &fields[..] {
+            [IDLField {
+                id: first,
+                val: key,
+            }, IDLField {
+                id: second,
+                val: value,
+            }] if first.get_id() == 0 && second.get_id() == 1 => Some([key, value]),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,337 @@
+//! Conversion of JSON back to Candid, reversing the typed conversion.
+use crate::{
+    generic_value::is_generic_value_type,
+    typed_conversion::{default_value, get_type_from_any},
+    BytesFormat, Idl2JsonOptions, JsonValue,
+};
+use anyhow::{anyhow, bail, Context};
+use candid::{
+    types::value::{IDLField, IDLValue, VariantValue},
+    IDLArgs, Int, Nat, Principal,
+};
+use candid_parser::types::{IDLType, IDLTypes, PrimType, TypeField};
+use std::{convert::TryFrom, str::FromStr};
+
+/// Converts JSON produced by `idl2json_with_weak_names()` back to a candid IDLValue of the given type.
+///
+/// The conventions of the typed conversion are reversed, using the same options:
+/// - 64 bit and big integers are parsed from decimal strings.
+/// - `opt` values are parsed from `[]` and `[x]`.
+/// - Bytes are parsed in the formats given by `options.bytes_as` and `options.long_bytes_as`.
+///   Hashed bytes cannot be converted back.  Bytes recognized as principals or text are parsed
+///   from strings on a best effort basis.
+/// - Generic values rendered as plain JSON cannot be converted back.
+/// - Variants are parsed from objects with a single key.
+///
+/// Record fields that Candid subtyping allows to be absent may be missing from the JSON.
+pub fn json2idl(
+    json: &JsonValue,
+    idl_type: &IDLType,
+    options: &Idl2JsonOptions,
+) -> anyhow::Result<IDLValue> {
+    if is_generic_value_type(idl_type, options) {
+        bail!("Generic values rendered as plain JSON cannot be converted back, as the types of their contents are lost");
+    }
+    match idl_type {
+        IDLType::VarT(type_name) => {
+            let resolved_type = get_type_from_any(&options.prog, type_name)
+                .with_context(|| format!("Could not find a type called {type_name:?}"))?;
+            json2idl(json, &resolved_type, options)
+        }
+        IDLType::PrimT(prim_type) => json2prim(json, prim_type, options),
+        IDLType::OptT(item_type) => match json {
+            JsonValue::Array(items) => match &items[..] {
+                [] => Ok(IDLValue::None),
+                [item] => Ok(IDLValue::Opt(Box::new(json2idl(item, item_type, options)?))),
+                _ => bail!("Expected an optional value as an array with at most one element, got {} elements", items.len()),
+            },
+            _ => bail!("Expected an optional value as an array, got: {json}"),
+        },
+        IDLType::VecT(item_type) => match (json, &**item_type) {
+            (_, IDLType::PrimT(PrimType::Nat8)) => Ok(IDLValue::Blob(json2bytes(json, options)?)),
+            (JsonValue::Array(items), _) => Ok(IDLValue::Vec(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(index, item)| {
+                        json2idl(item, item_type, options)
+                            .with_context(|| format!("Failed to convert element #{index}"))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+            )),
+            _ => bail!("Expected a vector as an array, got: {json}"),
+        },
+        IDLType::RecordT(field_types) => json2record(json, field_types, options),
+        IDLType::VariantT(field_types) => json2variant(json, field_types, options),
+        IDLType::PrincipalT => Ok(IDLValue::Principal(json2principal(json)?)),
+        IDLType::ServT(_) => Ok(IDLValue::Service(json2principal(json)?)),
+        IDLType::FuncT(_) => {
+            let principal = json
+                .get("principal")
+                .with_context(|| format!("Expected a func as an object with a principal, got: {json}"))?;
+            let code = json
+                .get("code")
+                .and_then(JsonValue::as_str)
+                .with_context(|| format!("Expected a func as an object with a method name as code, got: {json}"))?;
+            Ok(IDLValue::Func(json2principal(principal)?, code.to_string()))
+        }
+        IDLType::ClassT(_, _) => bail!("A class is not the type of a value"),
+    }
+}
+
+/// Converts JSON produced by `idl_args2json_with_weak_names()` back to candid IDLArgs of the given types.
+pub fn json_args2idl(
+    json: &JsonValue,
+    idl_types: &IDLTypes,
+    options: &Idl2JsonOptions,
+) -> anyhow::Result<IDLArgs> {
+    let JsonValue::Array(values) = json else {
+        bail!("Expected arguments as an array, got: {json}");
+    };
+    if values.len() != idl_types.args.len() {
+        bail!(
+            "Expected {} arguments, got {}",
+            idl_types.args.len(),
+            values.len()
+        );
+    }
+    let args = values
+        .iter()
+        .zip(&idl_types.args)
+        .enumerate()
+        .map(|(index, (value, idl_type))| {
+            json2idl(value, idl_type, options)
+                .with_context(|| format!("Failed to convert argument #{index}"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(IDLArgs { args })
+}
+
+/// Converts JSON to a primitive value.
+fn json2prim(
+    json: &JsonValue,
+    prim_type: &PrimType,
+    options: &Idl2JsonOptions,
+) -> anyhow::Result<IDLValue> {
+    Ok(match prim_type {
+        PrimType::Nat => IDLValue::Nat(
+            Nat::from_str(&json2decimal(json)?)
+                .map_err(|err| anyhow!("Could not parse {json} as nat: {err}"))?,
+        ),
+        PrimType::Int => IDLValue::Int(
+            Int::from_str(&json2decimal(json)?)
+                .map_err(|err| anyhow!("Could not parse {json} as int: {err}"))?,
+        ),
+        PrimType::Nat8 => IDLValue::Nat8(u8::try_from(json2u64(json)?)?),
+        PrimType::Nat16 => IDLValue::Nat16(u16::try_from(json2u64(json)?)?),
+        PrimType::Nat32 => IDLValue::Nat32(u32::try_from(json2u64(json)?)?),
+        PrimType::Nat64 => IDLValue::Nat64(json2decimal(json)?.parse()?),
+        PrimType::Int8 => IDLValue::Int8(i8::try_from(json2i64(json)?)?),
+        PrimType::Int16 => IDLValue::Int16(i16::try_from(json2i64(json)?)?),
+        PrimType::Int32 => IDLValue::Int32(i32::try_from(json2i64(json)?)?),
+        PrimType::Int64 => IDLValue::Int64(json2decimal(json)?.parse()?),
+        PrimType::Float32 => IDLValue::Float32(json2f64(json, options)? as f32),
+        PrimType::Float64 => IDLValue::Float64(json2f64(json, options)?),
+        PrimType::Bool => IDLValue::Bool(
+            json.as_bool()
+                .with_context(|| format!("Expected a boolean, got: {json}"))?,
+        ),
+        PrimType::Text => IDLValue::Text(
+            json.as_str()
+                .with_context(|| format!("Expected a string, got: {json}"))?
+                .to_string(),
+        ),
+        PrimType::Null => match json {
+            JsonValue::Null => IDLValue::Null,
+            _ => bail!("Expected null, got: {json}"),
+        },
+        // Any value may be given for a reserved type.
+        PrimType::Reserved => IDLValue::Reserved,
+        PrimType::Empty => bail!("There are no values of the empty type"),
+    })
+}
+
+/// Gets the digits of an integer given as a decimal string or as a number.
+///
+/// Underscores used as digit separators are removed.
+fn json2decimal(json: &JsonValue) -> anyhow::Result<String> {
+    match json {
+        JsonValue::String(decimal) => Ok(decimal.replace('_', "")),
+        JsonValue::Number(number) if number.is_i64() || number.is_u64() => Ok(number.to_string()),
+        _ => bail!("Expected an integer as a decimal string, got: {json}"),
+    }
+}
+
+/// Gets an unsigned integer given as a number.
+fn json2u64(json: &JsonValue) -> anyhow::Result<u64> {
+    json.as_u64()
+        .with_context(|| format!("Expected an unsigned integer, got: {json}"))
+}
+
+/// Gets a signed integer given as a number.
+fn json2i64(json: &JsonValue) -> anyhow::Result<i64> {
+    json.as_i64()
+        .with_context(|| format!("Expected an integer, got: {json}"))
+}
+
+/// Gets a float given as a number or, if not finite, as `"NaN"`, `"Infinity"` or `"-Infinity"`.
+///
+/// If `options.non_finite_floats_as_null` is set, `null` is taken to be NaN, as which infinity it was is lost.
+fn json2f64(json: &JsonValue, options: &Idl2JsonOptions) -> anyhow::Result<f64> {
+    match json {
+        JsonValue::Number(number) => number
+            .as_f64()
+            .with_context(|| format!("Could not parse {number} as a float")),
+        JsonValue::String(name) if name == "NaN" => Ok(f64::NAN),
+        JsonValue::String(name) if name == "Infinity" => Ok(f64::INFINITY),
+        JsonValue::String(name) if name == "-Infinity" => Ok(f64::NEG_INFINITY),
+        JsonValue::Null if options.non_finite_floats_as_null => Ok(f64::NAN),
+        _ => bail!("Expected a float, got: {json}"),
+    }
+}
+
+/// Gets a principal given as text.
+pub(crate) fn json2principal(json: &JsonValue) -> anyhow::Result<Principal> {
+    let text = json
+        .as_str()
+        .with_context(|| format!("Expected a principal as a string, got: {json}"))?;
+    Principal::from_text(text).with_context(|| format!("Invalid principal: {text}"))
+}
+
+/// Gets bytes in any of the formats that the options may produce.
+fn json2bytes(json: &JsonValue, options: &Idl2JsonOptions) -> anyhow::Result<Vec<u8>> {
+    let bytes_formats = [
+        Some(options.bytes_as.unwrap_or_default()),
+        options.long_bytes_as.map(|(_, bytes_format)| bytes_format),
+    ];
+    match json {
+        JsonValue::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                item.as_u64()
+                    .and_then(|byte| u8::try_from(byte).ok())
+                    .with_context(|| {
+                        format!("Expected byte #{index} to be a number from 0 to 255, got: {item}")
+                    })
+            })
+            .collect(),
+        JsonValue::String(string) => string2bytes(string, &bytes_formats, options),
+        _ => bail!("Expected bytes as an array of numbers, got: {json}"),
+    }
+}
+
+/// Gets bytes given as a string, in any of the formats that the options may produce.
+///
+/// If bytes may be recognized as principals or text, a string is taken to be, in order of
+/// preference, a principal, hex or text.  Text that looks like a principal or hex is ambiguous,
+/// so lossless JSON should be used if the bytes must be recovered exactly.
+fn string2bytes(
+    string: &str,
+    bytes_formats: &[Option<BytesFormat>],
+    options: &Idl2JsonOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let may_be_principal =
+        !options.principal_bytes_lengths.is_empty() || !options.principal_bytes_paths.is_empty();
+    if may_be_principal {
+        if let Ok(principal) = Principal::from_text(string) {
+            return Ok(principal.as_slice().to_vec());
+        }
+    }
+    if bytes_formats.contains(&Some(BytesFormat::Hex)) {
+        match hex2bytes(string) {
+            Ok(bytes) => return Ok(bytes),
+            Err(err) if !options.utf8_bytes_as_text => return Err(err),
+            Err(_) => {}
+        }
+    }
+    if options.utf8_bytes_as_text {
+        return Ok(string.as_bytes().to_vec());
+    }
+    #[cfg(feature = "crypto")]
+    if bytes_formats.contains(&Some(BytesFormat::Sha256)) {
+        bail!("Bytes represented by their hash cannot be converted back to bytes")
+    }
+    bail!("Expected bytes as an array of numbers, got: {string:?}")
+}
+
+/// Decodes a hex string, which may start with `0x`, as in YAML.
+pub(crate) fn hex2bytes(hex: &str) -> anyhow::Result<Vec<u8>> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    if hex.len() % 2 != 0 {
+        bail!("Expected an even number of hex digits, got {}", hex.len());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| {
+            hex.get(index..index + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .with_context(|| format!("Invalid hex at position {index}"))
+        })
+        .collect()
+}
+
+/// Converts a JSON object to a record.
+fn json2record(
+    json: &JsonValue,
+    field_types: &[TypeField],
+    options: &Idl2JsonOptions,
+) -> anyhow::Result<IDLValue> {
+    let JsonValue::Object(object) = json else {
+        bail!("Expected a record as an object, got: {json}");
+    };
+    if let Some(key) = object.keys().find(|key| {
+        !field_types
+            .iter()
+            .any(|field_type| field_type.label.to_string() == **key)
+    }) {
+        bail!("Unknown record field: {key}");
+    }
+    let mut fields = Vec::with_capacity(object.len());
+    for field_type in field_types {
+        let key = field_type.label.to_string();
+        match object.get(&key) {
+            Some(value) => fields.push(IDLField {
+                id: field_type.label.clone(),
+                val: json2idl(value, &field_type.typ, options)
+                    .with_context(|| format!("Failed to parse value for key '{key}'"))?,
+            }),
+            None if default_value(&field_type.typ, options).is_some() => {}
+            None => bail!("Missing key: {key}"),
+        }
+    }
+    fields.sort_by_key(|field| field.id.get_id());
+    Ok(IDLValue::Record(fields))
+}
+
+/// Converts a JSON object with a single key to a variant.
+fn json2variant(
+    json: &JsonValue,
+    field_types: &[TypeField],
+    options: &Idl2JsonOptions,
+) -> anyhow::Result<IDLValue> {
+    let (key, value) = match json {
+        JsonValue::Object(object) if object.len() == 1 => object
+            .iter()
+            .next()
+            .context("Expected an object with a single key")?,
+        _ => bail!("Expected a variant as an object with a single key, got: {json}"),
+    };
+    // The index is the position of the field in the type, with fields sorted by id.
+    let mut field_types: Vec<&TypeField> = field_types.iter().collect();
+    field_types.sort_by_key(|field_type| field_type.label.get_id());
+    let (index, field_type) = field_types
+        .iter()
+        .enumerate()
+        .find(|(_, field_type)| field_type.label.to_string() == *key)
+        .with_context(|| format!("Unknown variant: {key}"))?;
+    let field = IDLField {
+        id: field_type.label.clone(),
+        val: json2idl(value, &field_type.typ, options)
+            .with_context(|| format!("Failed to convert variant {key}"))?,
+    };
+    Ok(IDLValue::Variant(VariantValue(
+        Box::new(field),
+        index as u64,
+    )))
+}
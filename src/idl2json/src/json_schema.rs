@@ -0,0 +1,243 @@
+//! JSON schemas describing the JSON produced by the typed conversions.
+use crate::{
+    bytes::may_recognize_bytes,
+    generic_value::is_generic_value_type,
+    redact::is_redacted_type,
+    truncate::{element_schema, text_schema},
+    typed_conversion::{default_value, get_type_from_any},
+    BytesFormat, Idl2JsonOptions, JsonValue,
+};
+use candid_parser::types::{IDLType, IDLTypes, PrimType, TypeField};
+use serde_json::{json, Map};
+
+/// The JSON schema dialect of the generated schemas.
+const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Returns a JSON schema for the JSON produced by `idl2json_with_weak_names()` for the given type.
+///
+/// - The schema reflects the options, such as how bytes are represented.
+/// - Named types are resolved using the types in `options.prog` and placed in `$defs`.
+/// - Named types that cannot be found are described by the schema that accepts anything,
+///   as values of those types are converted without the benefit of type information.
+pub fn idl_type2json_schema(idl_type: &IDLType, options: &Idl2JsonOptions) -> JsonValue {
+    let mut builder = SchemaBuilder::new(options);
+    let schema = builder.schema(idl_type);
+    builder.root(schema)
+}
+
+/// Returns a JSON schema for the JSON produced by `idl_args2json_with_weak_names()` for the given types.
+pub fn idl_types2json_schema(idl_types: &IDLTypes, options: &Idl2JsonOptions) -> JsonValue {
+    let mut builder = SchemaBuilder::new(options);
+    let schema = json!({
+        "type": "array",
+        "prefixItems": idl_types.args.iter().map(|idl_type| builder.schema(idl_type)).collect::<Vec<_>>(),
+    });
+    builder.root(schema)
+}
+
+/// Creates a JSON schema, collecting the definitions of named types on the way.
+struct SchemaBuilder<'a> {
+    options: &'a Idl2JsonOptions,
+    /// Schemas of the named types encountered so far.
+    defs: Map<String, JsonValue>,
+}
+
+impl<'a> SchemaBuilder<'a> {
+    fn new(options: &'a Idl2JsonOptions) -> Self {
+        SchemaBuilder {
+            options,
+            defs: Map::new(),
+        }
+    }
+
+    /// Adds the schema dialect and the definitions of named types to the top level schema.
+    fn root(self, schema: JsonValue) -> JsonValue {
+        let mut root = Map::new();
+        root.insert("$schema".to_string(), json!(SCHEMA_DIALECT));
+        match schema {
+            JsonValue::Object(schema) => root.extend(schema),
+            schema => {
+                root.insert("allOf".to_string(), json!([schema]));
+            }
+        }
+        if !self.defs.is_empty() {
+            root.insert("$defs".to_string(), JsonValue::Object(self.defs));
+        }
+        JsonValue::Object(root)
+    }
+
+    /// The schema for a type.
+    fn schema(&mut self, idl_type: &IDLType) -> JsonValue {
+        if is_redacted_type(idl_type, self.options) {
+            // Redacted values are replaced by a placeholder or hash.
+            return json!({ "type": "string" });
+        }
+        if is_generic_value_type(idl_type, self.options) {
+            // Generic values rendered as plain JSON may be any JSON.
+            return json!({});
+        }
+        match idl_type {
+            IDLType::VarT(type_name) => {
+                if !self.defs.contains_key(type_name) {
+                    let Some(resolved_type) = get_type_from_any(&self.options.prog, type_name)
+                    else {
+                        return json!({});
+                    };
+                    // Add a placeholder first, so that recursive types terminate.
+                    self.defs.insert(type_name.clone(), json!({}));
+                    let schema = self.schema(&resolved_type);
+                    self.defs.insert(type_name.clone(), schema);
+                }
+                json!({ "$ref": format!("#/$defs/{type_name}") })
+            }
+            IDLType::PrimT(prim_type) => prim_schema(prim_type, self.options),
+            IDLType::OptT(item_type) => json!({
+                "type": "array",
+                "items": self.schema(item_type),
+                "maxItems": 1,
+            }),
+            IDLType::VecT(item_type) => match &**item_type {
+                IDLType::PrimT(PrimType::Nat8) => self.bytes_schema(),
+                _ => json!({
+                    "type": "array",
+                    "items": element_schema(self.schema(item_type), self.options),
+                }),
+            },
+            IDLType::RecordT(fields) => self.record_schema(fields),
+            IDLType::VariantT(fields) => json!({
+                "oneOf": fields
+                    .iter()
+                    .map(|field| {
+                        let key = field.label.to_string();
+                        json!({
+                            "type": "object",
+                            "properties": { key.clone(): self.schema(&field.typ) },
+                            "required": [key],
+                            "additionalProperties": false,
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+            }),
+            IDLType::FuncT(_) => json!({
+                "type": "object",
+                "properties": {
+                    "principal": { "type": "string" },
+                    "code": { "type": "string" },
+                },
+                "required": ["principal", "code"],
+            }),
+            IDLType::ServT(_) | IDLType::PrincipalT => json!({ "type": "string" }),
+            // A class is not the type of a value.
+            IDLType::ClassT(_, _) => json!({}),
+        }
+    }
+
+    /// The schema for a record.
+    ///
+    /// Fields that Candid subtyping allows to be absent are not required, unless they are filled in.
+    fn record_schema(&mut self, fields: &[TypeField]) -> JsonValue {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for field in fields {
+            let key = field.label.to_string();
+            properties.insert(key.clone(), self.schema(&field.typ));
+            if self.options.fill_missing_fields || default_value(&field.typ, self.options).is_none()
+            {
+                required.push(JsonValue::String(key));
+            }
+        }
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        })
+    }
+
+    /// The schema for `blob` and `vec nat8`.
+    ///
+    /// Bytes recognized as text or principals are strings.
+    fn bytes_schema(&self) -> JsonValue {
+        let short_bytes_format = self.options.bytes_as.unwrap_or_default();
+        let mut schemas = vec![bytes_format_schema(&short_bytes_format)];
+        match self.options.long_bytes_as {
+            Some((_, long_bytes_format)) if long_bytes_format != short_bytes_format => {
+                schemas.push(bytes_format_schema(&long_bytes_format));
+            }
+            _ => {}
+        }
+        if may_recognize_bytes(self.options) {
+            schemas.push(json!({ "type": "string" }));
+        }
+        match &schemas[..] {
+            [schema] => schema.clone(),
+            _ => json!({ "anyOf": schemas }),
+        }
+    }
+}
+
+/// The schema for bytes in the given format.
+fn bytes_format_schema(bytes_format: &BytesFormat) -> JsonValue {
+    match bytes_format {
+        BytesFormat::Numbers => json!({
+            "type": "array",
+            "items": { "type": "integer", "minimum": 0, "maximum": u8::MAX },
+        }),
+        BytesFormat::Hex => json!({
+            "type": "string",
+            "pattern": "^([0-9a-f]{2})*$",
+        }),
+        #[cfg(feature = "crypto")]
+        BytesFormat::Sha256 => json!({
+            "type": "string",
+            "pattern": "^Bytes with sha256: [0-9a-f]{64}$",
+        }),
+    }
+}
+
+/// The schema for a primitive type.
+fn prim_schema(prim_type: &PrimType, options: &Idl2JsonOptions) -> JsonValue {
+    match prim_type {
+        // Big integers are given as decimal strings, possibly with underscores as digit separators.
+        PrimType::Nat => json!({ "type": "string", "pattern": "^[0-9][0-9_]*$" }),
+        PrimType::Int => json!({ "type": "string", "pattern": "^[+-]?[0-9][0-9_]*$" }),
+        PrimType::Nat8 => integer_schema(u8::MIN, u8::MAX),
+        PrimType::Nat16 => integer_schema(u16::MIN, u16::MAX),
+        PrimType::Nat32 => integer_schema(u32::MIN, u32::MAX),
+        PrimType::Int8 => integer_schema(i8::MIN, i8::MAX),
+        PrimType::Int16 => integer_schema(i16::MIN, i16::MAX),
+        PrimType::Int32 => integer_schema(i32::MIN, i32::MAX),
+        // 64 bit integers are given as strings, as they may be too large for JSON numbers.
+        PrimType::Nat64 => json!({ "type": "string", "pattern": "^[0-9]+$" }),
+        PrimType::Int64 => json!({ "type": "string", "pattern": "^-?[0-9]+$" }),
+        PrimType::Float32 | PrimType::Float64 => float_schema(options),
+        PrimType::Bool => json!({ "type": "boolean" }),
+        PrimType::Text => text_schema(options),
+        PrimType::Null => json!({ "type": "null" }),
+        PrimType::Reserved => json!({ "const": "null : reserved" }),
+        // There are no values of the empty type.
+        PrimType::Empty => json!(false),
+    }
+}
+
+/// The schema for a float, which may not be finite.
+fn float_schema(options: &Idl2JsonOptions) -> JsonValue {
+    if options.non_finite_floats_as_null {
+        json!({ "type": ["number", "null"] })
+    } else {
+        json!({
+            "anyOf": [
+                { "type": "number" },
+                { "enum": ["NaN", "Infinity", "-Infinity"] },
+            ],
+        })
+    }
+}
+
+/// The schema for an integer in the given range.
+fn integer_schema<T: Into<i64>>(minimum: T, maximum: T) -> JsonValue {
+    json!({
+        "type": "integer",
+        "minimum": minimum.into(),
+        "maximum": maximum.into(),
+    })
+}
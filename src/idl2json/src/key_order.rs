@@ -0,0 +1,184 @@
+//! Ordering of the keys of JSON objects.
+use crate::{Idl2JsonOptions, KeyOrder};
+use candid::idl_hash;
+use candid_parser::{
+    token::{Token, Tokenizer},
+    types::TypeField,
+};
+use serde_json::value::Value as JsonValue;
+use std::collections::BTreeMap;
+
+/// A field of a JSON object: The candid field id, the JSON key and the JSON value.
+pub(crate) type ObjectField = (u32, String, JsonValue);
+
+/// The order in which record fields are declared in `.did` files.
+///
+/// Parsing a `.did` file into an `IDLProg` sorts record fields by hash, so the
+/// declaration order is recovered from the `.did` source instead.
+///
+/// Records are identified by their set of field labels.  If several records have
+/// the same labels declared in different orders, the first declaration wins.
+#[derive(Clone, Debug, Default)]
+pub struct DeclaredFieldOrder {
+    /// Field ids in declaration order, keyed by the sorted field ids.
+    orders: BTreeMap<Vec<u32>, Vec<u32>>,
+}
+
+impl DeclaredFieldOrder {
+    /// Gets the declaration order of all records in the given `.did` source.
+    pub fn from_did(did: &str) -> Self {
+        let mut declared_field_order = Self::default();
+        declared_field_order.add_did(did);
+        declared_field_order
+    }
+
+    /// Adds the declaration order of all records in the given `.did` source.
+    ///
+    /// Note: The source is expected to be a valid `.did` file.  Anything that cannot
+    /// be tokenized is skipped.
+    pub fn add_did(&mut self, did: &str) {
+        /// A `{ }` block in the source.
+        struct Block {
+            /// Whether the block contains the fields of a record.
+            is_record: bool,
+            /// The depth of parentheses at which the block was opened.
+            paren_depth: usize,
+            /// The ids of the named fields declared directly in the block.
+            field_ids: Vec<u32>,
+        }
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut paren_depth = 0;
+        let mut previous: Option<Token> = None;
+        for (_, token, _) in Tokenizer::new(did).filter_map(Result::ok) {
+            match &token {
+                Token::LBrace => blocks.push(Block {
+                    is_record: previous == Some(Token::Record),
+                    paren_depth,
+                    field_ids: vec![],
+                }),
+                Token::RBrace => {
+                    if let Some(block) = blocks.pop() {
+                        if block.is_record {
+                            self.insert(block.field_ids);
+                        }
+                    }
+                }
+                Token::LParen => paren_depth += 1,
+                Token::RParen => paren_depth = paren_depth.saturating_sub(1),
+                Token::Colon => {
+                    if let Some(block) = blocks.last_mut() {
+                        if block.is_record && block.paren_depth == paren_depth {
+                            if let Some(field_id) = previous.as_ref().and_then(field_id) {
+                                block.field_ids.push(field_id);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+            previous = Some(token);
+        }
+    }
+
+    /// Records the declaration order of one record.
+    fn insert(&mut self, field_ids: Vec<u32>) {
+        if field_ids.len() > 1 {
+            let mut sorted_ids = field_ids.clone();
+            sorted_ids.sort_unstable();
+            self.orders.entry(sorted_ids).or_insert(field_ids);
+        }
+    }
+
+    /// Gets the declaration order of a record with the given fields, if known.
+    fn get(&self, record_types: &[TypeField]) -> Option<&[u32]> {
+        let mut sorted_ids: Vec<u32> = record_types
+            .iter()
+            .map(|field_type| field_type.label.get_id())
+            .collect();
+        sorted_ids.sort_unstable();
+        self.orders.get(&sorted_ids).map(Vec::as_slice)
+    }
+}
+
+/// The field id of a field label token.
+fn field_id(token: &Token) -> Option<u32> {
+    match token {
+        Token::Id(name) | Token::Text(name) => Some(idl_hash(name)),
+        Token::Decimal(number) => number.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Creates a JSON object with the keys in the order given by `options.key_order`.
+///
+/// The record type, if provided, determines the declaration order.  Fields that are
+/// not in the record type follow the declared fields, in hash order.
+pub(crate) fn json_object(
+    mut fields: Vec<ObjectField>,
+    record_types: Option<&[TypeField]>,
+    options: &Idl2JsonOptions,
+) -> JsonValue {
+    sort_fields(&mut fields, record_types, options);
+    JsonValue::Object(
+        fields
+            .into_iter()
+            .map(|(_, key, value)| (key, value))
+            .collect(),
+    )
+}
+
+/// Sorts fields, given by candid field id and JSON key, in the order given by `options.key_order`.
+pub(crate) fn sort_fields<T>(
+    fields: &mut [(u32, String, T)],
+    record_types: Option<&[TypeField]>,
+    options: &Idl2JsonOptions,
+) {
+    match options.key_order {
+        KeyOrder::Alphabetical => fields.sort_by(|(_, left, _), (_, right, _)| left.cmp(right)),
+        KeyOrder::Hash => fields.sort_by_key(|(field_id, _, _)| *field_id),
+        KeyOrder::Declaration => {
+            let record_types = record_types.unwrap_or_default();
+            let declared_ids: Vec<u32> = options
+                .declared_field_order
+                .get(record_types)
+                .map(<[u32]>::to_vec)
+                .unwrap_or_else(|| {
+                    record_types
+                        .iter()
+                        .map(|field_type| field_type.label.get_id())
+                        .collect()
+                });
+            fields.sort_by_key(|(field_id, _, _)| {
+                let position = declared_ids
+                    .iter()
+                    .position(|declared_id| declared_id == field_id)
+                    .unwrap_or(declared_ids.len());
+                (position, *field_id)
+            });
+        }
+    }
+}
+
+/// Creates the JSON object representing a func reference.
+pub(crate) fn func_object(
+    principal: String,
+    method: String,
+    options: &Idl2JsonOptions,
+) -> JsonValue {
+    json_object(
+        vec![
+            (
+                idl_hash("principal"),
+                "principal".to_string(),
+                JsonValue::String(principal),
+            ),
+            (
+                idl_hash("code"),
+                "code".to_string(),
+                JsonValue::String(method),
+            ),
+        ],
+        None,
+        options,
+    )
+}
@@ -7,13 +7,42 @@
 
 mod bytes;
 pub mod candid_types;
+mod canonical;
+mod check;
+mod diff;
+mod format;
+mod generic_value;
+mod json2idl;
+mod json_schema;
+mod key_order;
+mod lossless;
+mod path;
 pub mod polyfill;
+mod redact;
+mod select;
+mod summary;
+mod table;
+mod truncate;
 mod typed_conversion;
+mod typescript;
 mod untyped_conversion;
 
 use candid_parser::types::IDLProg;
+pub use canonical::to_canonical_json;
+pub use check::{check2json, idl_args_check, idl_check, Nonconformance};
+pub use diff::{diff2json_patch, diff2text, idl_args_diff, idl_diff, DiffStep, ValueChange};
+pub use format::serialize;
+pub use json2idl::{json2idl, json_args2idl};
+pub use json_schema::{idl_type2json_schema, idl_types2json_schema};
+pub use key_order::DeclaredFieldOrder;
+pub use lossless::{lossless_json2idl, lossless_json2idl_args};
+pub use path::PathPattern;
+pub use select::{idl2json_select, idl2json_with_weak_names_select};
 pub use serde_json::Value as JsonValue;
-pub use typed_conversion::{idl2json_with_weak_names, idl_args2json_with_weak_names};
+pub use summary::{idl2summary, idl2summary_with_weak_names};
+pub use table::{idl2table, idl2table_with_weak_names};
+pub use typed_conversion::{idl2json_with_weak_names, idl_args2json_with_weak_names, resolve_type};
+pub use typescript::{idl_progs2typescript, idl_type2typescript, idl_types2typescript};
 pub use untyped_conversion::{idl2json, idl_args2json};
 #[cfg(test)]
 mod test;
@@ -25,6 +54,16 @@ pub struct Idl2JsonOptions {
     pub bytes_as: Option<BytesFormat>,
     /// How to represent `Vec<u8>` of at least some given length.
     pub long_bytes_as: Option<(usize, BytesFormat)>,
+    /// Represent `Vec<u8>` that is UTF-8 text as a string, instead of as given by `bytes_as`.
+    ///
+    /// Only non-empty text without control characters, other than whitespace, is recognized.
+    pub utf8_bytes_as_text: bool,
+    /// Represent `Vec<u8>` of any of these lengths as principal text, instead of as given by `bytes_as`.
+    ///
+    /// Principals are at most 29 bytes long; longer bytes are not affected.
+    pub principal_bytes_lengths: Vec<usize>,
+    /// Represent `Vec<u8>` at any of these paths as principal text, instead of as given by `bytes_as`.
+    pub principal_bytes_paths: Vec<PathPattern>,
     /// Type definitions.
     ///
     /// Note:
@@ -36,8 +75,64 @@ pub struct Idl2JsonOptions {
     ///   `idl2json` will use the first match it finds.  It is the
     ///   caller's responsibility to ensure that there are no conflicting definitions.
     pub prog: Vec<IDLProg>,
+    /// The format in which to serialize converted values.  See `serialize()`.
+    pub output_format: OutputFormat,
     /// Compact JSON, without formatting whitespace.
     pub compact: bool,
+    /// Represent floats that are not finite as `null`, instead of as `"NaN"`, `"Infinity"` and `"-Infinity"`.
+    pub non_finite_floats_as_null: bool,
+    /// Canonical JSON, as defined by RFC 8785, for hashing and signing.  See `to_canonical_json()`.
+    ///
+    /// This implies compact JSON and overrides the key order.
+    pub canonical: bool,
+    /// Add record fields that are declared in the type but absent from the value.
+    ///
+    /// Only fields that Candid subtyping allows to be missing are added:
+    /// - `opt T` is added as `[]`
+    /// - `null` is added as `null`
+    /// - `reserved` is added as `"null : reserved"`, as reserved values are always represented
+    ///
+    /// This applies only to typed conversions.
+    pub fill_missing_fields: bool,
+    /// How to order the keys of JSON objects.
+    pub key_order: KeyOrder,
+    /// The order in which record fields are declared in the `.did` files of `prog`.
+    ///
+    /// This is used for `KeyOrder::Declaration`.  If the order of a record is not known,
+    /// the order of the fields in the type is used.
+    pub declared_field_order: DeclaredFieldOrder,
+    /// Tag every value with its candid type, so that the JSON can be converted back to
+    /// exactly the same candid with `lossless_json2idl()`.
+    ///
+    /// This overrides `bytes_as` and `long_bytes_as`: bytes are given in hex.  Bytes recognized as
+    /// text or principals are tagged as such, so they remain distinct.
+    pub lossless: bool,
+    /// Render generic values, such as the ICRC-3 and ICRC-16 `Value` types, as plain JSON.
+    ///
+    /// - Maps become objects, arrays become arrays and the contents are converted as usual.
+    /// - Types are recognized by their shape: a variant with a `Map` case and no cases
+    ///   other than those of the ICRC-3 and ICRC-16 values.
+    /// - Without type information, only `Map` and `Array` values are recognized.
+    pub flatten_generic_values: bool,
+    /// Names of types to render as plain JSON, as with `flatten_generic_values`, whatever their shape.  Aliases of
+    /// these types, and the types that they alias, are rendered the same way.
+    pub generic_value_types: Vec<String>,
+    /// Redact the values at any of these paths, replacing them as given by `redact_as`.
+    ///
+    /// JSON schemas and TypeScript declarations do not reflect redacted paths, only `redact_types`.
+    pub redact_paths: Vec<PathPattern>,
+    /// Redact the values of these named types, such as `SessionKey`, in typed conversions.
+    pub redact_types: Vec<String>,
+    /// How to represent redacted values.
+    pub redact_as: RedactFormat,
+    /// Show at most this many elements of a vector, followed by `{"…": "4213 more elements"}`.
+    ///
+    /// Bytes are not affected, nor is lossless JSON.
+    pub max_vec_elements: Option<usize>,
+    /// Show at most this many characters of a text, as `{"text": "The first characters", "…": "4213 more characters"}`.
+    ///
+    /// Lossless JSON is not affected.
+    pub max_text_length: Option<usize>,
 }
 
 /// Options for how to represent `Vec<u8>`
@@ -57,3 +152,86 @@ pub enum BytesFormat {
     /// Data is hashed:  "sha512:abbabababababababbababababab"
     Sha256,
 }
+
+/// Options for how to represent redacted values
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "clap", clap(rename_all = "kebab_case"))]
+pub enum RedactFormat {
+    /// Values are replaced by a placeholder: `"[redacted]"`
+    #[default]
+    Placeholder,
+    #[cfg(feature = "crypto")]
+    /// Values are replaced by a hash of their candid text, so that equal values may be recognized:
+    /// `"[redacted] sha256:abbabababababababbababababab"`
+    Sha256,
+}
+
+/// Formats in which converted values may be serialized
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "clap", clap(rename_all = "kebab_case"))]
+pub enum OutputFormat {
+    /// JSON
+    #[default]
+    Json,
+    #[cfg(feature = "formats")]
+    /// YAML, as read by yaml2candid: numbers that fit in 64 bits are numbers, rather than strings, and hex bytes
+    /// start with `0x`
+    Yaml,
+    #[cfg(feature = "formats")]
+    /// CBOR (RFC 8949), a binary format
+    Cbor,
+    #[cfg(feature = "formats")]
+    /// MessagePack, a binary format
+    Msgpack,
+    #[cfg(feature = "formats")]
+    /// TOML, for values that are records without nulls
+    Toml,
+    /// CSV (RFC 4180), with a row for each element of a vector of records.  See `idl2table()`.
+    Csv,
+    /// Tab separated values, with a row for each element of a vector of records.  See `idl2table()`.
+    Tsv,
+}
+
+impl OutputFormat {
+    /// Whether the format is binary, rather than text.
+    pub fn is_binary(self) -> bool {
+        #[cfg(feature = "formats")]
+        return matches!(self, OutputFormat::Cbor | OutputFormat::Msgpack);
+        #[cfg(not(feature = "formats"))]
+        false
+    }
+
+    /// Whether the format is a table, for the output of `idl2table()`.
+    pub fn is_tabular(self) -> bool {
+        matches!(self, OutputFormat::Csv | OutputFormat::Tsv)
+    }
+
+    /// Whether the format is YAML, with numbers and bytes written as yaml2candid reads them.
+    pub fn is_yaml(self) -> bool {
+        #[cfg(feature = "formats")]
+        return matches!(self, OutputFormat::Yaml);
+        #[cfg(not(feature = "formats"))]
+        false
+    }
+}
+
+/// Options for how to order the keys of JSON objects
+///
+/// Orders other than `Alphabetical` need the `preserve_order` feature.  Without it, `serde_json` sorts the keys of
+/// every object.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "clap", clap(rename_all = "kebab_case"))]
+pub enum KeyOrder {
+    /// Keys are sorted alphabetically: `{"a":1,"b":2}`
+    #[default]
+    Alphabetical,
+    /// Keys are in wire order, which is sorted by field hash.
+    Hash,
+    /// Keys are in the order in which the fields are declared in the type.
+    ///
+    /// Fields that are not in the type follow, in hash order.  Without a type, this is the same as `Hash`.
+    Declaration,
+}
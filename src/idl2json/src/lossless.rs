@@ -0,0 +1,338 @@
+//! Lossless conversion of candid to JSON, in which every value is tagged with its candid type.
+//!
+//! Every value is represented by an object with a single key, the type tag:
+//! - `{"nat64": "5"}`, `{"float32": 0.5}`, `{"text": "Hi"}`, ...
+//! - `{"opt": []}`, `{"opt": [value]}`, `{"vec": [value, ...]}`, `{"blob": "a4b7"}`
+//! - `{"blob_text": "Hi"}`, `{"blob_principal": "aaaaa-aa"}` for blobs recognized as text or principals
+//! - `{"record": {"name": value, ...}}`, `{"variant": {"name": value}}`
+//!
+//! Non-finite floats are given by their bits in hex, as in `{"float64": "0x7ff8000000000000"}`.
+//!
+//! Record fields and variants are named where the name is known, else they are given by their numeric id.
+use crate::{
+    bytes::{format_blob, recognize_bytes, RecognizedBytes},
+    json2idl::{hex2bytes, json2principal},
+    key_order::{json_object, ObjectField},
+    path::ValuePath,
+    redact::{is_redacted, redact},
+    typed_conversion::resolve_type,
+    BytesFormat, Idl2JsonOptions, JsonValue,
+};
+use anyhow::{anyhow, bail, Context};
+use candid::{
+    types::{
+        value::{IDLField, IDLValue, VariantValue},
+        Label,
+    },
+    IDLArgs, Int, Nat,
+};
+use candid_parser::types::{IDLType, TypeField};
+use std::{convert::TryFrom, str::FromStr};
+
+/// Converts a candid IDLValue to lossless JSON.
+///
+/// The type, if provided, is used only to name record fields and variants.
+pub(crate) fn idl2lossless_json(
+    idl: &IDLValue,
+    idl_type: Option<&IDLType>,
+    path: &ValuePath,
+    options: &Idl2JsonOptions,
+) -> JsonValue {
+    if is_redacted(idl_type, path, options) {
+        // Redacted values cannot be converted back, so they are not tagged.
+        return redact(idl, options);
+    }
+    let idl_type = idl_type.and_then(|idl_type| resolve_type(idl_type, options));
+    let (tag, value) = match idl {
+        IDLValue::Bool(value) => ("bool", JsonValue::Bool(*value)),
+        IDLValue::Null => ("null", JsonValue::Null),
+        IDLValue::Text(value) => ("text", JsonValue::String(value.clone())),
+        // A number that was not annotated with a type when parsed.
+        IDLValue::Number(value) => ("number", JsonValue::String(value.clone())),
+        IDLValue::Float64(value) => ("float64", float2json(*value, value.to_bits())),
+        IDLValue::Float32(value) => (
+            "float32",
+            float2json(f64::from(*value), u64::from(value.to_bits())),
+        ),
+        IDLValue::Opt(value) => {
+            let item_type = match &idl_type {
+                Some(IDLType::OptT(item_type)) => Some(&**item_type),
+                _ => None,
+            };
+            (
+                "opt",
+                JsonValue::Array(vec![idl2lossless_json(value, item_type, path, options)]),
+            )
+        }
+        IDLValue::None => ("opt", JsonValue::Array(vec![])),
+        IDLValue::Vec(values) => {
+            let item_type = match &idl_type {
+                Some(IDLType::VecT(item_type)) => Some(&**item_type),
+                _ => None,
+            };
+            (
+                "vec",
+                JsonValue::Array(
+                    values
+                        .iter()
+                        .enumerate()
+                        .map(|(index, value)| {
+                            idl2lossless_json(value, item_type, &path.index(index), options)
+                        })
+                        .collect(),
+                ),
+            )
+        }
+        // Recognized blobs have tags of their own, so that they remain distinct from hex.
+        IDLValue::Blob(bytes) => match recognize_bytes(bytes, path, options) {
+            Some(RecognizedBytes::Principal(principal)) => {
+                ("blob_principal", JsonValue::String(principal.to_text()))
+            }
+            Some(RecognizedBytes::Text(text)) => ("blob_text", JsonValue::String(text.to_string())),
+            None => ("blob", format_blob(bytes, &BytesFormat::Hex)),
+        },
+        IDLValue::Record(fields) => {
+            let field_types = match &idl_type {
+                Some(IDLType::RecordT(field_types)) => Some(&field_types[..]),
+                _ => None,
+            };
+            let fields: Vec<ObjectField> = fields
+                .iter()
+                .map(|field| convert_field(field, field_types, path, options))
+                .collect();
+            ("record", json_object(fields, field_types, options))
+        }
+        IDLValue::Variant(VariantValue(field, _)) => {
+            let field_types = match &idl_type {
+                Some(IDLType::VariantT(field_types)) => Some(&field_types[..]),
+                _ => None,
+            };
+            let (_, key, value) = convert_field(field, field_types, path, options);
+            (
+                "variant",
+                JsonValue::Object(vec![(key, value)].into_iter().collect()),
+            )
+        }
+        IDLValue::Principal(principal) => ("principal", JsonValue::String(principal.to_text())),
+        IDLValue::Service(principal) => ("service", JsonValue::String(principal.to_text())),
+        IDLValue::Func(principal, method) => (
+            "func",
+            JsonValue::Object(
+                vec![
+                    (
+                        "principal".to_string(),
+                        JsonValue::String(principal.to_text()),
+                    ),
+                    ("method".to_string(), JsonValue::String(method.clone())),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+        ),
+        IDLValue::Int(value) => ("int", JsonValue::String(value.0.to_string())),
+        IDLValue::Nat(value) => ("nat", JsonValue::String(value.0.to_string())),
+        IDLValue::Nat8(value) => ("nat8", JsonValue::from(*value)),
+        IDLValue::Nat16(value) => ("nat16", JsonValue::from(*value)),
+        IDLValue::Nat32(value) => ("nat32", JsonValue::from(*value)),
+        IDLValue::Nat64(value) => ("nat64", JsonValue::String(value.to_string())),
+        IDLValue::Int8(value) => ("int8", JsonValue::from(*value)),
+        IDLValue::Int16(value) => ("int16", JsonValue::from(*value)),
+        IDLValue::Int32(value) => ("int32", JsonValue::from(*value)),
+        IDLValue::Int64(value) => ("int64", JsonValue::String(value.to_string())),
+        IDLValue::Reserved => ("reserved", JsonValue::Null),
+    };
+    JsonValue::Object(vec![(tag.to_string(), value)].into_iter().collect())
+}
+
+/// Returns a field as an (id, key, value) triple, naming the field from the type if possible.
+fn convert_field(
+    field: &IDLField,
+    field_types: Option<&[TypeField]>,
+    path: &ValuePath,
+    options: &Idl2JsonOptions,
+) -> ObjectField {
+    let field_id = field.id.get_id();
+    let field_type = field_types.and_then(|field_types| {
+        field_types
+            .iter()
+            .find(|field_type| field_type.label.get_id() == field_id)
+    });
+    let label = field_type.map_or(&field.id, |field_type| &field_type.label);
+    let key = label2key(label);
+    let value = idl2lossless_json(
+        &field.val,
+        field_type.map(|field_type| &field_type.typ),
+        &path.key(&key),
+        options,
+    );
+    (field_id, key, value)
+}
+
+/// The JSON key for a field label.
+///
+/// Names that could be mistaken for numeric ids are replaced by their id, which is
+/// all that is preserved in binary candid.
+fn label2key(label: &Label) -> String {
+    match label {
+        Label::Named(name) if name.parse::<u32>().is_err() => name.clone(),
+        label => label.get_id().to_string(),
+    }
+}
+
+/// The field label for a JSON key.
+fn key2label(key: &str) -> Label {
+    key.parse::<u32>()
+        .map(Label::Id)
+        .unwrap_or_else(|_| Label::Named(key.to_string()))
+}
+
+/// A float as a JSON number or, if not finite, as its bits in hex.
+fn float2json(value: f64, bits: u64) -> JsonValue {
+    serde_json::Number::from_f64(value)
+        .map(JsonValue::Number)
+        .unwrap_or_else(|| JsonValue::String(format!("{bits:#x}")))
+}
+
+/// Converts lossless JSON back to a candid IDLValue.
+pub fn lossless_json2idl(json: &JsonValue) -> anyhow::Result<IDLValue> {
+    let (tag, value) = match json {
+        JsonValue::Object(object) if object.len() == 1 => object
+            .iter()
+            .next()
+            .context("Expected an object with a single key")?,
+        _ => bail!(
+            "Expected a value tagged with its type, as an object with a single key, got: {json}"
+        ),
+    };
+    let idl = match tag.as_str() {
+        "bool" => IDLValue::Bool(
+            value
+                .as_bool()
+                .with_context(|| expected("a boolean", value))?,
+        ),
+        "null" => IDLValue::Null,
+        "text" => IDLValue::Text(json2str(value)?.to_string()),
+        "number" => IDLValue::Number(json2str(value)?.to_string()),
+        "float64" => IDLValue::Float64(match value {
+            JsonValue::String(bits) => f64::from_bits(hex2u64(bits)?),
+            _ => value.as_f64().with_context(|| expected("a float", value))?,
+        }),
+        "float32" => IDLValue::Float32(match value {
+            JsonValue::String(bits) => f32::from_bits(u32::try_from(hex2u64(bits)?)?),
+            _ => value.as_f64().with_context(|| expected("a float", value))? as f32,
+        }),
+        "opt" => match json2array(value)? {
+            [] => IDLValue::None,
+            [value] => IDLValue::Opt(Box::new(lossless_json2idl(value)?)),
+            _ => bail!("Expected an optional value with at most one element, got: {value}"),
+        },
+        "vec" => IDLValue::Vec(
+            json2array(value)?
+                .iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    lossless_json2idl(value)
+                        .with_context(|| format!("Failed to convert element #{index}"))
+                })
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        "blob" => IDLValue::Blob(hex2bytes(json2str(value)?)?),
+        "blob_text" => IDLValue::Blob(json2str(value)?.as_bytes().to_vec()),
+        "blob_principal" => IDLValue::Blob(json2principal(value)?.as_slice().to_vec()),
+        "record" => {
+            let mut fields = json2fields(value)?;
+            fields.sort_by_key(|field| field.id.get_id());
+            IDLValue::Record(fields)
+        }
+        "variant" => match &mut json2fields(value)?[..] {
+            [field] => IDLValue::Variant(VariantValue(Box::new(field.clone()), 0)),
+            _ => bail!("Expected a variant with a single key, got: {value}"),
+        },
+        "principal" => IDLValue::Principal(json2principal(value)?),
+        "service" => IDLValue::Service(json2principal(value)?),
+        "func" => IDLValue::Func(
+            json2principal(
+                value
+                    .get("principal")
+                    .with_context(|| expected("a principal", value))?,
+            )?,
+            json2str(
+                value
+                    .get("method")
+                    .with_context(|| expected("a method", value))?,
+            )?
+            .to_string(),
+        ),
+        "int" => IDLValue::Int(
+            Int::from_str(json2str(value)?).map_err(|err| anyhow!("Invalid int {value}: {err}"))?,
+        ),
+        "nat" => IDLValue::Nat(
+            Nat::from_str(json2str(value)?).map_err(|err| anyhow!("Invalid nat {value}: {err}"))?,
+        ),
+        "nat8" => IDLValue::Nat8(serde_json::from_value(value.clone())?),
+        "nat16" => IDLValue::Nat16(serde_json::from_value(value.clone())?),
+        "nat32" => IDLValue::Nat32(serde_json::from_value(value.clone())?),
+        "nat64" => IDLValue::Nat64(json2str(value)?.parse()?),
+        "int8" => IDLValue::Int8(serde_json::from_value(value.clone())?),
+        "int16" => IDLValue::Int16(serde_json::from_value(value.clone())?),
+        "int32" => IDLValue::Int32(serde_json::from_value(value.clone())?),
+        "int64" => IDLValue::Int64(json2str(value)?.parse()?),
+        "reserved" => IDLValue::Reserved,
+        _ => bail!("Unknown type tag: {tag}"),
+    };
+    Ok(idl)
+}
+
+/// Converts a lossless JSON array back to candid IDLArgs.
+pub fn lossless_json2idl_args(json: &JsonValue) -> anyhow::Result<IDLArgs> {
+    let args = json2array(json)?
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            lossless_json2idl(value).with_context(|| format!("Failed to convert argument #{index}"))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok(IDLArgs { args })
+}
+
+/// The fields of a record or variant.
+fn json2fields(json: &JsonValue) -> anyhow::Result<Vec<IDLField>> {
+    let JsonValue::Object(object) = json else {
+        bail!("{}", expected("an object", json));
+    };
+    object
+        .iter()
+        .map(|(key, value)| {
+            Ok(IDLField {
+                id: key2label(key),
+                val: lossless_json2idl(value)
+                    .with_context(|| format!("Failed to convert field '{key}'"))?,
+            })
+        })
+        .collect()
+}
+
+/// Gets a JSON array.
+fn json2array(json: &JsonValue) -> anyhow::Result<&[JsonValue]> {
+    json.as_array()
+        .map(Vec::as_slice)
+        .with_context(|| expected("an array", json))
+}
+
+/// Gets a JSON string.
+fn json2str(json: &JsonValue) -> anyhow::Result<&str> {
+    json.as_str().with_context(|| expected("a string", json))
+}
+
+/// Parses a hex number prefixed with `0x`.
+fn hex2u64(hex: &str) -> anyhow::Result<u64> {
+    let digits = hex
+        .strip_prefix("0x")
+        .with_context(|| format!("Expected float bits in hex prefixed with 0x, got: {hex}"))?;
+    Ok(u64::from_str_radix(digits, 16)?)
+}
+
+/// A message describing an unexpected value.
+fn expected(what: &str, json: &JsonValue) -> String {
+    format!("Expected {what}, got: {json}")
+}
@@ -0,0 +1,141 @@
+//! Paths to values inside candid values, and patterns that match them.
+//!
+//! A path is made of the names of record fields and variant tags, as they appear in the JSON,
+//! and the indices of vector elements.  Optional values do not add to the path, so a field
+//! `proposal : opt Proposal` is followed by the fields of `Proposal` directly: `proposal.action`.
+use std::{convert::Infallible, fmt, str::FromStr};
+
+/// A step from a value to a value inside it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum PathStep<'a> {
+    /// A record field or variant tag.
+    Key(&'a str),
+    /// A vector element.
+    Index(usize),
+}
+
+/// The path from a top level value to the value being converted.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct ValuePath<'a> {
+    /// The path to the containing value, and the step from it to this value.
+    parent: Option<(&'a ValuePath<'a>, PathStep<'a>)>,
+}
+
+impl<'a> ValuePath<'a> {
+    /// The path of a top level value.
+    pub(crate) const ROOT: ValuePath<'static> = ValuePath { parent: None };
+
+    /// The path of a record field or variant tag in this value.
+    pub(crate) fn key(&'a self, key: &'a str) -> Self {
+        ValuePath {
+            parent: Some((self, PathStep::Key(key))),
+        }
+    }
+
+    /// The path of a vector element in this value.
+    pub(crate) fn index(&'a self, index: usize) -> Self {
+        ValuePath {
+            parent: Some((self, PathStep::Index(index))),
+        }
+    }
+
+    /// The steps from the top level value to this value.
+    pub(crate) fn steps(&self) -> Vec<PathStep<'a>> {
+        let mut steps = Vec::new();
+        let mut path = self;
+        while let Some((parent, step)) = &path.parent {
+            steps.push(*step);
+            path = parent;
+        }
+        steps.reverse();
+        steps
+    }
+}
+
+/// A pattern that matches paths, such as `proposal.action.*.payload`.
+///
+/// - Steps are separated by dots.
+/// - A step is the name of a record field or variant tag, or the index of a vector element.
+/// - `*` matches any single step, such as any element of a vector or any variant tag.
+/// - The empty pattern matches the top level value.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PathPattern {
+    steps: Vec<PatternStep>,
+}
+
+/// A step in a path pattern.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum PatternStep {
+    /// Matches any step.
+    Any,
+    /// Matches a key, or an index given in decimal.
+    Name(String),
+}
+
+impl PathPattern {
+    /// Determines whether the pattern matches a path.
+    pub(crate) fn matches(&self, path: &ValuePath) -> bool {
+        let steps = path.steps();
+        steps.len() == self.steps.len()
+            && self
+                .steps
+                .iter()
+                .zip(&steps)
+                .all(|(pattern_step, step)| pattern_step.matches(step))
+    }
+
+    /// Determines whether the pattern may match a path inside the value at a path.
+    pub(crate) fn may_match_inside(&self, path: &ValuePath) -> bool {
+        let steps = path.steps();
+        steps.len() < self.steps.len()
+            && self
+                .steps
+                .iter()
+                .zip(&steps)
+                .all(|(pattern_step, step)| pattern_step.matches(step))
+    }
+}
+
+impl PatternStep {
+    fn matches(&self, step: &PathStep) -> bool {
+        match (self, step) {
+            (PatternStep::Any, _) => true,
+            (PatternStep::Name(name), PathStep::Key(key)) => name == key,
+            (PatternStep::Name(name), PathStep::Index(index)) => *name == index.to_string(),
+        }
+    }
+}
+
+impl FromStr for PathPattern {
+    type Err = Infallible;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        if pattern.is_empty() {
+            return Ok(PathPattern::default());
+        }
+        Ok(PathPattern {
+            steps: pattern
+                .split('.')
+                .map(|step| match step {
+                    "*" => PatternStep::Any,
+                    name => PatternStep::Name(name.to_string()),
+                })
+                .collect(),
+        })
+    }
+}
+
+impl fmt::Display for PathPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, step) in self.steps.iter().enumerate() {
+            if index > 0 {
+                write!(f, ".")?;
+            }
+            match step {
+                PatternStep::Any => write!(f, "*")?,
+                PatternStep::Name(name) => write!(f, "{name}")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -3,7 +3,7 @@
 /// Polyfills for the candid IDLProg struct.
 pub mod idl_prog {
     use candid_parser::{
-        types::{Dec, IDLType, IDLTypes},
+        types::{Binding, Dec, FuncType, IDLType, IDLTypes, TypeField},
         IDLProg,
     };
 
@@ -15,16 +15,38 @@ pub mod idl_prog {
 
     /// Gets a type defined in a program declarations section.
     pub fn get_type(prog: &IDLProg, key: &str) -> Option<IDLType> {
-        prog.decs.iter().find_map(|x| {
-            if let Dec::TypD(y) = x {
-                if y.id == key {
-                    return Some(y.typ.clone());
-                }
+        get_types(prog)
+            .find(|binding| binding.id == key)
+            .map(|binding| binding.typ.clone())
+    }
+
+    /// Gets the types defined in a program declarations section, in the order in which they are declared.
+    pub fn get_types(prog: &IDLProg) -> impl Iterator<Item = &Binding> {
+        prog.decs.iter().filter_map(|dec| {
+            if let Dec::TypD(binding) = dec {
+                Some(binding)
+            } else {
+                None
             }
-            None
         })
     }
 
+    /// Copies a prog, as IDLProg does not implement Clone.
+    pub fn clone(prog: &IDLProg) -> IDLProg {
+        IDLProg {
+            decs: prog
+                .decs
+                .iter()
+                .map(|dec| match dec {
+                    Dec::TypD(binding) => Dec::TypD(binding.clone()),
+                    Dec::ImportType(path) => Dec::ImportType(path.clone()),
+                    Dec::ImportServ(path) => Dec::ImportServ(path.clone()),
+                })
+                .collect(),
+            actor: prog.actor.clone(),
+        }
+    }
+
     /// Gets the arguments for creating a service.
     ///
     /// This will return None if the prog contains no service aka actor of type ClassT.
@@ -35,4 +57,326 @@ pub mod idl_prog {
             None
         }
     }
+
+    /// Gets the type of a method of the service.
+    ///
+    /// This will return None if the prog contains no service or the service has no such method.
+    pub fn get_method_type(prog: &IDLProg, method_name: &str) -> Option<FuncType> {
+        get_methods(prog)
+            .into_iter()
+            .find(|(name, _)| name == method_name)
+            .map(|(_, func_type)| func_type)
+    }
+
+    /// Gets the names and types of the methods of the service, in the order in which they are declared.
+    ///
+    /// This will return an empty list if the prog contains no service.
+    pub fn get_methods(prog: &IDLProg) -> Vec<(String, FuncType)> {
+        let service = prog
+            .actor
+            .as_ref()
+            .and_then(|actor| resolve(prog, actor))
+            .and_then(|actor| match actor {
+                IDLType::ClassT(_, service) => resolve(prog, &service),
+                service => Some(service),
+            });
+        let Some(IDLType::ServT(methods)) = service else {
+            return vec![];
+        };
+        methods
+            .iter()
+            .filter_map(|method| match resolve(prog, &method.typ)? {
+                IDLType::FuncT(func_type) => Some((method.id.clone(), func_type)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Replaces the names of types with their definitions, to the given depth of nested names.
+    ///
+    /// - With a depth of 0 the type is returned as it is.  Without a depth, names are replaced wherever possible.
+    /// - Names that are not defined in the prog are left in place, as are the names of recursive types where
+    ///   they recur.
+    pub fn expand_type(prog: &IDLProg, idl_type: &IDLType, depth: Option<usize>) -> IDLType {
+        expand(prog, idl_type, depth, &mut Vec::new())
+    }
+
+    /// Replaces the names of types with their definitions, except for the names that are being expanded.
+    fn expand(
+        prog: &IDLProg,
+        idl_type: &IDLType,
+        depth: Option<usize>,
+        expanding: &mut Vec<String>,
+    ) -> IDLType {
+        let expand_fields = |fields: &[TypeField], expanding: &mut Vec<String>| {
+            fields
+                .iter()
+                .map(|field| TypeField {
+                    label: field.label.clone(),
+                    typ: expand(prog, &field.typ, depth, expanding),
+                })
+                .collect()
+        };
+        match idl_type {
+            IDLType::VarT(name) => {
+                let definition = match depth {
+                    Some(0) => None,
+                    _ if expanding.contains(name) => None,
+                    _ => get_type(prog, name),
+                };
+                let Some(definition) = definition else {
+                    return idl_type.clone();
+                };
+                expanding.push(name.clone());
+                let expanded = expand(prog, &definition, depth.map(|depth| depth - 1), expanding);
+                expanding.pop();
+                expanded
+            }
+            IDLType::OptT(idl_type) => {
+                IDLType::OptT(Box::new(expand(prog, idl_type, depth, expanding)))
+            }
+            IDLType::VecT(idl_type) => {
+                IDLType::VecT(Box::new(expand(prog, idl_type, depth, expanding)))
+            }
+            IDLType::RecordT(fields) => IDLType::RecordT(expand_fields(fields, expanding)),
+            IDLType::VariantT(fields) => IDLType::VariantT(expand_fields(fields, expanding)),
+            IDLType::FuncT(func_type) => IDLType::FuncT(FuncType {
+                modes: func_type.modes.clone(),
+                args: expand_all(prog, &func_type.args, depth, expanding),
+                rets: expand_all(prog, &func_type.rets, depth, expanding),
+            }),
+            IDLType::ServT(methods) => IDLType::ServT(
+                methods
+                    .iter()
+                    .map(|method| Binding {
+                        id: method.id.clone(),
+                        typ: expand(prog, &method.typ, depth, expanding),
+                    })
+                    .collect(),
+            ),
+            IDLType::ClassT(args, service) => IDLType::ClassT(
+                expand_all(prog, args, depth, expanding),
+                Box::new(expand(prog, service, depth, expanding)),
+            ),
+            IDLType::PrimT(_) | IDLType::PrincipalT => idl_type.clone(),
+        }
+    }
+
+    /// Replaces the names of types with their definitions in each of the given types.
+    fn expand_all(
+        prog: &IDLProg,
+        idl_types: &[IDLType],
+        depth: Option<usize>,
+        expanding: &mut Vec<String>,
+    ) -> Vec<IDLType> {
+        idl_types
+            .iter()
+            .map(|idl_type| expand(prog, idl_type, depth, expanding))
+            .collect()
+    }
+
+    /// Follows type names until a type that is not a name is found.
+    ///
+    /// This will return None if a name is not defined in the prog or the names are cyclic.
+    fn resolve(prog: &IDLProg, idl_type: &IDLType) -> Option<IDLType> {
+        let mut idl_type = idl_type.clone();
+        for _ in 0..=prog.decs.len() {
+            match idl_type {
+                IDLType::VarT(name) => idl_type = get_type(prog, &name)?,
+                idl_type => return Some(idl_type),
+            }
+        }
+        None
+    }
+}
+
+/// Polyfills for the candid IDLType enum.
+pub mod idl_type {
+    use candid::types::{FuncMode, Label};
+    use candid_parser::types::{FuncType, IDLType, PrimType, TypeField};
+
+    /// Prints a type as candid, on one line.
+    pub fn to_string(idl_type: &IDLType) -> String {
+        let mut out = String::new();
+        write_type(&mut out, idl_type, None);
+        out
+    }
+
+    /// Prints a type as candid, with the fields of records and variants on separate, indented lines.
+    pub fn to_pretty_string(idl_type: &IDLType) -> String {
+        let mut out = String::new();
+        write_type(&mut out, idl_type, Some(0));
+        out
+    }
+
+    /// Prints the type of a function as candid, on one line, as in the methods of a service: `(nat) -> (text) query`.
+    pub fn func_to_string(func_type: &FuncType) -> String {
+        let mut out = String::new();
+        write_func(&mut out, func_type, None);
+        out
+    }
+
+    /// Writes a type, on one line or, given the indentation level, on several.
+    fn write_type(out: &mut String, idl_type: &IDLType, indent: Option<usize>) {
+        match idl_type {
+            IDLType::PrimT(prim_type) => out.push_str(prim_type_name(prim_type)),
+            IDLType::VarT(name) => out.push_str(name),
+            IDLType::PrincipalT => out.push_str("principal"),
+            IDLType::VecT(idl_type) if matches!(**idl_type, IDLType::PrimT(PrimType::Nat8)) => {
+                out.push_str("blob")
+            }
+            IDLType::OptT(idl_type) => {
+                out.push_str("opt ");
+                write_type(out, idl_type, indent);
+            }
+            IDLType::VecT(idl_type) => {
+                out.push_str("vec ");
+                write_type(out, idl_type, indent);
+            }
+            IDLType::RecordT(fields) => write_fields(out, "record", fields, indent),
+            IDLType::VariantT(fields) => write_fields(out, "variant", fields, indent),
+            IDLType::FuncT(func_type) => {
+                out.push_str("func ");
+                write_func(out, func_type, indent);
+            }
+            IDLType::ServT(methods) => {
+                out.push_str("service {");
+                for method in methods {
+                    write_separator(out, indent.map(|level| level + 1));
+                    out.push_str(&label_name(&Label::Named(method.id.clone())));
+                    out.push_str(" : ");
+                    match &method.typ {
+                        IDLType::FuncT(func_type) => write_func(out, func_type, indent),
+                        idl_type => write_type(out, idl_type, indent.map(|level| level + 1)),
+                    }
+                    out.push(';');
+                }
+                write_separator(out, indent);
+                out.push('}');
+            }
+            IDLType::ClassT(args, service) => {
+                write_tuple(out, args, indent);
+                out.push_str(" -> ");
+                write_type(out, service, indent);
+            }
+        }
+    }
+
+    /// Writes the fields of a record or variant, such as `record { owner : principal; subaccount : opt blob }`.
+    ///
+    /// The fields of tuples are written on one line without labels.  Variant cases without data are written
+    /// without a type.
+    fn write_fields(out: &mut String, keyword: &str, fields: &[TypeField], indent: Option<usize>) {
+        out.push_str(keyword);
+        out.push_str(" {");
+        if fields.is_empty() {
+            out.push('}');
+            return;
+        }
+        let is_variant = keyword == "variant";
+        let is_tuple = !is_variant
+            && fields
+                .iter()
+                .all(|field| matches!(field.label, Label::Unnamed(_)));
+        let indent = if is_tuple { None } else { indent };
+        for (index, field) in fields.iter().enumerate() {
+            write_separator(out, indent.map(|level| level + 1));
+            let is_null = matches!(field.typ, IDLType::PrimT(PrimType::Null));
+            if !is_tuple {
+                out.push_str(&label_name(&field.label));
+            }
+            if !(is_variant && is_null) {
+                if !is_tuple {
+                    out.push_str(" : ");
+                }
+                write_type(out, &field.typ, indent.map(|level| level + 1));
+            }
+            if indent.is_some() || index + 1 < fields.len() {
+                out.push(';');
+            }
+        }
+        write_separator(out, indent);
+        out.push('}');
+    }
+
+    /// Writes the arguments, results and modes of a function.
+    fn write_func(out: &mut String, func_type: &FuncType, indent: Option<usize>) {
+        write_tuple(out, &func_type.args, indent);
+        out.push_str(" -> ");
+        write_tuple(out, &func_type.rets, indent);
+        for mode in &func_type.modes {
+            out.push_str(match mode {
+                FuncMode::Oneway => " oneway",
+                FuncMode::Query => " query",
+                FuncMode::CompositeQuery => " composite_query",
+            });
+        }
+    }
+
+    /// Writes a tuple of types, such as the arguments of a function: `(nat, text)`.
+    fn write_tuple(out: &mut String, idl_types: &[IDLType], indent: Option<usize>) {
+        out.push('(');
+        for (index, idl_type) in idl_types.iter().enumerate() {
+            if index > 0 {
+                out.push_str(", ");
+            }
+            write_type(out, idl_type, indent);
+        }
+        out.push(')');
+    }
+
+    /// Writes a new line at the given indentation level or, on one line, a space.
+    fn write_separator(out: &mut String, indent: Option<usize>) {
+        match indent {
+            Some(level) => {
+                out.push('\n');
+                out.push_str(&"  ".repeat(level));
+            }
+            None => out.push(' '),
+        }
+    }
+
+    /// The label of a field, quoted if it is not a valid identifier.
+    fn label_name(label: &Label) -> String {
+        match label {
+            Label::Named(name) => {
+                let is_identifier = name
+                    .chars()
+                    .next()
+                    .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+                    && name
+                        .chars()
+                        .all(|char| char.is_ascii_alphanumeric() || char == '_');
+                if is_identifier {
+                    name.clone()
+                } else {
+                    format!("{name:?}")
+                }
+            }
+            Label::Id(id) | Label::Unnamed(id) => id.to_string(),
+        }
+    }
+
+    /// The name of a primitive type.
+    fn prim_type_name(prim_type: &PrimType) -> &'static str {
+        match prim_type {
+            PrimType::Nat => "nat",
+            PrimType::Nat8 => "nat8",
+            PrimType::Nat16 => "nat16",
+            PrimType::Nat32 => "nat32",
+            PrimType::Nat64 => "nat64",
+            PrimType::Int => "int",
+            PrimType::Int8 => "int8",
+            PrimType::Int16 => "int16",
+            PrimType::Int32 => "int32",
+            PrimType::Int64 => "int64",
+            PrimType::Float32 => "float32",
+            PrimType::Float64 => "float64",
+            PrimType::Bool => "bool",
+            PrimType::Text => "text",
+            PrimType::Null => "null",
+            PrimType::Reserved => "reserved",
+            PrimType::Empty => "empty",
+        }
+    }
 }
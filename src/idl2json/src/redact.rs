@@ -0,0 +1,42 @@
+//! Redaction of values that should not be shared, such as keys.
+//!
+//! Redacted values are replaced by a placeholder or a hash, so that the structure around them is kept.
+use crate::{path::ValuePath, Idl2JsonOptions, JsonValue, RedactFormat};
+use candid::types::value::IDLValue;
+use candid_parser::types::IDLType;
+#[cfg(feature = "crypto")]
+use sha2::{Digest, Sha256};
+
+/// The placeholder for redacted values.
+const REDACTED: &str = "[redacted]";
+
+/// Determines whether values of the given type should be redacted, wherever they are.
+pub(crate) fn is_redacted_type(idl_type: &IDLType, options: &Idl2JsonOptions) -> bool {
+    matches!(idl_type, IDLType::VarT(type_name) if options.redact_types.contains(type_name))
+}
+
+/// Determines whether the value at a path, of the given type if known, should be redacted.
+pub(crate) fn is_redacted(
+    idl_type: Option<&IDLType>,
+    path: &ValuePath,
+    options: &Idl2JsonOptions,
+) -> bool {
+    idl_type.is_some_and(|idl_type| is_redacted_type(idl_type, options))
+        || options
+            .redact_paths
+            .iter()
+            .any(|pattern| pattern.matches(path))
+}
+
+/// The JSON that replaces a redacted value.
+#[cfg_attr(not(feature = "crypto"), allow(unused_variables))]
+pub(crate) fn redact(idl: &IDLValue, options: &Idl2JsonOptions) -> JsonValue {
+    match options.redact_as {
+        RedactFormat::Placeholder => JsonValue::String(REDACTED.to_string()),
+        #[cfg(feature = "crypto")]
+        RedactFormat::Sha256 => {
+            let digest = Sha256::digest(idl.to_string().as_bytes());
+            JsonValue::String(format!("{REDACTED} sha256:{digest:x}"))
+        }
+    }
+}
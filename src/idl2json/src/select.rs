@@ -0,0 +1,140 @@
+//! Conversion of only the values at the paths matching a pattern.
+//!
+//! Values are not converted unless they match, so large unrelated values, such as blobs, cost nothing.
+use crate::{
+    path::{PathPattern, ValuePath},
+    redact::is_redacted,
+    typed_conversion::{get_type_from_any, idl2json_with_weak_names_at, typed_field},
+    untyped_conversion::idl2json_at,
+    Idl2JsonOptions, JsonValue,
+};
+use candid::types::value::{IDLField, IDLValue};
+use candid_parser::types::IDLType;
+
+/// Converts the values inside a candid IDLValue at the paths matching a pattern, without type information.
+///
+/// The values are returned in the order in which they appear.
+pub fn idl2json_select(
+    idl: &IDLValue,
+    pattern: &PathPattern,
+    options: &Idl2JsonOptions,
+) -> Vec<JsonValue> {
+    let mut selected = Vec::new();
+    select_at(idl, None, &ValuePath::ROOT, pattern, options, &mut selected);
+    selected
+}
+
+/// Converts the values inside a candid IDLValue at the paths matching a pattern, with keys as names where possible.
+///
+/// Paths are made of the names given by the type, so they are as in the output of `idl2json_with_weak_names`.
+/// The values are returned in the order in which they appear.
+pub fn idl2json_with_weak_names_select(
+    idl: &IDLValue,
+    idl_type: &IDLType,
+    pattern: &PathPattern,
+    options: &Idl2JsonOptions,
+) -> Vec<JsonValue> {
+    let mut selected = Vec::new();
+    select_at(
+        idl,
+        Some(idl_type),
+        &ValuePath::ROOT,
+        pattern,
+        options,
+        &mut selected,
+    );
+    selected
+}
+
+/// Appends the converted values at paths matching the pattern to a list.
+///
+/// Redacted values are selected as they are converted, redacted, and the values inside them are not selected.
+fn select_at(
+    idl: &IDLValue,
+    idl_type: Option<&IDLType>,
+    path: &ValuePath,
+    pattern: &PathPattern,
+    options: &Idl2JsonOptions,
+    selected: &mut Vec<JsonValue>,
+) {
+    if pattern.matches(path) {
+        selected.push(match idl_type {
+            Some(idl_type) => idl2json_with_weak_names_at(idl, idl_type, path, options),
+            None => idl2json_at(idl, path, options),
+        });
+        return;
+    }
+    // Values inside a redacted value are not selected, as they would otherwise be revealed.  A redacted value
+    // that is itself selected is redacted when it is converted.
+    if !pattern.may_match_inside(path) || is_redacted(idl_type, path, options) {
+        return;
+    }
+    // Names are followed one at a time, as each may be a redacted type.
+    if let Some(IDLType::VarT(type_name)) = idl_type {
+        let resolved_type = get_type_from_any(&options.prog, type_name);
+        return select_at(
+            idl,
+            resolved_type.as_ref(),
+            path,
+            pattern,
+            options,
+            selected,
+        );
+    }
+    match (idl, idl_type) {
+        (IDLValue::Opt(value), Some(IDLType::OptT(opt_type))) => {
+            select_at(value, Some(opt_type), path, pattern, options, selected)
+        }
+        (IDLValue::Opt(value), _) => select_at(value, None, path, pattern, options, selected),
+        (IDLValue::Vec(items), _) => {
+            let item_type = match idl_type {
+                Some(IDLType::VecT(item_type)) => Some(&**item_type),
+                _ => None,
+            };
+            for (index, item) in items.iter().enumerate() {
+                select_at(
+                    item,
+                    item_type,
+                    &path.index(index),
+                    pattern,
+                    options,
+                    selected,
+                );
+            }
+        }
+        (IDLValue::Record(fields), _) => {
+            for field in fields {
+                select_field(field, idl_type, path, pattern, options, selected);
+            }
+        }
+        (IDLValue::Variant(variant), _) => {
+            select_field(&variant.0, idl_type, path, pattern, options, selected)
+        }
+        _ => {}
+    }
+}
+
+/// Appends the converted values in a record field or variant that match the pattern to a list.
+fn select_field(
+    field: &IDLField,
+    idl_type: Option<&IDLType>,
+    path: &ValuePath,
+    pattern: &PathPattern,
+    options: &Idl2JsonOptions,
+    selected: &mut Vec<JsonValue>,
+) {
+    let (key, field_type) = match idl_type {
+        Some(IDLType::RecordT(field_types) | IDLType::VariantT(field_types)) => {
+            typed_field(field, field_types)
+        }
+        _ => (field.id.to_string(), None),
+    };
+    select_at(
+        &field.val,
+        field_type,
+        &path.key(&key),
+        pattern,
+        options,
+        selected,
+    )
+}
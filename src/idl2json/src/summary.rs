@@ -0,0 +1,259 @@
+//! Summaries of the shape of candid values, rather than their content.
+//!
+//! A summary gives, for the value as a whole and for every path in it:
+//! - how many values there are of each candid type,
+//! - the lengths of vectors and the total number of bytes in blobs,
+//! - the variant tags seen.
+//!
+//! Paths are as for `PathPattern`, with `*` in place of vector indices, so all the elements of a vector
+//! are summarized together.  The top level value has the path `.`.
+use crate::{
+    typed_conversion::{resolve_type, typed_field},
+    Idl2JsonOptions, JsonValue,
+};
+use candid::types::value::{IDLField, IDLValue};
+use candid_parser::types::{IDLType, PrimType};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Summarizes the shape of a candid value, without type information.
+pub fn idl2summary(idl: &IDLValue, options: &Idl2JsonOptions) -> JsonValue {
+    let mut summary = Summary::default();
+    summary.add(idl, None, "", 0, options);
+    summary.to_json()
+}
+
+/// Summarizes the shape of a candid value, with field names and variant tags from the type where possible.
+pub fn idl2summary_with_weak_names(
+    idl: &IDLValue,
+    idl_type: &IDLType,
+    options: &Idl2JsonOptions,
+) -> JsonValue {
+    let mut summary = Summary::default();
+    summary.add(idl, Some(idl_type), "", 0, options);
+    summary.to_json()
+}
+
+/// The shape of a value.
+#[derive(Default)]
+struct Summary {
+    /// The number of steps in the longest path.
+    max_depth: usize,
+    /// The number of values of each type.
+    types: BTreeMap<&'static str, usize>,
+    /// The shapes of the values at each path.
+    paths: BTreeMap<String, PathSummary>,
+}
+
+/// The shape of the values at a path.
+#[derive(Default)]
+struct PathSummary {
+    /// The number of values of each type.
+    types: BTreeMap<&'static str, usize>,
+    /// The minimum, maximum and total lengths of vectors.
+    lengths: Option<(usize, usize, usize)>,
+    /// The total number of bytes in blobs.
+    bytes: Option<usize>,
+    /// The number of times each variant tag has been seen.
+    tags: BTreeMap<String, usize>,
+}
+
+impl Summary {
+    /// Adds a value at a path, of the given type if known, to the summary.
+    fn add(
+        &mut self,
+        idl: &IDLValue,
+        idl_type: Option<&IDLType>,
+        path: &str,
+        depth: usize,
+        options: &Idl2JsonOptions,
+    ) {
+        let resolved_type = idl_type.and_then(|idl_type| resolve_type(idl_type, options));
+        let is_blob = match (idl, &resolved_type) {
+            (IDLValue::Blob(_), _) => true,
+            (IDLValue::Vec(items), Some(IDLType::VecT(item_type))) => {
+                matches!(**item_type, IDLType::PrimT(PrimType::Nat8)) || is_bytes(items)
+            }
+            (IDLValue::Vec(items), _) => is_bytes(items),
+            _ => false,
+        };
+        let type_name = match (idl, &resolved_type) {
+            _ if is_blob => "blob",
+            // Numbers parsed from text without annotation have the type declared for them.
+            (IDLValue::Number(_), Some(IDLType::PrimT(prim_type))) => {
+                number_type_name(prim_type).unwrap_or("number")
+            }
+            (idl, _) => type_name(idl),
+        };
+        self.max_depth = self.max_depth.max(depth);
+        *self.types.entry(type_name).or_default() += 1;
+        let path_summary = self.paths.entry(path.to_string()).or_default();
+        *path_summary.types.entry(type_name).or_default() += 1;
+        match idl {
+            IDLValue::Blob(bytes) => *path_summary.bytes.get_or_insert(0) += bytes.len(),
+            IDLValue::Vec(items) if is_blob => *path_summary.bytes.get_or_insert(0) += items.len(),
+            IDLValue::Vec(items) => {
+                let (min, max, total) =
+                    path_summary
+                        .lengths
+                        .get_or_insert((items.len(), items.len(), 0));
+                *min = (*min).min(items.len());
+                *max = (*max).max(items.len());
+                *total += items.len();
+                let item_type = match &resolved_type {
+                    Some(IDLType::VecT(item_type)) => Some(&**item_type),
+                    _ => None,
+                };
+                let item_path = child_path(path, "*");
+                for item in items {
+                    self.add(item, item_type, &item_path, depth + 1, options);
+                }
+            }
+            IDLValue::Opt(value) => {
+                let value_type = match &resolved_type {
+                    Some(IDLType::OptT(value_type)) => Some(&**value_type),
+                    _ => None,
+                };
+                self.add(value, value_type, path, depth, options);
+            }
+            IDLValue::Record(fields) => {
+                for field in fields {
+                    self.add_field(field, &resolved_type, path, depth, options);
+                }
+            }
+            IDLValue::Variant(variant) => {
+                let key = self.add_field(&variant.0, &resolved_type, path, depth, options);
+                if let Some(path_summary) = self.paths.get_mut(path) {
+                    *path_summary.tags.entry(key).or_default() += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds a record field or variant to the summary, returning its key.
+    fn add_field(
+        &mut self,
+        field: &IDLField,
+        idl_type: &Option<IDLType>,
+        path: &str,
+        depth: usize,
+        options: &Idl2JsonOptions,
+    ) -> String {
+        let (key, field_type) = match idl_type {
+            Some(IDLType::RecordT(field_types) | IDLType::VariantT(field_types)) => {
+                typed_field(field, field_types)
+            }
+            _ => (field.id.to_string(), None),
+        };
+        self.add(
+            &field.val,
+            field_type,
+            &child_path(path, &key),
+            depth + 1,
+            options,
+        );
+        key
+    }
+
+    /// The summary as JSON.
+    fn to_json(&self) -> JsonValue {
+        json!({
+            "max_depth": self.max_depth,
+            "types": self.types,
+            "paths": self
+                .paths
+                .iter()
+                .map(|(path, path_summary)| {
+                    let path = if path.is_empty() { "." } else { path };
+                    (path.to_string(), path_summary.to_json())
+                })
+                .collect::<serde_json::Map<_, _>>(),
+        })
+    }
+}
+
+impl PathSummary {
+    /// The summary as JSON, omitting what does not apply.
+    fn to_json(&self) -> JsonValue {
+        let mut json = serde_json::Map::new();
+        json.insert("types".to_string(), json!(self.types));
+        if let Some((min, max, total)) = self.lengths {
+            json.insert(
+                "lengths".to_string(),
+                json!({ "min": min, "max": max, "total": total }),
+            );
+        }
+        if let Some(bytes) = self.bytes {
+            json.insert("bytes".to_string(), json!(bytes));
+        }
+        if !self.tags.is_empty() {
+            json.insert("tags".to_string(), json!(self.tags));
+        }
+        JsonValue::Object(json)
+    }
+}
+
+/// The path of a value inside the value at a path.
+fn child_path(path: &str, step: &str) -> String {
+    if path.is_empty() {
+        step.to_string()
+    } else {
+        format!("{path}.{step}")
+    }
+}
+
+/// Whether a vector, of unknown type, is made of bytes.
+fn is_bytes(items: &[IDLValue]) -> bool {
+    !items.is_empty() && items.iter().all(|item| matches!(item, IDLValue::Nat8(_)))
+}
+
+/// The name of a numeric type.
+fn number_type_name(prim_type: &PrimType) -> Option<&'static str> {
+    Some(match prim_type {
+        PrimType::Nat => "nat",
+        PrimType::Nat8 => "nat8",
+        PrimType::Nat16 => "nat16",
+        PrimType::Nat32 => "nat32",
+        PrimType::Nat64 => "nat64",
+        PrimType::Int => "int",
+        PrimType::Int8 => "int8",
+        PrimType::Int16 => "int16",
+        PrimType::Int32 => "int32",
+        PrimType::Int64 => "int64",
+        PrimType::Float32 => "float32",
+        PrimType::Float64 => "float64",
+        _ => return None,
+    })
+}
+
+/// The name of the candid type of a value.
+fn type_name(idl: &IDLValue) -> &'static str {
+    match idl {
+        IDLValue::Bool(_) => "bool",
+        IDLValue::Null => "null",
+        IDLValue::Text(_) => "text",
+        IDLValue::Number(_) => "number",
+        IDLValue::Float32(_) => "float32",
+        IDLValue::Float64(_) => "float64",
+        IDLValue::Opt(_) | IDLValue::None => "opt",
+        IDLValue::Vec(_) => "vec",
+        IDLValue::Blob(_) => "blob",
+        IDLValue::Record(_) => "record",
+        IDLValue::Variant(_) => "variant",
+        IDLValue::Principal(_) => "principal",
+        IDLValue::Service(_) => "service",
+        IDLValue::Func(_, _) => "func",
+        IDLValue::Int(_) => "int",
+        IDLValue::Nat(_) => "nat",
+        IDLValue::Nat8(_) => "nat8",
+        IDLValue::Nat16(_) => "nat16",
+        IDLValue::Nat32(_) => "nat32",
+        IDLValue::Nat64(_) => "nat64",
+        IDLValue::Int8(_) => "int8",
+        IDLValue::Int16(_) => "int16",
+        IDLValue::Int32(_) => "int32",
+        IDLValue::Int64(_) => "int64",
+        IDLValue::Reserved => "reserved",
+    }
+}
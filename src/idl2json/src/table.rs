@@ -0,0 +1,463 @@
+//! Tables of candid values, such as `vec record {...}` query responses, for CSV and TSV.
+//!
+//! - The rows are the elements of a top level vector, or the top level value if it is not a vector.
+//! - The columns are the paths to the values in a row, as for `PathPattern` but without the row index.
+//!   A row that is not a record has the single column `.`.
+//! - Records are flattened: the field `proposal : opt record { id : nat64 }` has the column `proposal.id`.
+//! - A variant has a column with its tag and columns for the payloads of its tags, such as `status`
+//!   and `status.failed.reason`.
+//! - Other values, such as vectors, blobs and numbers, are converted as usual, so they follow `bytes_as`
+//!   and the other options.
+//!
+//! With type information, there is a column for every field and variant payload declared by the type,
+//! whether or not it is in the value.
+//! The columns are in key order, as given by `options.key_order`, with the `preserve_order` feature, and
+//! otherwise alphabetical.
+use crate::{
+    generic_value::{is_generic_value, is_generic_value_type},
+    key_order::sort_fields,
+    path::ValuePath,
+    redact::{is_redacted, is_redacted_type},
+    typed_conversion::{idl2json_with_weak_names_at, resolve_type, typed_field},
+    untyped_conversion::idl2json_at,
+    Idl2JsonOptions, JsonValue,
+};
+use anyhow::Context;
+use candid::types::value::{IDLField, IDLValue};
+use candid_parser::types::{IDLType, PrimType, TypeField};
+use serde_json::Map;
+use std::collections::BTreeSet;
+
+/// The column of a row that is not a record.
+const VALUE_COLUMN: &str = ".";
+
+/// A record field or variant in a row: The candid field id, the key, and the type, if known, and value.
+type RowField<'a> = (u32, String, (Option<&'a IDLType>, &'a IDLValue));
+
+/// Converts a candid IDLValue to table rows, without type information.
+///
+/// The rows are JSON objects with a key for every column, in column order.  Cells without a value are `null`,
+/// and objects that are not flattened, such as generic values, are compact JSON strings.
+pub fn idl2table(idl: &IDLValue, options: &Idl2JsonOptions) -> JsonValue {
+    TableBuilder::new(None, options).rows(idl)
+}
+
+/// Converts a candid IDLValue to table rows, with columns named by the type where possible.
+///
+/// The rows are JSON objects with a key for every column, in column order.  Cells without a value are `null`,
+/// and objects that are not flattened, such as generic values, are compact JSON strings.
+pub fn idl2table_with_weak_names(
+    idl: &IDLValue,
+    idl_type: &IDLType,
+    options: &Idl2JsonOptions,
+) -> JsonValue {
+    TableBuilder::new(Some(idl_type), options).rows(idl)
+}
+
+/// Creates table rows, with the columns known from the type.
+struct TableBuilder<'a> {
+    options: &'a Idl2JsonOptions,
+    /// The type of the top level value.
+    idl_type: Option<&'a IDLType>,
+    /// The columns declared by the type of a row.
+    columns: Vec<String>,
+}
+
+impl<'a> TableBuilder<'a> {
+    fn new(idl_type: Option<&'a IDLType>, options: &'a Idl2JsonOptions) -> Self {
+        let mut builder = TableBuilder {
+            options,
+            idl_type,
+            columns: Vec::new(),
+        };
+        if let Some(row_type) = idl_type.and_then(|idl_type| builder.row_type(idl_type)) {
+            builder.add_columns(&row_type, "", &mut BTreeSet::new());
+        }
+        builder
+    }
+
+    /// The type of the rows of a table of the given type.
+    fn row_type(&self, idl_type: &IDLType) -> Option<IDLType> {
+        match self.resolve(Some(idl_type))? {
+            IDLType::OptT(value_type) => self.row_type(&value_type),
+            IDLType::VecT(item_type) if !is_bytes_type(&item_type) => Some(*item_type),
+            idl_type => Some(idl_type),
+        }
+    }
+
+    /// Adds the columns of values of the given type, at the given column, to the table.
+    ///
+    /// Recursive types are not flattened beyond their first occurrence.
+    fn add_columns(
+        &mut self,
+        idl_type: &IDLType,
+        column: &str,
+        named_types: &mut BTreeSet<String>,
+    ) {
+        if let IDLType::VarT(type_name) = idl_type {
+            if !named_types.insert(type_name.clone()) {
+                self.columns.push(value_column(column));
+                return;
+            }
+        }
+        match self.resolve(Some(idl_type)) {
+            Some(IDLType::OptT(value_type)) => self.add_columns(&value_type, column, named_types),
+            Some(IDLType::RecordT(fields)) if self.is_flattened(idl_type) && !fields.is_empty() => {
+                for (_, key, field_type) in self.field_types(&fields) {
+                    self.add_columns(field_type, &child_column(column, &key), named_types);
+                }
+            }
+            Some(IDLType::VariantT(fields)) if self.is_flattened(idl_type) => {
+                self.columns.push(value_column(column));
+                for (_, key, field_type) in self.field_types(&fields) {
+                    if !is_empty_payload_type(field_type) {
+                        self.add_columns(field_type, &child_column(column, &key), named_types);
+                    }
+                }
+            }
+            _ => self.columns.push(value_column(column)),
+        }
+        if let IDLType::VarT(type_name) = idl_type {
+            named_types.remove(type_name);
+        }
+    }
+
+    /// The keys and types of record fields or variant tags, in the order given by `options.key_order`.
+    fn field_types<'b>(&self, fields: &'b [TypeField]) -> Vec<(u32, String, &'b IDLType)> {
+        let mut field_types: Vec<_> = fields
+            .iter()
+            .map(|field| (field.label.get_id(), field.label.to_string(), &field.typ))
+            .collect();
+        sort_fields(&mut field_types, Some(fields), self.options);
+        field_types
+    }
+
+    /// Whether values of the type are spread over several columns, rather than converted as usual.
+    fn is_flattened(&self, idl_type: &IDLType) -> bool {
+        !self.options.lossless
+            && !is_generic_value_type(idl_type, self.options)
+            && !is_redacted_type(idl_type, self.options)
+    }
+
+    /// The given type, with named types looked up.
+    fn resolve(&self, idl_type: Option<&IDLType>) -> Option<IDLType> {
+        idl_type.and_then(|idl_type| resolve_type(idl_type, self.options))
+    }
+
+    /// The rows of the table.
+    fn rows(&self, idl: &IDLValue) -> JsonValue {
+        let (rows, row_type) = self.row_values(idl, self.idl_type);
+        JsonValue::Array(match rows {
+            Rows::Elements(rows) => rows
+                .iter()
+                .enumerate()
+                .map(|(index, row)| self.row(row, row_type.as_ref(), &ValuePath::ROOT.index(index)))
+                .collect(),
+            Rows::Value(row) => vec![self.row(row, row_type.as_ref(), &ValuePath::ROOT)],
+        })
+    }
+
+    /// The values of the rows and their type.
+    fn row_values<'b>(
+        &self,
+        idl: &'b IDLValue,
+        idl_type: Option<&IDLType>,
+    ) -> (Rows<'b>, Option<IDLType>) {
+        let resolved_type = self.resolve(idl_type);
+        match (idl, &resolved_type) {
+            (IDLValue::Opt(value), Some(IDLType::OptT(value_type))) => {
+                self.row_values(value, Some(value_type))
+            }
+            (IDLValue::Opt(value), _) => self.row_values(value, None),
+            (IDLValue::Vec(items), Some(IDLType::VecT(item_type))) if !is_bytes_type(item_type) => {
+                (Rows::Elements(items), Some((**item_type).clone()))
+            }
+            (IDLValue::Vec(items), None) if !is_bytes(items) => (Rows::Elements(items), None),
+            _ => (Rows::Value(idl), idl_type.cloned()),
+        }
+    }
+
+    /// A row of the table, with the declared columns first.
+    fn row(&self, idl: &IDLValue, idl_type: Option<&IDLType>, path: &ValuePath) -> JsonValue {
+        let mut row: Map<String, JsonValue> = self
+            .columns
+            .iter()
+            .map(|column| (column.clone(), JsonValue::Null))
+            .collect();
+        self.add_cells(idl, idl_type, "", path, &mut row);
+        JsonValue::Object(row)
+    }
+
+    /// Adds the cells for a value, at the given column, to a row.
+    fn add_cells(
+        &self,
+        idl: &IDLValue,
+        idl_type: Option<&IDLType>,
+        column: &str,
+        path: &ValuePath,
+        row: &mut Map<String, JsonValue>,
+    ) {
+        let resolved_type = self.resolve(idl_type);
+        let is_flattened = !is_redacted(idl_type, path, self.options)
+            && !is_generic_value(idl, self.options)
+            && idl_type.map_or(!self.options.lossless, |idl_type| {
+                self.is_flattened(idl_type)
+            });
+        match (idl, &resolved_type) {
+            (IDLValue::Opt(value), Some(IDLType::OptT(value_type))) if is_flattened => {
+                self.add_cells(value, Some(value_type), column, path, row)
+            }
+            (IDLValue::Opt(value), _) if is_flattened => {
+                self.add_cells(value, None, column, path, row)
+            }
+            // In candid text, `null` is also given for absent optional values.
+            (IDLValue::None, _) | (IDLValue::Null, Some(IDLType::OptT(_))) if is_flattened => {}
+            (IDLValue::Record(fields), _) if is_flattened && !fields.is_empty() => {
+                for (_, key, (field_type, value)) in self.fields(fields, &resolved_type) {
+                    let column = child_column(column, &key);
+                    self.add_cells(value, field_type, &column, &path.key(&key), row);
+                }
+            }
+            (IDLValue::Variant(variant), _) if is_flattened => {
+                let variant = std::slice::from_ref(&*variant.0);
+                for (_, key, (field_type, value)) in self.fields(variant, &resolved_type) {
+                    row.insert(value_column(column), JsonValue::String(key.clone()));
+                    // Payloads without content, such as `null`, have no cells.
+                    if !matches!(value, IDLValue::Null | IDLValue::Reserved) {
+                        let column = child_column(column, &key);
+                        self.add_cells(value, field_type, &column, &path.key(&key), row);
+                    }
+                }
+            }
+            _ => {
+                let json = match idl_type {
+                    Some(idl_type) => {
+                        idl2json_with_weak_names_at(idl, idl_type, path, self.options)
+                    }
+                    None => idl2json_at(idl, path, self.options),
+                };
+                // Objects that are not flattened, such as generic values, stay in one cell.
+                let json = match json {
+                    JsonValue::Object(_) => JsonValue::String(json.to_string()),
+                    json => json,
+                };
+                row.insert(value_column(column), json);
+            }
+        }
+    }
+
+    /// The keys, types and values of record fields or a variant, in the order given by `options.key_order`.
+    fn fields<'b>(
+        &self,
+        fields: &'b [IDLField],
+        idl_type: &'b Option<IDLType>,
+    ) -> Vec<RowField<'b>> {
+        let field_types = match idl_type {
+            Some(IDLType::RecordT(field_types) | IDLType::VariantT(field_types)) => {
+                Some(&field_types[..])
+            }
+            _ => None,
+        };
+        let mut fields: Vec<_> = fields
+            .iter()
+            .map(|field| {
+                let (key, field_type) = match field_types {
+                    Some(field_types) => typed_field(field, field_types),
+                    None => (field.id.to_string(), None),
+                };
+                (field.id.get_id(), key, (field_type, &field.val))
+            })
+            .collect();
+        sort_fields(&mut fields, field_types, self.options);
+        fields
+    }
+}
+
+/// The values of the rows of a table.
+enum Rows<'a> {
+    /// The elements of a vector, one per row.
+    Elements(&'a [IDLValue]),
+    /// A value that is not a vector, which is the only row.
+    Value(&'a IDLValue),
+}
+
+/// The column of a value inside the value at a column.
+fn child_column(column: &str, key: &str) -> String {
+    if column.is_empty() {
+        key.to_string()
+    } else {
+        format!("{column}.{key}")
+    }
+}
+
+/// The column of the value at a column, which is `.` for a row that is not a record.
+fn value_column(column: &str) -> String {
+    if column.is_empty() {
+        VALUE_COLUMN.to_string()
+    } else {
+        column.to_string()
+    }
+}
+
+/// Whether a type is `blob` or `vec nat8`, which is converted as bytes rather than as rows.
+fn is_bytes_type(idl_type: &IDLType) -> bool {
+    matches!(idl_type, IDLType::PrimT(PrimType::Nat8))
+}
+
+/// Whether a vector, of unknown type, is made of bytes.
+fn is_bytes(items: &[IDLValue]) -> bool {
+    !items.is_empty() && items.iter().all(|item| matches!(item, IDLValue::Nat8(_)))
+}
+
+/// Whether a variant payload type carries no content, so has no column.
+fn is_empty_payload_type(idl_type: &IDLType) -> bool {
+    matches!(
+        idl_type,
+        IDLType::PrimT(PrimType::Null | PrimType::Reserved)
+    )
+}
+
+/// Writes table rows, as produced by `idl2table()`, or any JSON, as CSV (RFC 4180) or TSV.
+///
+/// - The rows are the elements of a top level array, or the top level value if it is not an array.
+/// - Nested objects are flattened, with keys joined by dots, and the columns are all the keys in the order
+///   in which they first appear.
+/// - Strings are written as they are, `null` as an empty cell and other values as compact JSON.
+/// - Cells that a spreadsheet would take as a formula, starting with `=`, `+`, `-` or `@` but not numbers,
+///   are prefixed with `'`.
+/// - In TSV, tabs, line breaks and backslashes in cells are escaped as `\t`, `\n`, `\r` and `\\`.
+/// - There is no output if there are no columns, such as for an empty vector.
+pub(crate) fn json2table(json: &JsonValue, delimiter: char) -> anyhow::Result<String> {
+    let rows: Vec<Map<String, JsonValue>> = match json {
+        JsonValue::Array(rows) => rows.iter().map(flat_row).collect(),
+        row => vec![flat_row(row)],
+    };
+    let mut columns: Vec<&str> = Vec::new();
+    for row in &rows {
+        for column in row.keys() {
+            if !columns.contains(&column.as_str()) {
+                columns.push(column);
+            }
+        }
+    }
+    let mut table = String::new();
+    if columns.is_empty() {
+        return Ok(table);
+    }
+    write_line(
+        columns.iter().map(|column| column.to_string()),
+        delimiter,
+        &mut table,
+    );
+    for row in &rows {
+        let cells = columns
+            .iter()
+            .map(|column| match row.get(*column) {
+                None | Some(JsonValue::Null) => Ok(String::new()),
+                Some(JsonValue::String(string)) => Ok(string.clone()),
+                Some(json) => serde_json::to_string(json).context("Failed to serialize a cell"),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        write_line(cells.into_iter(), delimiter, &mut table);
+    }
+    Ok(table)
+}
+
+/// A row with nested objects flattened.
+fn flat_row(json: &JsonValue) -> Map<String, JsonValue> {
+    let mut row = Map::new();
+    flatten(json, "", &mut row);
+    row
+}
+
+/// Adds the cells for a JSON value, at the given column, to a row.
+fn flatten(json: &JsonValue, column: &str, row: &mut Map<String, JsonValue>) {
+    match json {
+        JsonValue::Object(object) if !object.is_empty() => {
+            for (key, value) in object {
+                flatten(value, &child_column(column, key), row);
+            }
+        }
+        json => {
+            row.insert(value_column(column), json.clone());
+        }
+    }
+}
+
+/// Appends a line of cells to a table.
+fn write_line(cells: impl Iterator<Item = String>, delimiter: char, table: &mut String) {
+    let line = cells
+        .map(escape_formula)
+        .map(|cell| match delimiter {
+            '\t' => escape_tsv(&cell),
+            _ => escape_csv(&cell, delimiter),
+        })
+        .collect::<Vec<_>>()
+        .join(&delimiter.to_string());
+    table.push_str(&line);
+    table.push('\n');
+}
+
+/// A cell that a spreadsheet would not evaluate as a formula.
+fn escape_formula(cell: String) -> String {
+    if cell.starts_with(['=', '+', '-', '@']) && cell.parse::<f64>().is_err() {
+        format!("'{cell}")
+    } else {
+        cell
+    }
+}
+
+/// A CSV cell, quoted if it contains the delimiter, quotes or line breaks.
+fn escape_csv(cell: &str, delimiter: char) -> String {
+    if cell.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// A TSV cell, with tabs, line breaks and backslashes escaped.
+fn escape_tsv(cell: &str) -> String {
+    let mut escaped = String::with_capacity(cell.len());
+    for c in cell.chars() {
+        match c {
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use candid_parser::{parse_idl_args, IDLProg};
+    use std::str::FromStr;
+
+    /// Rows whose type is reached through a chain of aliases have the columns of the record.
+    #[test]
+    fn aliases_of_row_types_are_resolved() {
+        let prog = IDLProg::from_str(
+            "type Row = record { a : nat8; b : text }; type Alias = Row; type Rows = vec Alias;",
+        )
+        .expect("Failed to parse did");
+        let idl_value = parse_idl_args(r#"(vec { record { a = 1 : nat8; b = "x" } })"#)
+            .expect("Failed to parse candid")
+            .args
+            .remove(0);
+        let options = Idl2JsonOptions {
+            prog: vec![prog],
+            ..Idl2JsonOptions::default()
+        };
+        let rows = |idl_type: &str| {
+            let idl_type = IDLType::from_str(idl_type).expect("Bad type");
+            idl2table_with_weak_names(&idl_value, &idl_type, &options)
+        };
+        assert_eq!(serde_json::json!([{"a": 1, "b": "x"}]), rows("Rows"));
+        assert_eq!(rows("vec Row"), rows("Rows"));
+    }
+}
@@ -1,18 +1,28 @@
 #![allow(clippy::expect_used)]
 #![allow(clippy::panic)]
 use crate::{
-    candid_types::internal_candid_type_to_idl_type, idl2json, idl2json_with_weak_names,
-    BytesFormat, Idl2JsonOptions, JsonValue,
+    candid_types::internal_candid_type_to_idl_type, check2json, diff2json_patch, diff2text,
+    idl2json, idl2json_select, idl2json_with_weak_names, idl2json_with_weak_names_select,
+    idl2summary, idl2summary_with_weak_names, idl2table, idl2table_with_weak_names, idl_args2json,
+    idl_args2json_with_weak_names, idl_args_check, idl_args_diff, idl_check, idl_type2json_schema,
+    idl_type2typescript, idl_types2json_schema, json2idl, json_args2idl, lossless_json2idl,
+    lossless_json2idl_args, polyfill, serialize, to_canonical_json, BytesFormat,
+    DeclaredFieldOrder, Idl2JsonOptions, JsonValue, KeyOrder, OutputFormat, PathPattern,
+    RedactFormat,
 };
 use candid::{
-    types::internal::Label, types::value::IDLValue, CandidType, Decode, Deserialize, IDLArgs,
+    types::internal::Label,
+    types::value::{IDLField, IDLValue, VariantValue},
+    types::Type,
+    CandidType, Decode, Deserialize, IDLArgs, TypeEnv,
 };
 use candid_parser::{
     parse_idl_args,
-    types::{IDLType, PrimType, TypeField},
+    types::{IDLProg, IDLType, IDLTypes, PrimType, TypeField},
+    typing::{ast_to_type, check_prog},
 };
 use serde::Serialize;
-use std::fs;
+use std::{fs, str::FromStr};
 
 /// Returns the absolute path to a file in the samples directory.
 macro_rules! sample_file {
@@ -389,3 +399,1294 @@ fn types_should_be_represented_correctly() {
         }
     }
 }
+
+/// Verifies that absent optional fields are added only when requested.
+#[test]
+fn missing_fields_are_filled_only_on_request() {
+    let idl_type = test_idl_type();
+    let BinaryTestVector { binary, .. } = test_vector();
+    let idl_value = Decode!(&binary[..], IDLValue).expect("Failed to parse buffer");
+    let options = Idl2JsonOptions {
+        bytes_as: Some(BytesFormat::Hex),
+        ..Idl2JsonOptions::default()
+    };
+    let expected_json: JsonValue = serde_json::from_str(
+        r#"[{
+            "archive_module_hash":["f691f269dd66aa4fc44e6916aefee03bb7feb821aef43467526974f470cd4b07"],
+            "canister_creation_cycles_cost":["1000000000000"]
+        }]"#,
+    )
+    .expect("Invalid JSON in test");
+    assert_eq!(
+        expected_json,
+        idl2json_with_weak_names(&idl_value, &idl_type, &options)
+    );
+    let options = Idl2JsonOptions {
+        fill_missing_fields: true,
+        ..options
+    };
+    let expected_json: JsonValue = serde_json::from_str(
+        r#"[{
+            "archive_module_hash":["f691f269dd66aa4fc44e6916aefee03bb7feb821aef43467526974f470cd4b07"],
+            "assigned_user_number_range":[],
+            "canister_creation_cycles_cost":["1000000000000"]
+        }]"#,
+    )
+    .expect("Invalid JSON in test");
+    assert_eq!(
+        expected_json,
+        idl2json_with_weak_names(&idl_value, &idl_type, &options)
+    );
+    // Fields that may not be absent are never invented.
+    let idl_type = IDLType::RecordT(vec![
+        TypeField {
+            label: Label::Named("nothing".to_string()),
+            typ: IDLType::PrimT(PrimType::Null),
+        },
+        TypeField {
+            label: Label::Named("ignored".to_string()),
+            typ: IDLType::PrimT(PrimType::Reserved),
+        },
+        TypeField {
+            label: Label::Named("required".to_string()),
+            typ: IDLType::PrimT(PrimType::Nat64),
+        },
+    ]);
+    let idl_value = IDLValue::Record(vec![]);
+    assert_eq!(
+        serde_json::json!({"nothing": null, "ignored": "null : reserved"}),
+        idl2json_with_weak_names(&idl_value, &idl_type, &options)
+    );
+}
+
+/// Verifies that object keys are ordered as requested.
+#[test]
+fn keys_are_ordered_as_requested() {
+    let did = r#"type Person = record { name: text; age: nat8; city: text; };"#;
+    let idl_type = IDLType::VarT("Person".to_string());
+    let idl_args = parse_idl_args(
+        r#"(record { name = "Alice"; age = 30 : nat8; city = "Zurich"; zip = "8000" })"#,
+    )
+    .expect("Malformed input");
+    let idl_value = &idl_args.args[0];
+    let test_vectors = [
+        (
+            KeyOrder::Alphabetical,
+            r#"{"age":30,"city":"Zurich","name":"Alice","zip":"8000"}"#,
+            r#"{"age":30,"city":"Zurich","name":"Alice","zip":"8000"}"#,
+        ),
+        (
+            KeyOrder::Hash,
+            r#"{"age":30,"zip":"8000","city":"Zurich","name":"Alice"}"#,
+            r#"{"age":30,"zip":"8000","city":"Zurich","name":"Alice"}"#,
+        ),
+        (
+            KeyOrder::Declaration,
+            r#"{"name":"Alice","age":30,"city":"Zurich","zip":"8000"}"#,
+            r#"{"age":30,"zip":"8000","city":"Zurich","name":"Alice"}"#,
+        ),
+    ];
+    for (key_order, typed_json, untyped_json) in test_vectors {
+        let options = Idl2JsonOptions {
+            prog: vec![IDLProg::from_str(did).expect("Failed to parse did")],
+            declared_field_order: DeclaredFieldOrder::from_did(did),
+            key_order,
+            ..Idl2JsonOptions::default()
+        };
+        assert_eq!(
+            typed_json,
+            serde_json::to_string(&idl2json_with_weak_names(idl_value, &idl_type, &options))
+                .expect("Failed to serialize JSON"),
+            "Unexpected typed conversion with {key_order:?}"
+        );
+        assert_eq!(
+            untyped_json,
+            serde_json::to_string(&idl2json(idl_value, &options))
+                .expect("Failed to serialize JSON"),
+            "Unexpected untyped conversion with {key_order:?}"
+        );
+    }
+}
+
+/// Verifies that JSON schemas describe the typed conversion, with named types in `$defs`.
+#[test]
+fn json_schemas_describe_the_typed_conversion() {
+    let did_filename = "internet_identity.did";
+    let did =
+        fs::read_to_string(sample_file!(did_filename)).expect("Could not read sample did file");
+    let options = Idl2JsonOptions {
+        prog: vec![IDLProg::from_str(&did).expect("Failed to parse did")],
+        bytes_as: Some(BytesFormat::Hex),
+        ..Idl2JsonOptions::default()
+    };
+    let nat64_schema = serde_json::json!({"type": "string", "pattern": "^[0-9]+$"});
+    let expected_schema = serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$ref": "#/$defs/InternetIdentityInit",
+        "$defs": {
+            "InternetIdentityInit": {
+                "type": "object",
+                "properties": {
+                    "archive_module_hash": {
+                        "type": "array",
+                        "items": {"type": "string", "pattern": "^([0-9a-f]{2})*$"},
+                        "maxItems": 1
+                    },
+                    "assigned_user_number_range": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {"0": nat64_schema, "1": nat64_schema},
+                            "required": ["0", "1"]
+                        },
+                        "maxItems": 1
+                    },
+                    "canister_creation_cycles_cost": {
+                        "type": "array",
+                        "items": nat64_schema,
+                        "maxItems": 1
+                    }
+                },
+                "required": []
+            }
+        }
+    });
+    let schema = idl_type2json_schema(&IDLType::VarT("InternetIdentityInit".to_string()), &options);
+    assert_eq!(expected_schema, schema);
+}
+
+/// Verifies that recursive types are described with references.
+#[test]
+fn json_schemas_of_recursive_types_use_references() {
+    let did = "type List = opt record { head: nat8; tail: List };";
+    let options = Idl2JsonOptions {
+        prog: vec![IDLProg::from_str(did).expect("Failed to parse did")],
+        ..Idl2JsonOptions::default()
+    };
+    let expected_schema = serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "type": "array",
+        "prefixItems": [{"$ref": "#/$defs/List"}],
+        "$defs": {
+            "List": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "head": {"type": "integer", "minimum": 0, "maximum": 255},
+                        "tail": {"$ref": "#/$defs/List"}
+                    },
+                    "required": ["head"]
+                },
+                "maxItems": 1
+            }
+        }
+    });
+    let idl_types = IDLTypes {
+        args: vec![IDLType::VarT("List".to_string())],
+    };
+    assert_eq!(expected_schema, idl_types2json_schema(&idl_types, &options));
+}
+
+/// Verifies that TypeScript declarations describe the typed conversion.
+#[test]
+fn typescript_declarations_describe_the_typed_conversion() {
+    let did = r#"
+        type Hash = blob;
+        type Tree = variant { leaf: Hash; node: record { left: Tree; right: Tree } };
+        type Root = record { tree: opt Tree; "size in bytes": nat64; ratio: float32 };
+    "#;
+    let options = Idl2JsonOptions {
+        prog: vec![IDLProg::from_str(did).expect("Failed to parse did")],
+        bytes_as: Some(BytesFormat::Hex),
+        ..Idl2JsonOptions::default()
+    };
+    let idl_type = IDLType::VecT(Box::new(IDLType::VarT("Root".to_string())));
+    let expected_typescript = r#"export type Roots = Array<Root>;
+export type Root = {
+  tree?: [] | [Tree];
+  "size in bytes": string;
+  ratio: number | "NaN" | "Infinity" | "-Infinity";
+};
+export type Tree = { leaf: Hash } | { node: {
+  left: Tree;
+  right: Tree;
+} };
+export type Hash = string;
+"#;
+    assert_eq!(
+        expected_typescript,
+        idl_type2typescript("Roots", &idl_type, &options)
+    );
+}
+
+/// The samples used in round trip tests, as (idl file, did file, type name).
+const ROUND_TRIP_SAMPLES: [(&str, &str, &str); 2] = [
+    ("all_types.idl", "all_types.did", "AllTypes"),
+    ("proposal.idl", "proposal.did", "ProposalInfo"),
+];
+
+/// A sample value annotated with its type, as an optional value of the named type.
+///
+/// The sample values are annotated with their types, as the parser cannot know whether, for example, a number is a `nat64` or an `int32`.
+struct AnnotatedSample {
+    did: String,
+    type_env: TypeEnv,
+    types: Vec<Type>,
+    idl_types: IDLTypes,
+    idl_value: IDLArgs,
+}
+
+impl AnnotatedSample {
+    fn new(idl_filename: &str, did_filename: &str, type_name: &str) -> Self {
+        let did =
+            fs::read_to_string(sample_file!(did_filename)).expect("Could not read sample did");
+        let prog = IDLProg::from_str(&did).expect("Failed to parse did");
+        let idl_types = IDLTypes {
+            args: vec![IDLType::OptT(Box::new(IDLType::VarT(
+                type_name.to_string(),
+            )))],
+        };
+        let mut type_env = TypeEnv::new();
+        check_prog(&mut type_env, &prog).expect("Invalid did");
+        let types = idl_types
+            .args
+            .iter()
+            .map(|idl_type| ast_to_type(&type_env, idl_type))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Invalid type");
+        let idl_string =
+            fs::read_to_string(sample_file!(idl_filename)).expect("Could not read sample IDL");
+        let idl_value = parse_idl_args(&idl_string)
+            .expect("Malformed input")
+            .annotate_types(true, &type_env, &types)
+            .expect("Sample does not match type");
+        AnnotatedSample {
+            did,
+            type_env,
+            types,
+            idl_types,
+            idl_value,
+        }
+    }
+
+    fn prog(&self) -> IDLProg {
+        IDLProg::from_str(&self.did).expect("Failed to parse did")
+    }
+}
+
+/// Verifies that JSON produced by the typed conversion converts back to the same candid.
+#[test]
+fn sample_idls_survive_a_round_trip() {
+    for (idl_filename, did_filename, type_name) in ROUND_TRIP_SAMPLES {
+        let sample = AnnotatedSample::new(idl_filename, did_filename, type_name);
+        for (bytes_as, long_bytes_as) in [
+            (None, None),
+            (Some(BytesFormat::Hex), None),
+            (Some(BytesFormat::Numbers), Some((5, BytesFormat::Hex))),
+        ] {
+            let options = Idl2JsonOptions {
+                prog: vec![sample.prog()],
+                bytes_as,
+                long_bytes_as,
+                ..Idl2JsonOptions::default()
+            };
+            let json =
+                idl_args2json_with_weak_names(&sample.idl_value, &sample.idl_types, &options);
+            let round_trip = json_args2idl(&json, &sample.idl_types, &options)
+                .unwrap_or_else(|err| panic!("Failed to convert {} back: {:?}", idl_filename, err));
+            assert_eq!(
+                sample.idl_value.args, round_trip.args,
+                "{idl_filename} changed in a round trip with {bytes_as:?} {long_bytes_as:?}"
+            );
+        }
+    }
+}
+
+/// Verifies that lossless JSON converts back to candid that encodes to exactly the same bytes.
+#[test]
+fn lossless_json_survives_a_round_trip() {
+    for (idl_filename, did_filename, type_name) in ROUND_TRIP_SAMPLES {
+        let sample = AnnotatedSample::new(idl_filename, did_filename, type_name);
+        let options = Idl2JsonOptions {
+            prog: vec![sample.prog()],
+            lossless: true,
+            ..Idl2JsonOptions::default()
+        };
+        // With and without the benefit of type information:
+        for json in [
+            idl_args2json_with_weak_names(&sample.idl_value, &sample.idl_types, &options),
+            idl_args2json(&sample.idl_value, &options),
+        ] {
+            let json: JsonValue = serde_json::from_str(&json.to_string()).expect("Invalid JSON");
+            let round_trip = lossless_json2idl_args(&json)
+                .unwrap_or_else(|err| panic!("Failed to convert {} back: {:?}", idl_filename, err));
+            assert_eq!(sample.idl_value.args, round_trip.args);
+            assert_eq!(
+                sample
+                    .idl_value
+                    .to_bytes_with_types(&sample.type_env, &sample.types)
+                    .expect("Failed to encode sample"),
+                round_trip
+                    .to_bytes_with_types(&sample.type_env, &sample.types)
+                    .expect("Failed to encode round trip"),
+                "{idl_filename} changed in a lossless round trip"
+            );
+        }
+    }
+}
+
+/// Verifies that values that the default conversion cannot distinguish are distinct in lossless JSON.
+#[test]
+fn lossless_json_preserves_what_json_cannot_express() {
+    let field = |id: Label, val: IDLValue| IDLField { id, val };
+    // Record fields are sorted by id, as in decoded candid.
+    let mut fields = vec![
+        field(Label::Id(0), IDLValue::Nat8(0)),
+        field(Label::Named("1".to_string()), IDLValue::Nat64(0)),
+        field(Label::Named("zero".to_string()), IDLValue::Float64(-0.0)),
+        field(
+            Label::Named("nan".to_string()),
+            IDLValue::Float32(f32::from_bits(0x7fc0_0001)),
+        ),
+    ];
+    fields.sort_by_key(|field| field.id.get_id());
+    let idl_value = IDLArgs {
+        args: vec![
+            IDLValue::Record(fields),
+            IDLValue::Variant(VariantValue(
+                Box::new(field(Label::Id(5_097_222), IDLValue::Null)),
+                0,
+            )),
+            IDLValue::Opt(Box::new(IDLValue::Opt(Box::new(IDLValue::Null)))),
+            IDLValue::Vec(vec![IDLValue::Opt(Box::new(IDLValue::Reserved))]),
+        ],
+    };
+    let options = Idl2JsonOptions {
+        lossless: true,
+        ..Idl2JsonOptions::default()
+    };
+    let json = idl_args2json(&idl_value, &options);
+    assert_eq!(
+        json,
+        serde_json::json!([
+            {"record": {
+                "0": {"nat8": 0},
+                candid::idl_hash("1").to_string(): {"nat64": "0"},
+                "zero": {"float64": -0.0},
+                "nan": {"float32": "0x7fc00001"},
+            }},
+            {"variant": {"5097222": {"null": null}}},
+            {"opt": [{"opt": [{"null": null}]}]},
+            {"vec": [{"opt": [{"reserved": null}]}]},
+        ])
+    );
+    let round_trip = lossless_json2idl_args(&json).expect("Failed to convert back");
+    assert_eq!(
+        idl_value.to_bytes().expect("Failed to encode"),
+        round_trip.to_bytes().expect("Failed to encode round trip")
+    );
+    let IDLValue::Record(round_trip_fields) = &round_trip.args[0] else {
+        panic!("{}", "Expected a record")
+    };
+    let value = |name: &str| {
+        &round_trip_fields
+            .iter()
+            .find(|field| field.id.get_id() == candid::idl_hash(name))
+            .expect("Missing field")
+            .val
+    };
+    assert!(matches!(value("zero"), IDLValue::Float64(f) if f.is_sign_negative()));
+    assert!(matches!(value("nan"), IDLValue::Float32(f) if f.to_bits() == 0x7fc0_0001));
+}
+
+/// Verifies that lossless JSON without type tags is rejected.
+#[test]
+fn untagged_lossless_json_is_rejected() {
+    for json in [
+        serde_json::json!(5),
+        serde_json::json!({"nat8": 5, "nat16": 5}),
+        serde_json::json!({"nat8": 300}),
+        serde_json::json!({"record": {"a": 5}}),
+        serde_json::json!({"float64": "NaN"}),
+        serde_json::json!({"unknown": null}),
+    ] {
+        assert!(
+            lossless_json2idl(&json).is_err(),
+            "{} should have been rejected",
+            json
+        );
+    }
+}
+
+/// Verifies that JSON that does not match the type is rejected.
+#[test]
+fn json_not_matching_the_type_is_rejected() {
+    let did = r#"type Item = record { name: text; count: nat64; tag: opt variant { a; b } };"#;
+    let options = Idl2JsonOptions {
+        prog: vec![IDLProg::from_str(did).expect("Failed to parse did")],
+        bytes_as: Some(BytesFormat::Sha256),
+        ..Idl2JsonOptions::default()
+    };
+    let idl_type = IDLType::VarT("Item".to_string());
+    for (json, error) in [
+        (r#"{"name": "x"}"#, "Missing key: count"),
+        (
+            r#"{"name": "x", "count": "1", "size": 2}"#,
+            "Unknown record field: size",
+        ),
+        (r#"{"name": "x", "count": "-1"}"#, "count"),
+        (
+            r#"{"name": "x", "count": "1", "tag": [{"c": null}]}"#,
+            "Unknown variant: c",
+        ),
+        (
+            r#"{"name": "x", "count": "1", "tag": {"a": null}}"#,
+            "as an array",
+        ),
+    ] {
+        let json: JsonValue = serde_json::from_str(json).expect("Invalid JSON in test");
+        let result = json2idl(&json, &idl_type, &options);
+        let message = format!("{:?}", result.expect_err("Conversion should fail"));
+        assert!(
+            message.contains(error),
+            "'{}' should contain '{}'",
+            message,
+            error
+        );
+    }
+    let hashed_bytes = serde_json::json!(
+        "Bytes with sha256: ac0c88f389e4af11790089d940f8483905e8766de960ccd847d0500b4caf6acf"
+    );
+    let blob_type = IDLType::VecT(Box::new(IDLType::PrimT(PrimType::Nat8)));
+    assert!(json2idl(&hashed_bytes, &blob_type, &options).is_err());
+}
+
+/// Verifies that canonical JSON matches the examples in RFC 8785 and the number formatting of ECMAScript.
+#[test]
+fn canonical_json_matches_rfc_8785() {
+    let numbers = [
+        (0x0000_0000_0000_0000, "0"),
+        (0x8000_0000_0000_0000, "0"),
+        (0x0000_0000_0000_0001, "5e-324"),
+        (0x8000_0000_0000_0001, "-5e-324"),
+        (0x7fef_ffff_ffff_ffff, "1.7976931348623157e+308"),
+        (0xffef_ffff_ffff_ffff, "-1.7976931348623157e+308"),
+        (0x4340_0000_0000_0000, "9007199254740992"),
+        (0xc340_0000_0000_0000, "-9007199254740992"),
+        (0x4430_0000_0000_0000, "295147905179352830000"),
+        (0x44b5_2d02_c7e1_4af5, "9.999999999999997e+22"),
+        (0x44b5_2d02_c7e1_4af6, "1e+23"),
+        (0x44b5_2d02_c7e1_4af7, "1.0000000000000001e+23"),
+        (0x444b_1ae4_d6e2_ef4e, "999999999999999700000"),
+        (0x444b_1ae4_d6e2_ef4f, "999999999999999900000"),
+        (0x444b_1ae4_d6e2_ef50, "1e+21"),
+        (0x3eb0_c6f7_a0b5_ed8c, "9.999999999999997e-7"),
+        (0x3eb0_c6f7_a0b5_ed8d, "0.000001"),
+        (0x41b3_de43_5555_5553, "333333333.3333332"),
+        (0x41b3_de43_5555_5554, "333333333.33333325"),
+        (0x41b3_de43_5555_5555, "333333333.3333333"),
+        (0x41b3_de43_5555_5556, "333333333.3333334"),
+        (0x41b3_de43_5555_5557, "333333333.33333343"),
+        (0xbecb_f647_612f_3696, "-0.0000033333333333333333"),
+        (0x4314_3ff3_c1cb_0959, "1424953923781206.2"),
+    ];
+    for (bits, expected) in numbers {
+        let number = serde_json::json!(f64::from_bits(bits));
+        assert_eq!(expected, to_canonical_json(&number), "for bits {bits:#x}");
+    }
+    let json: JsonValue = serde_json::from_str(
+        r#"{
+          "numbers": [1E30, 4.50, 2e-3, 0.000000000000000000000000001, 42],
+          "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+          "literals": [null, true, false]
+        }"#,
+    )
+    .expect("Invalid JSON");
+    assert_eq!(
+        r#"{"literals":[null,true,false],"numbers":[1e+30,4.5,0.002,1e-27,42],"string":"€$\u000f\nA'B\"\\\\\"/"}"#,
+        to_canonical_json(&json)
+    );
+    // Keys are sorted by UTF-16 code units, not by code points:
+    let json: JsonValue = serde_json::from_str(
+        r#"{"\u20ac": 5, "\r": 1, "\ufb33": 7, "1": 2, "\ud83d\ude00": 6, "\u0080": 3, "\u00f6": 4}"#,
+    )
+    .expect("Invalid JSON");
+    assert_eq!(
+        "{\"\\r\":1,\"1\":2,\"\u{80}\":3,\"ö\":4,\"€\":5,\"😀\":6,\"\u{fb33}\":7}",
+        to_canonical_json(&json)
+    );
+}
+
+/// Verifies that the canonical form of a candid value does not depend on the conversion options.
+#[test]
+fn canonical_json_does_not_depend_on_key_order() {
+    let idl_value =
+        parse_idl_args(r#"(record { zebra = 1.5 : float64; apple = 10 : nat32; mango = "m" })"#)
+            .expect("Malformed input");
+    let canonical: Vec<String> = [
+        KeyOrder::Alphabetical,
+        KeyOrder::Hash,
+        KeyOrder::Declaration,
+    ]
+    .iter()
+    .map(|&key_order| {
+        let options = Idl2JsonOptions {
+            key_order,
+            ..Idl2JsonOptions::default()
+        };
+        to_canonical_json(&idl_args2json(&idl_value, &options))
+    })
+    .collect();
+    for canonical in &canonical {
+        assert_eq!(r#"[{"apple":10,"mango":"m","zebra":1.5}]"#, canonical);
+    }
+}
+
+/// Verifies that ICRC-3 generic values are rendered as plain JSON, if requested.
+#[test]
+fn generic_values_are_flattened_on_request() {
+    let idl_filename = "icrc3_block.idl";
+    let did_filename = "icrc3.did";
+    let idl_string =
+        fs::read_to_string(sample_file!(idl_filename)).expect("Could not read sample IDL");
+    let idl_value = parse_idl_args(&idl_string).expect("Malformed input");
+    let did = "type LedgerValue = ValueAlias; type ValueAlias = Value;\n".to_string()
+        + &fs::read_to_string(sample_file!(did_filename)).expect("Could not read sample did");
+    let idl_type = IDLType::VarT("GetBlocksResult".to_string());
+    let expected_json = serde_json::json!({
+        "log_length": "2",
+        "blocks": [{
+            "id": "1",
+            "block": {
+                "btype": "1xfer",
+                "phash": "0102ff",
+                "ts": "1_722_000_000_000_000_000",
+                "tx": { "amt": "100_000", "delta": "-5", "from": ["00"] },
+            },
+        }],
+    });
+    let options =
+        |flatten_generic_values: bool, generic_value_types: Vec<String>| Idl2JsonOptions {
+            prog: vec![IDLProg::from_str(&did).expect("Failed to parse did")],
+            bytes_as: Some(BytesFormat::Hex),
+            flatten_generic_values,
+            generic_value_types,
+            ..Idl2JsonOptions::default()
+        };
+    // Recognized by the shape of the type:
+    assert_eq!(
+        expected_json,
+        idl2json_with_weak_names(&idl_value.args[0], &idl_type, &options(true, vec![]))
+    );
+    // Recognized by the name of the type:
+    assert_eq!(
+        expected_json,
+        idl2json_with_weak_names(
+            &idl_value.args[0],
+            &idl_type,
+            &options(false, vec!["Value".to_string()])
+        )
+    );
+    // Recognized by the name of an alias of the type:
+    assert_eq!(
+        expected_json,
+        idl2json_with_weak_names(
+            &idl_value.args[0],
+            &idl_type,
+            &options(false, vec!["LedgerValue".to_string()])
+        )
+    );
+    // Recognized without type information:
+    assert_eq!(
+        expected_json,
+        idl2json(&idl_value.args[0], &options(true, vec![]))
+    );
+    // Not requested:
+    let json = idl2json_with_weak_names(&idl_value.args[0], &idl_type, &options(false, vec![]));
+    assert!(json["blocks"][0]["block"]["Map"].is_array());
+    // The schema and TypeScript declarations accept any JSON:
+    let schema = idl_type2json_schema(&idl_type, &options(true, vec![]));
+    assert_eq!(
+        serde_json::json!({}),
+        schema["$defs"]["GetBlocksResult"]["properties"]["blocks"]["items"]["properties"]["block"]
+    );
+    assert!(
+        idl_type2typescript("Output", &idl_type, &options(true, vec![]))
+            .contains("block: unknown;\n")
+    );
+}
+
+/// Verifies that bytes may be recognized as text or principals, and remain distinct in lossless JSON.
+#[test]
+fn bytes_may_be_recognized_as_text_or_principals() {
+    let did = r#"
+        type Account = record { owner : blob; subaccount : opt blob; memo : blob; data : vec nat8 };
+        type Accounts = record { accounts : vec Account };
+    "#;
+    let idl_value = parse_idl_args(
+        r#"(record {
+            accounts = vec {
+                record {
+                    owner = blob "\00\00\00\00\00\00\00\01\01\01";
+                    subaccount = opt blob "\00\00\00\00";
+                    memo = blob "Hello, world!\n";
+                    data = blob "\01\02\03";
+                }
+            }
+        })"#,
+    )
+    .expect("Malformed input");
+    let idl_type = IDLType::VarT("Accounts".to_string());
+    let options = |utf8_bytes_as_text, principal_bytes_lengths, principal_bytes_paths: &[&str]| {
+        Idl2JsonOptions {
+            prog: vec![IDLProg::from_str(did).expect("Failed to parse did")],
+            bytes_as: Some(BytesFormat::Hex),
+            utf8_bytes_as_text,
+            principal_bytes_lengths,
+            principal_bytes_paths: principal_bytes_paths
+                .iter()
+                .map(|path| PathPattern::from_str(path).expect("Invalid path"))
+                .collect(),
+            ..Idl2JsonOptions::default()
+        }
+    };
+    let account = |options: &Idl2JsonOptions| {
+        idl2json_with_weak_names(&idl_value.args[0], &idl_type, options)["accounts"][0].clone()
+    };
+    // Bytes that are not text, such as the zero bytes of the subaccount, are not recognized as text.
+    assert_eq!(
+        serde_json::json!({
+            "owner": "00000000000000010101",
+            "subaccount": ["00000000"],
+            "memo": "Hello, world!\n",
+            "data": "010203",
+        }),
+        account(&options(true, vec![], &[]))
+    );
+    // Principals are recognized by path or by length:
+    for options in [
+        options(false, vec![], &["accounts.*.owner"]),
+        options(false, vec![10], &[]),
+    ] {
+        assert_eq!(
+            serde_json::json!("rrkah-fqaaa-aaaaa-aaaaq-cai"),
+            account(&options)["owner"]
+        );
+    }
+    assert_eq!(
+        serde_json::json!("00000000000000010101"),
+        account(&options(false, vec![], &["owner"]))["owner"]
+    );
+    // Recognized blobs are tagged in lossless JSON, so that they can be converted back.
+    let options = Idl2JsonOptions {
+        lossless: true,
+        ..options(true, vec![], &["accounts.*.owner"])
+    };
+    let annotated_value = {
+        let mut type_env = TypeEnv::new();
+        check_prog(&mut type_env, &options.prog[0]).expect("Invalid did");
+        let types = vec![ast_to_type(&type_env, &idl_type).expect("Invalid type")];
+        idl_value
+            .annotate_types(true, &type_env, &types)
+            .expect("Value does not match type")
+    };
+    let json = idl2json_with_weak_names(&annotated_value.args[0], &idl_type, &options);
+    let account = &json["record"]["accounts"]["vec"][0]["record"];
+    assert_eq!(
+        serde_json::json!({"blob_principal": "rrkah-fqaaa-aaaaa-aaaaq-cai"}),
+        account["owner"]
+    );
+    assert_eq!(
+        serde_json::json!({"blob_text": "Hello, world!\n"}),
+        account["memo"]
+    );
+    assert_eq!(serde_json::json!({"blob": "010203"}), account["data"]);
+    assert_eq!(
+        annotated_value.args[0],
+        lossless_json2idl(&json).expect("Failed to convert back")
+    );
+}
+
+/// Verifies that NaN and the infinities are distinguished, and can be converted back.
+#[test]
+fn non_finite_floats_are_distinguished() {
+    let idl_type = IDLType::VecT(Box::new(IDLType::PrimT(PrimType::Float64)));
+    let idl_value = IDLValue::Vec(vec![
+        IDLValue::Float64(1.5),
+        IDLValue::Float64(f64::NAN),
+        IDLValue::Float64(f64::INFINITY),
+        IDLValue::Float64(f64::NEG_INFINITY),
+    ]);
+    for (non_finite_floats_as_null, expected_json) in [
+        (
+            false,
+            serde_json::json!([1.5, "NaN", "Infinity", "-Infinity"]),
+        ),
+        (true, serde_json::json!([1.5, null, null, null])),
+    ] {
+        let options = Idl2JsonOptions {
+            non_finite_floats_as_null,
+            ..Idl2JsonOptions::default()
+        };
+        assert_eq!(
+            expected_json,
+            idl2json_with_weak_names(&idl_value, &idl_type, &options)
+        );
+        assert_eq!(expected_json, idl2json(&idl_value, &options));
+    }
+    let options = Idl2JsonOptions::default();
+    let json = idl2json_with_weak_names(&idl_value, &idl_type, &options);
+    let IDLValue::Vec(floats) =
+        json2idl(&json, &idl_type, &options).expect("Failed to convert back")
+    else {
+        panic!("Expected a vec");
+    };
+    assert!(matches!(floats[..], [
+        IDLValue::Float64(finite),
+        IDLValue::Float64(nan),
+        IDLValue::Float64(infinity),
+        IDLValue::Float64(negative_infinity),
+    ] if finite == 1.5 && nan.is_nan() && infinity == f64::INFINITY && negative_infinity == f64::NEG_INFINITY));
+}
+
+/// Verifies that the differences between values are found, and that the JSON Patch turns one into the other.
+#[test]
+fn differences_are_given_as_json_patch_and_text() {
+    let did = r#"type Item = record { name: text; tags: vec text; owner: opt record { id: nat64 }; kind: variant { a; b: nat8 } };"#;
+    let options = Idl2JsonOptions {
+        prog: vec![IDLProg::from_str(did).expect("Failed to parse did")],
+        ..Idl2JsonOptions::default()
+    };
+    let idl_types = IDLTypes::from_str("(Item)").expect("Failed to parse type");
+    let before = parse_idl_args(
+        r#"(record { name = "x"; tags = vec { "a"; "b"; "c" }; owner = opt record { id = 4 }; kind = variant { a } })"#,
+    )
+    .expect("Failed to parse candid");
+    let after = parse_idl_args(
+        r#"(record { name = "y"; tags = vec { "a" }; owner = opt record { id = 5 }; kind = variant { b = 1 } }, "extra")"#,
+    )
+    .expect("Failed to parse candid");
+    let changes = idl_args_diff(&before, &after, &idl_types, &options);
+    assert_eq!(
+        r#"- 0.kind.a: null
++ 0.kind.b: "1"
+~ 0.name: "x" -> "y"
+~ 0.owner.id: "4" -> "5"
+- 0.tags.2: "c"
+- 0.tags.1: "b"
++ 1: "extra""#,
+        diff2text(&changes)
+    );
+    let mut json = idl_args2json_with_weak_names(&before, &idl_types, &options);
+    let patch: json_patch::Patch =
+        serde_json::from_value(diff2json_patch(&changes)).expect("Invalid JSON Patch");
+    json_patch::patch(&mut json, &patch).expect("Failed to apply JSON Patch");
+    assert_eq!(
+        idl_args2json_with_weak_names(&after, &idl_types, &options),
+        json
+    );
+    assert!(idl_args_diff(&before, &before, &idl_types, &options).is_empty());
+}
+
+/// Verifies that only the values at paths matching a pattern are converted.
+#[test]
+fn values_at_matching_paths_are_selected() {
+    let did = r#"
+        type Proposal = record { title: opt text; action: opt Action };
+        type Action = variant { Upgrade: record { wasm: blob }; Motion: record { text: text } };
+        type Proposals = vec Proposal;
+    "#;
+    let options = Idl2JsonOptions {
+        prog: vec![IDLProg::from_str(did).expect("Failed to parse did")],
+        bytes_as: Some(BytesFormat::Hex),
+        ..Idl2JsonOptions::default()
+    };
+    let idl_type = IDLType::VarT("Proposals".to_string());
+    let idl_value = parse_idl_args(
+        r#"(vec {
+            record { title = opt "Upgrade"; action = opt variant { Upgrade = record { wasm = blob "\01\02" } } };
+            record { title = null; action = opt variant { Motion = record { text = "Hello" } } };
+        })"#,
+    )
+    .expect("Failed to parse candid")
+    .args
+    .remove(0);
+    let select = |pattern: &str, options: &Idl2JsonOptions| {
+        idl2json_with_weak_names_select(
+            &idl_value,
+            &idl_type,
+            &PathPattern::from_str(pattern).expect("Invalid pattern"),
+            options,
+        )
+    };
+    // Optional values are transparent and `*` matches any element or variant tag.
+    assert_eq!(
+        vec![serde_json::json!("0102")],
+        select("*.action.*.wasm", &options)
+    );
+    assert_eq!(
+        vec![
+            serde_json::json!({"wasm": "0102"}),
+            serde_json::json!({"text": "Hello"}),
+        ],
+        select("*.action.*", &options)
+    );
+    assert_eq!(
+        vec![serde_json::json!(["Upgrade"])],
+        select("0.title", &options)
+    );
+    assert_eq!(Vec::<JsonValue>::new(), select("2.title", &options));
+    // Selected values are converted as they would be in place.
+    let hello = IDLValue::Vec(b"Hello".iter().map(|byte| IDLValue::Nat8(*byte)).collect());
+    let options = Idl2JsonOptions {
+        utf8_bytes_as_text: true,
+        ..options
+    };
+    assert_eq!(
+        vec![serde_json::json!("0102")],
+        select("*.action.*.wasm", &options)
+    );
+    assert_eq!(
+        vec![serde_json::json!(["Hello"])],
+        idl2json_select(
+            &IDLValue::Opt(Box::new(hello)),
+            &PathPattern::default(),
+            &options
+        )
+    );
+}
+
+/// Verifies that values are redacted by type and by path, keeping the structure around them.
+#[test]
+fn values_are_redacted_by_type_and_path() {
+    let did_filename = "internet_identity.did";
+    let did = fs::read_to_string(sample_file!(did_filename)).expect("Missing did");
+    let idl_types =
+        IDLTypes::from_str("(record { user_key: UserKey; expiration: nat64 }, SessionKey)")
+            .expect("Failed to parse type");
+    let idl_args = parse_idl_args(
+        r#"(record { user_key = blob "\01\02"; expiration = 5 : nat64 }, blob "\01\02")"#,
+    )
+    .expect("Failed to parse candid");
+    let options = Idl2JsonOptions {
+        prog: vec![IDLProg::from_str(&did).expect("Failed to parse did")],
+        redact_types: vec!["PublicKey".to_string()],
+        ..Idl2JsonOptions::default()
+    };
+    // Aliases of a redacted type are redacted too.
+    assert_eq!(
+        serde_json::json!([{"expiration": "5", "user_key": "[redacted]"}, "[redacted]"]),
+        idl_args2json_with_weak_names(&idl_args, &idl_types, &options)
+    );
+    assert_eq!(
+        serde_json::json!({"type": "string"}),
+        idl_type2json_schema(&IDLType::VarT("SessionKey".to_string()), &options)["$defs"]
+            ["SessionKey"]
+    );
+    // Paths are redacted without type information, and equal values have equal hashes.
+    let options = Idl2JsonOptions {
+        redact_paths: vec![PathPattern::from_str("user_key").expect("Invalid pattern")],
+        redact_as: RedactFormat::Sha256,
+        ..Idl2JsonOptions::default()
+    };
+    let json = idl_args2json(&idl_args, &options);
+    let redacted = json[0]["user_key"].as_str().expect("Not redacted");
+    assert!(redacted.starts_with("[redacted] sha256:"));
+    assert_eq!(serde_json::json!("5"), json[0]["expiration"]);
+    assert_eq!(serde_json::json!([1, 2]), json[1]);
+    let copy = idl_args2json(&idl_args, &options);
+    assert_eq!(json, copy);
+}
+
+/// Verifies that long vectors and texts are truncated, with a marker describing what is left out.
+#[test]
+fn long_vectors_and_texts_are_truncated() {
+    let idl_type = IDLType::from_str("record { names: vec text; bytes: blob }").expect("Bad type");
+    let idl_value = parse_idl_args(
+        r#"(record { names = vec { "Ann"; "Zoë Smith"; "Bob"; "Cy" }; bytes = blob "\01\02\03" })"#,
+    )
+    .expect("Failed to parse candid")
+    .args
+    .remove(0);
+    let options = Idl2JsonOptions {
+        max_vec_elements: Some(2),
+        max_text_length: Some(3),
+        ..Idl2JsonOptions::default()
+    };
+    let expected_json = serde_json::json!({
+        "bytes": [1, 2, 3],
+        "names": [
+            "Ann",
+            {"text": "Zoë", "…": "6 more characters"},
+            {"…": "2 more elements"},
+        ],
+    });
+    assert_eq!(
+        expected_json,
+        idl2json_with_weak_names(&idl_value, &idl_type, &options)
+    );
+    assert_eq!(expected_json, idl2json(&idl_value, &options));
+    // Values within the limits are unchanged.
+    let options = Idl2JsonOptions {
+        max_vec_elements: Some(4),
+        max_text_length: Some(9),
+        ..Idl2JsonOptions::default()
+    };
+    assert_eq!(
+        idl2json(&idl_value, &Idl2JsonOptions::default()),
+        idl2json(&idl_value, &options)
+    );
+}
+
+/// Verifies that the shape of a value is summarized per type and per path.
+#[test]
+fn values_are_summarized() {
+    let idl_type = IDLType::from_str(
+        "record { items: vec record { key: blob; kind: variant { a; b } }; note: opt text }",
+    )
+    .expect("Bad type");
+    let idl_value = parse_idl_args(
+        r#"(record {
+            items = vec {
+                record { key = blob "\01\02"; kind = variant { a } };
+                record { key = blob "\03"; kind = variant { b } };
+                record { key = blob ""; kind = variant { a } };
+            };
+            note = opt "Hi";
+        })"#,
+    )
+    .expect("Failed to parse candid")
+    .args
+    .remove(0);
+    assert_eq!(
+        serde_json::json!({
+            "max_depth": 4,
+            "types": {"blob": 3, "null": 3, "opt": 1, "record": 4, "text": 1, "variant": 3, "vec": 1},
+            "paths": {
+                ".": {"types": {"record": 1}},
+                "items": {"types": {"vec": 1}, "lengths": {"min": 3, "max": 3, "total": 3}},
+                "items.*": {"types": {"record": 3}},
+                "items.*.key": {"types": {"blob": 3}, "bytes": 3},
+                "items.*.kind": {"types": {"variant": 3}, "tags": {"a": 2, "b": 1}},
+                "items.*.kind.a": {"types": {"null": 2}},
+                "items.*.kind.b": {"types": {"null": 1}},
+                "note": {"types": {"opt": 1, "text": 1}},
+            },
+        }),
+        idl2summary_with_weak_names(&idl_value, &idl_type, &Idl2JsonOptions::default())
+    );
+    // The text format names the fields, so the summary is the same without a type.
+    assert_eq!(
+        idl2summary_with_weak_names(&idl_value, &idl_type, &Idl2JsonOptions::default()),
+        idl2summary(&idl_value, &Idl2JsonOptions::default())
+    );
+    // Numbers have the type declared for them, through any number of aliases.
+    let options = Idl2JsonOptions {
+        prog: vec![
+            IDLProg::from_str("type A = B; type B = C; type C = record { n : nat8 };")
+                .expect("Failed to parse did"),
+        ],
+        ..Idl2JsonOptions::default()
+    };
+    let idl_value = parse_idl_args("(record { n = 1 })")
+        .expect("Failed to parse candid")
+        .args
+        .remove(0);
+    for type_name in ["A", "C"] {
+        let summary = idl2summary_with_weak_names(
+            &idl_value,
+            &IDLType::VarT(type_name.to_string()),
+            &options,
+        );
+        assert_eq!(
+            serde_json::json!({"nat8": 1}),
+            summary["paths"]["n"]["types"]
+        );
+    }
+}
+
+#[test]
+fn values_may_be_serialized_in_other_formats() {
+    let json = serde_json::json!({"a": [1, -2, 1.5], "b": "x", "c": null, "d": true});
+    let serialize_as = |output_format| {
+        serialize(
+            &json,
+            &Idl2JsonOptions {
+                output_format,
+                compact: true,
+                ..Idl2JsonOptions::default()
+            },
+        )
+        .expect("Failed to serialize")
+    };
+    assert_eq!(
+        br#"{"a":[1,-2,1.5],"b":"x","c":null,"d":true}"#.to_vec(),
+        serialize_as(OutputFormat::Json)
+    );
+    #[cfg(feature = "formats")]
+    {
+        assert_eq!(
+            vec![
+                0xa4, 0x61, b'a', 0x83, 0x01, 0x21, 0xf9, 0x3e, 0x00, 0x61, b'b', 0x61, b'x', 0x61,
+                b'c', 0xf6, 0x61, b'd', 0xf5
+            ],
+            serialize_as(OutputFormat::Cbor)
+        );
+        assert_eq!(
+            vec![
+                0x84, 0xa1, b'a', 0x93, 0x01, 0xfe, 0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0, 0xa1, b'b',
+                0xa1, b'x', 0xa1, b'c', 0xc0, 0xa1, b'd', 0xc3
+            ],
+            serialize_as(OutputFormat::Msgpack)
+        );
+        assert_eq!(
+            "a:\n- 1\n- -2\n- 1.5\nb: x\nc: null\nd: true\n",
+            String::from_utf8(serialize_as(OutputFormat::Yaml)).expect("Not UTF-8")
+        );
+        // TOML has no null.
+        let json = serde_json::json!({"a": [1, -2, 1.5], "b": "x"});
+        assert_eq!(
+            "a = [1, -2, 1.5]\nb = \"x\"\n",
+            String::from_utf8(
+                serialize(
+                    &json,
+                    &Idl2JsonOptions {
+                        output_format: OutputFormat::Toml,
+                        compact: true,
+                        ..Idl2JsonOptions::default()
+                    }
+                )
+                .expect("Failed to serialize")
+            )
+            .expect("Not UTF-8")
+        );
+    }
+}
+
+#[test]
+fn vectors_of_records_are_tables() {
+    let prog = IDLProg::from_str(
+        r#"
+        type Status = variant { ok; failed : record { reason : text; code : opt nat8 } };
+        type Row = record { id : nat64; owner : blob; status : Status; note : opt text };
+        "#,
+    )
+    .expect("Failed to parse did");
+    let idl_type = IDLType::from_str("vec Row").expect("Bad type");
+    let idl_value = parse_idl_args(
+        r#"(vec {
+            record { id = 1 : nat64; owner = blob "\01\02"; status = variant { failed = record { reason = "x, \"y\"" } } };
+            record { id = 2 : nat64; owner = blob ""; status = variant { ok }; note = opt "z" };
+        })"#,
+    )
+    .expect("Failed to parse candid")
+    .args
+    .remove(0);
+    let options = Idl2JsonOptions {
+        prog: vec![prog],
+        bytes_as: Some(BytesFormat::Hex),
+        ..Idl2JsonOptions::default()
+    };
+    // Every column declared by the type is present, in key order.
+    let rows = idl2table_with_weak_names(&idl_value, &idl_type, &options);
+    assert_eq!(
+        serde_json::json!([
+            {"id": "1", "note": null, "owner": "0102", "status": "failed", "status.failed.code": null, "status.failed.reason": "x, \"y\""},
+            {"id": "2", "note": "z", "owner": "", "status": "ok", "status.failed.code": null, "status.failed.reason": null},
+        ]),
+        rows
+    );
+    let table = |output_format| {
+        String::from_utf8(
+            serialize(
+                &rows,
+                &Idl2JsonOptions {
+                    output_format,
+                    ..Idl2JsonOptions::default()
+                },
+            )
+            .expect("Failed to serialize"),
+        )
+        .expect("Not UTF-8")
+    };
+    assert_eq!(
+        "id,note,owner,status,status.failed.code,status.failed.reason\n\
+         1,,0102,failed,,\"x, \"\"y\"\"\"\n\
+         2,z,,ok,,\n",
+        table(OutputFormat::Csv)
+    );
+    assert_eq!(
+        "id\tnote\towner\tstatus\tstatus.failed.code\tstatus.failed.reason\n\
+         1\t\t0102\tfailed\t\tx, \"y\"\n\
+         2\tz\t\tok\t\t\n",
+        table(OutputFormat::Tsv)
+    );
+    // Without a type, the columns are those of the values.
+    assert_eq!(
+        serde_json::json!([
+            {"id": "1", "owner": "0102", "status": "failed", "status.failed.reason": "x, \"y\""},
+            {"id": "2", "owner": "", "status": "ok", "note": "z"},
+        ]),
+        idl2table(&idl_value, &options)
+    );
+}
+
+#[test]
+fn types_may_be_listed_expanded_and_printed() {
+    let prog = IDLProg::from_str(
+        r#"type Id = nat64;
+        type Item = record { id : Id; tags : vec text; "kind" : variant { small; large : Id } };
+        type Tree = variant { leaf : Item; node : vec Tree };
+        service : (record { owner : principal }) -> {
+            get : (Id) -> (opt Item) query;
+            put : (Item, blob) -> ();
+        }"#,
+    )
+    .expect("Failed to parse did");
+    let names: Vec<&str> = polyfill::idl_prog::get_types(&prog)
+        .map(|binding| binding.id.as_str())
+        .collect();
+    assert_eq!(vec!["Id", "Item", "Tree"], names);
+    let methods: Vec<String> = polyfill::idl_prog::get_methods(&prog)
+        .iter()
+        .map(|(name, func_type)| {
+            format!("{name} : {}", polyfill::idl_type::func_to_string(func_type))
+        })
+        .collect();
+    assert_eq!(
+        vec!["get : (Id) -> (opt Item) query", "put : (Item, blob) -> ()"],
+        methods
+    );
+
+    let tree = IDLType::VarT("Tree".to_string());
+    let expand = |depth| {
+        polyfill::idl_type::to_string(&polyfill::idl_prog::expand_type(&prog, &tree, depth))
+    };
+    assert_eq!("Tree", expand(Some(0)));
+    assert_eq!("variant { leaf : Item; node : vec Tree }", expand(Some(1)));
+    // Recursive types are not expanded where they recur.
+    assert_eq!(
+        "variant { leaf : record { id : nat64; kind : variant { large : nat64; small }; tags : vec text }; node : vec Tree }",
+        expand(None)
+    );
+    assert_eq!(
+        "record {\n  id : Id;\n  kind : variant {\n    large : Id;\n    small;\n  };\n  tags : vec text;\n}",
+        polyfill::idl_type::to_pretty_string(
+            &polyfill::idl_prog::get_type(&prog, "Item").expect("No Item type")
+        )
+    );
+}
+
+#[test]
+fn values_are_checked_against_types() {
+    let prog = IDLProg::from_str(
+        "type Account = record { owner : principal; subaccount : opt blob };
+         type Transfer = record { to : Account; amount : nat; memo : opt nat64; kind : variant { fast; slow : nat8 } };",
+    )
+    .expect("Failed to parse did");
+    let options = Idl2JsonOptions {
+        prog: vec![prog],
+        ..Idl2JsonOptions::default()
+    };
+    let transfer = IDLType::VarT("Transfer".to_string());
+    let check = |idl_str: &str| {
+        let idl_args = parse_idl_args(idl_str).expect("Failed to parse candid");
+        idl_args_check(
+            &idl_args,
+            &IDLTypes {
+                args: vec![transfer.clone(), IDLType::PrimT(PrimType::Int)],
+            },
+            &options,
+        )
+        .into_iter()
+        .map(|nonconformance| format!("{}: {}", nonconformance.path, nonconformance.reason))
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        Vec::<String>::new(),
+        check(
+            r#"(record { to = record { owner = principal "aaaaa-aa" }; amount = 5; kind = variant { slow = 255 }; extra = "x" }, 3 : nat)"#
+        )
+    );
+    assert_eq!(
+        vec![
+            "0.to.owner: expected principal, found \"aaaaa-aa\"",
+            "0.to.subaccount: expected blob, found 1",
+            "0.kind.slow: 256 is out of range for nat8",
+            "0.memo: -1 is out of range for nat64",
+            "0.amount: missing field of type nat",
+            "1: missing argument of type int",
+        ],
+        check(
+            r#"(record { to = record { owner = "aaaaa-aa"; subaccount = opt 1 }; memo = opt -1; kind = variant { slow = 256 } })"#
+        )
+    );
+    assert_eq!(
+        vec![
+            "0: expected Transfer, found a vec",
+            "1: expected int, found \"1\"",
+        ],
+        check(r#"(vec {}, "1")"#)
+    );
+    let idl_args = parse_idl_args(
+        r#"(record { to = record { owner = principal "aaaaa-aa" }; amount = 1; kind = variant { medium } })"#,
+    )
+    .expect("Failed to parse candid");
+    assert_eq!(
+        serde_json::json!([{"path": "kind", "reason": "variant tag medium is not in variant { fast; slow : nat8 }"}]),
+        check2json(&idl_check(&idl_args.args[0], &transfer, &options))
+    );
+}
+
+/// Verifies that selecting values does not reveal those that are redacted, by path or by type.
+#[test]
+fn selected_values_are_redacted() {
+    let did = r#"
+        type Devices = record { devices: vec Device };
+        type Device = record { pubkey: Key; alias: text };
+        type Key = PublicKey;
+        type PublicKey = blob;
+    "#;
+    let idl_type = IDLType::VarT("Devices".to_string());
+    let idl_value = parse_idl_args(
+        r#"(record { devices = vec { record { pubkey = blob "\01\02"; alias = "phone" } } })"#,
+    )
+    .expect("Failed to parse candid")
+    .args
+    .remove(0);
+    let options = Idl2JsonOptions {
+        prog: vec![IDLProg::from_str(did).expect("Failed to parse did")],
+        redact_paths: vec![PathPattern::from_str("devices.*").expect("Invalid pattern")],
+        ..Idl2JsonOptions::default()
+    };
+    let select = |pattern: &str, options: &Idl2JsonOptions| {
+        let pattern = PathPattern::from_str(pattern).expect("Invalid pattern");
+        (
+            idl2json_with_weak_names_select(&idl_value, &idl_type, &pattern, options),
+            idl2json_select(&idl_value, &pattern, options),
+        )
+    };
+    // Values inside a redacted value are not selected; a selected redacted value is redacted.
+    let nothing = Vec::<JsonValue>::new();
+    assert_eq!(
+        (nothing.clone(), nothing.clone()),
+        select("devices.0.pubkey", &options)
+    );
+    let redacted = vec![serde_json::json!("[redacted]")];
+    assert_eq!(
+        (redacted.clone(), redacted.clone()),
+        select("devices.0", &options)
+    );
+    // Types are redacted through aliases.
+    let options = Idl2JsonOptions {
+        redact_paths: vec![],
+        redact_types: vec!["PublicKey".to_string()],
+        ..options
+    };
+    assert_eq!(redacted, select("devices.0.pubkey", &options).0);
+    assert_eq!(
+        vec![serde_json::json!("phone")],
+        select("devices.*.alias", &options).0
+    );
+}
@@ -0,0 +1,105 @@
+//! Truncation of long vectors and texts, so that the output stays readable.
+//!
+//! What is left out is described by an object with the key `…`, which cannot be mistaken for data:
+//! - A vector is truncated to its first elements, followed by `{"…": "4213 more elements"}`.
+//! - A text is replaced by `{"text": "The first characters", "…": "4213 more characters"}`.
+use crate::{Idl2JsonOptions, JsonValue};
+use candid::types::value::IDLValue;
+use serde_json::json;
+
+/// The key of objects describing what has been left out.
+const MARKER: &str = "…";
+
+/// Converts a text, truncated to at most `options.max_text_length` characters.
+pub(crate) fn convert_text(text: &str, options: &Idl2JsonOptions) -> JsonValue {
+    if let Some(max_length) = options.max_text_length {
+        if let Some((end, _)) = text.char_indices().nth(max_length) {
+            let more = text[end..].chars().count();
+            return json!({ "text": &text[..end], MARKER: format!("{more} more characters") });
+        }
+    }
+    JsonValue::String(text.to_string())
+}
+
+/// Converts the elements of a vector, truncated to at most `options.max_vec_elements` elements.
+///
+/// Elements that are left out are not converted.
+pub(crate) fn convert_elements<F>(
+    items: &[IDLValue],
+    options: &Idl2JsonOptions,
+    convert: F,
+) -> JsonValue
+where
+    F: Fn(usize, &IDLValue) -> JsonValue,
+{
+    let shown = options
+        .max_vec_elements
+        .map_or(items.len(), |max_elements| max_elements.min(items.len()));
+    let mut elements: Vec<JsonValue> = items[..shown]
+        .iter()
+        .enumerate()
+        .map(|(index, item)| convert(index, item))
+        .collect();
+    if shown < items.len() {
+        let more = items.len() - shown;
+        elements.push(json!({ MARKER: format!("{more} more elements") }));
+    }
+    JsonValue::Array(elements)
+}
+
+/// The JSON schema for a text, as it may be truncated.
+pub(crate) fn text_schema(options: &Idl2JsonOptions) -> JsonValue {
+    if options.max_text_length.is_none() {
+        return json!({ "type": "string" });
+    }
+    json!({
+        "anyOf": [
+            { "type": "string" },
+            {
+                "type": "object",
+                "properties": {
+                    "text": { "type": "string" },
+                    MARKER: { "type": "string" },
+                },
+                "required": ["text", MARKER],
+                "additionalProperties": false,
+            },
+        ],
+    })
+}
+
+/// The JSON schema for the elements of a vector, as it may be truncated.
+pub(crate) fn element_schema(item_schema: JsonValue, options: &Idl2JsonOptions) -> JsonValue {
+    if options.max_vec_elements.is_none() {
+        return item_schema;
+    }
+    json!({
+        "anyOf": [
+            item_schema,
+            {
+                "type": "object",
+                "properties": { MARKER: { "type": "string" } },
+                "required": [MARKER],
+                "additionalProperties": false,
+            },
+        ],
+    })
+}
+
+/// The TypeScript type for a text, as it may be truncated.
+pub(crate) fn text_typescript(options: &Idl2JsonOptions) -> &'static str {
+    if options.max_text_length.is_none() {
+        "string"
+    } else {
+        r#"string | { text: string; "…": string }"#
+    }
+}
+
+/// The TypeScript type for the elements of a vector, as it may be truncated.
+pub(crate) fn element_typescript(item_typescript: String, options: &Idl2JsonOptions) -> String {
+    if options.max_vec_elements.is_none() {
+        item_typescript
+    } else {
+        format!(r#"{item_typescript} | {{ "…": string }}"#)
+    }
+}
@@ -11,8 +11,13 @@ use std::iter;
 
 use crate::{
     bytes::{convert_blob, convert_bytes},
-    idl2json,
-    untyped_conversion::convert_non_bytes_array,
+    generic_value::{generic_value2json, is_generic_value_type},
+    key_order::{func_object, json_object, ObjectField},
+    lossless::idl2lossless_json,
+    path::ValuePath,
+    redact::{is_redacted, redact},
+    truncate::{convert_elements, convert_text},
+    untyped_conversion::{convert_float, convert_non_bytes_array, convert_number, idl2json_at},
     Idl2JsonOptions,
 };
 
@@ -21,7 +26,10 @@ use crate::{
 /// - Key names MAY be incorrect.  They are provided on a best-effort basis.
 /// - If types are incompatible with the data, the data wins.
 /// - Data is never omitted.
-/// - Fields are never added, even if the schema suggests that some fields are missing.
+/// - Fields are not added, even if the schema suggests that some fields are missing,
+///   unless `options.fill_missing_fields` is set.  In that case fields that Candid
+///   subtyping allows to be absent (`opt T`, `null` and `reserved`) are added with their
+///   default values.
 ///
 /// The data is preserved at all cost, the schema is applied only to make the data easier to understand and use.
 ///
@@ -33,104 +41,188 @@ pub fn idl2json_with_weak_names(
     idl_type: &IDLType,
     options: &Idl2JsonOptions,
 ) -> JsonValue {
+    idl2json_with_weak_names_at(idl, idl_type, &ValuePath::ROOT, options)
+}
+
+/// Converts a candid IDLValue at the given path to a serde JsonValue, with keys as names where possible.
+pub(crate) fn idl2json_with_weak_names_at(
+    idl: &IDLValue,
+    idl_type: &IDLType,
+    path: &ValuePath,
+    options: &Idl2JsonOptions,
+) -> JsonValue {
+    if is_redacted(Some(idl_type), path, options) {
+        return redact(idl, options);
+    }
+    if options.lossless {
+        return idl2lossless_json(idl, Some(idl_type), path, options);
+    }
+    if is_generic_value_type(idl_type, options) {
+        if let Some(json) = generic_value2json(idl, path, options) {
+            return json;
+        }
+    }
     match (idl, idl_type) {
         (idl, IDLType::VarT(type_name)) => {
             if let Some(resolved_type) = get_type_from_any(&options.prog, type_name) {
-                idl2json_with_weak_names(idl, &resolved_type, options)
+                idl2json_with_weak_names_at(idl, &resolved_type, path, options)
             } else {
                 // TODO: Return a set of warnings.  Under the "best effort" mantra, we proceed as
                 // best we can but it would be nice to provide some feedback.
-                idl2json(idl, options)
+                idl2json_at(idl, path, options)
             }
         }
-        (IDLValue::Blob(bytes), _) => convert_blob(bytes, options),
+        (IDLValue::Blob(bytes), _) => convert_blob(bytes, path, options),
         (IDLValue::Bool(bool), _) => JsonValue::Bool(*bool),
+        // Candid text gives `null` for absent options, which yaml2candid reads as an empty sequence.
+        (IDLValue::Null, IDLType::OptT(_)) if options.output_format.is_yaml() => {
+            JsonValue::Array(vec![])
+        }
         (IDLValue::Null, _) => JsonValue::Null,
-        (IDLValue::Text(s), _) => JsonValue::String(s.clone()),
-        (IDLValue::Number(s), _) => JsonValue::String(s.clone()), // Unspecified number type
-        (IDLValue::Float64(f), _) => serde_json::Number::from_f64(*f)
-            .map(JsonValue::Number)
-            .unwrap_or_else(|| JsonValue::String("NaN".to_string())),
+        (IDLValue::Text(s), _) => convert_text(s, options),
+        (IDLValue::Number(s), _) => convert_number(s.clone(), options), // Unspecified number type
+        (IDLValue::Float64(f), _) => convert_float(*f, options),
         (IDLValue::Opt(value), IDLType::OptT(opt_type)) => {
-            JsonValue::Array(vec![idl2json_with_weak_names(value, opt_type, options)])
+            JsonValue::Array(vec![idl2json_with_weak_names_at(
+                value, opt_type, path, options,
+            )])
         }
-        (IDLValue::Opt(_value), _) => idl2json(idl, options), // Fallback for mismatched types
+        (IDLValue::Opt(_value), _) => idl2json_at(idl, path, options), // Fallback for mismatched types
         (IDLValue::Vec(value), IDLType::VecT(item_type)) => match &**item_type {
-            IDLType::PrimT(prim_t) if *prim_t == PrimType::Nat8 => convert_bytes(value, options)
-                .unwrap_or_else(|_| convert_non_bytes_array(value, options)),
-            _ => JsonValue::Array(
-                value
-                    .iter()
-                    .map(|item| idl2json_with_weak_names(item, item_type, options))
-                    .collect(),
-            ),
+            IDLType::PrimT(prim_t) if *prim_t == PrimType::Nat8 => {
+                convert_bytes(value, path, options)
+                    .unwrap_or_else(|_| convert_non_bytes_array(value, path, options))
+            }
+            _ => convert_elements(value, options, |index, item| {
+                idl2json_with_weak_names_at(item, item_type, &path.index(index), options)
+            }),
         },
-        (IDLValue::Vec(_value), _) => idl2json(idl, options), // Fallback for mismatched types
-        (IDLValue::Record(value), IDLType::RecordT(record_types)) => JsonValue::Object(
-            value
+        (IDLValue::Vec(_value), _) => idl2json_at(idl, path, options), // Fallback for mismatched types
+        (IDLValue::Record(value), IDLType::RecordT(record_types)) => {
+            let mut fields: Vec<ObjectField> = value
                 .iter()
-                .map(|field| convert_idl_field(field, record_types, options))
-                .collect(),
-        ),
-        (IDLValue::Record(_value), _) => idl2json(idl, options), // Fallback for mismatched types
-        (IDLValue::Variant(field), IDLType::VariantT(record_types)) => JsonValue::Object(
-            vec![convert_idl_field(&field.0, record_types, options)]
-                .into_iter()
-                .collect(),
-        ),
-        (IDLValue::Variant(_field), _) => idl2json(idl, options), // Fallback for mismatched types
+                .map(|field| convert_idl_field(field, record_types, path, options))
+                .collect();
+            if options.fill_missing_fields {
+                fields.extend(missing_fields(value, record_types, options));
+            }
+            json_object(fields, Some(record_types), options)
+        }
+        (IDLValue::Record(_value), _) => idl2json_at(idl, path, options), // Fallback for mismatched types
+        (IDLValue::Variant(field), IDLType::VariantT(record_types)) => {
+            let (_, key, value) = convert_idl_field(&field.0, record_types, path, options);
+            JsonValue::Object(vec![(key, value)].into_iter().collect())
+        }
+        (IDLValue::Variant(_field), _) => idl2json_at(idl, path, options), // Fallback for mismatched types
         (IDLValue::Principal(p), _) => JsonValue::String(p.to_string()),
         (IDLValue::Service(p), _) => JsonValue::String(p.to_string()),
-        (IDLValue::Func(p, c), _) => JsonValue::Object(
-            vec![
-                ("principal".to_string(), JsonValue::String(p.to_string())),
-                ("code".to_string(), JsonValue::String(c.to_string())),
-            ]
-            .into_iter()
-            .collect(),
-        ),
+        (IDLValue::Func(p, c), _) => func_object(p.to_string(), c.to_string(), options),
         (IDLValue::None, _) => JsonValue::Array(vec![]),
-        (IDLValue::Int(i), _) => JsonValue::String(i.to_string()),
-        (IDLValue::Nat(i), _) => JsonValue::String(i.to_string()),
+        (IDLValue::Int(i), _) => convert_number(i.to_string(), options),
+        (IDLValue::Nat(i), _) => convert_number(i.to_string(), options),
         (IDLValue::Nat8(i), _) => JsonValue::Number(serde_json::Number::from(*i)),
         (IDLValue::Nat16(i), _) => JsonValue::Number(serde_json::Number::from(*i)),
         (IDLValue::Nat32(i), _) => JsonValue::Number(serde_json::Number::from(*i)),
-        (IDLValue::Nat64(i), _) => JsonValue::String(i.to_string()),
+        (IDLValue::Nat64(i), _) => convert_number(i.to_string(), options),
         (IDLValue::Int8(i), _) => JsonValue::Number(serde_json::Number::from(*i)),
         (IDLValue::Int16(i), _) => JsonValue::Number(serde_json::Number::from(*i)),
         (IDLValue::Int32(i), _) => JsonValue::Number(serde_json::Number::from(*i)),
-        (IDLValue::Int64(i), _) => JsonValue::String(i.to_string()),
-        (IDLValue::Float32(f), _) => {
-            // As far as I can see, JsonValue does not have an explicit NaN type so we provide NaN as a string.
-            serde_json::Number::from_f64(*f as f64)
-                .map(JsonValue::Number)
-                .unwrap_or_else(|| JsonValue::String("NaN".to_string()))
-        }
+        (IDLValue::Int64(i), _) => convert_number(i.to_string(), options),
+        (IDLValue::Float32(f), _) => convert_float(f64::from(*f), options),
         (IDLValue::Reserved, _) => JsonValue::String(idl.to_string()),
     }
 }
 
-/// Returns a typed IDLField as a (key, value) pair.
+/// Follows type names, through the .did files in the options, until a type that is not a name is found.
+///
+/// Returns None if a name is not defined or the names are cyclic.
+pub fn resolve_type(idl_type: &IDLType, options: &Idl2JsonOptions) -> Option<IDLType> {
+    let mut idl_type = idl_type.clone();
+    for _ in 0..=options
+        .prog
+        .iter()
+        .map(|prog| prog.decs.len())
+        .sum::<usize>()
+    {
+        match idl_type {
+            IDLType::VarT(name) => idl_type = get_type_from_any(&options.prog, &name)?,
+            idl_type => return Some(idl_type),
+        }
+    }
+    None
+}
+
+/// Returns a typed IDLField as an (id, key, value) triple.
 ///
 /// - The key is obtained from the type, if possible, else is the raw key as given.
 /// - The value is a typed conversion, if the type is as specified, else it is converted without the benefit of type information.
 fn convert_idl_field(
     field: &IDLField,
     record_types: &[TypeField],
+    path: &ValuePath,
     options: &Idl2JsonOptions,
-) -> (String, JsonValue) {
-    let field_id = field.id.get_id();
-    let field_type = record_types
+) -> ObjectField {
+    let (key, field_type) = typed_field(field, record_types);
+    let value = match field_type {
+        Some(field_type) => {
+            idl2json_with_weak_names_at(&field.val, field_type, &path.key(&key), options)
+        }
+        None => idl2json_at(&field.val, &path.key(&key), options),
+    };
+    (field.id.get_id(), key, value)
+}
+
+/// Returns the key and, if declared, the type of a record field or variant.
+///
+/// The key is the name given by the type, if possible, else the raw key as given.
+pub(crate) fn typed_field<'a>(
+    field: &IDLField,
+    record_types: &'a [TypeField],
+) -> (String, Option<&'a IDLType>) {
+    record_types
         .iter()
-        .find(|field_type| field_type.label.get_id() == field_id);
-    field_type
-        .map(|field_type| {
-            (
-                field_type.label.to_string(),
-                idl2json_with_weak_names(&field.val, &field_type.typ, options),
-            )
+        .find(|field_type| field_type.label.get_id() == field.id.get_id())
+        .map(|field_type| (field_type.label.to_string(), Some(&field_type.typ)))
+        .unwrap_or_else(|| (field.id.to_string(), None))
+}
+
+/// Returns the fields that are declared in the type but absent from the value, with default values.
+///
+/// Fields that have no default value under Candid subtyping rules are not returned.
+fn missing_fields<'a>(
+    fields: &'a [IDLField],
+    record_types: &'a [TypeField],
+    options: &'a Idl2JsonOptions,
+) -> impl Iterator<Item = ObjectField> + 'a {
+    record_types
+        .iter()
+        .filter(move |field_type| {
+            let field_id = field_type.label.get_id();
+            !fields.iter().any(|field| field.id.get_id() == field_id)
+        })
+        .filter_map(move |field_type| {
+            default_value(&field_type.typ, options).map(|default| {
+                (
+                    field_type.label.get_id(),
+                    field_type.label.to_string(),
+                    default,
+                )
+            })
         })
-        .unwrap_or_else(|| (field.id.to_string(), idl2json(&field.val, options)))
+}
+
+/// The JSON representation of the value that Candid subtyping supplies for an absent field of the given type, if any.
+pub(crate) fn default_value(idl_type: &IDLType, options: &Idl2JsonOptions) -> Option<JsonValue> {
+    match idl_type {
+        IDLType::VarT(type_name) => get_type_from_any(&options.prog, type_name)
+            .and_then(|resolved_type| default_value(&resolved_type, options)),
+        IDLType::OptT(_) => Some(JsonValue::Array(vec![])),
+        IDLType::PrimT(PrimType::Null) => Some(JsonValue::Null),
+        IDLType::PrimT(PrimType::Reserved) => {
+            Some(JsonValue::String(IDLValue::Reserved.to_string()))
+        }
+        _ => None,
+    }
 }
 
 /// Converts a candid IDLArgs to a serde JsonValue, with keys as names where possible.
@@ -0,0 +1,252 @@
+//! TypeScript declarations describing the JSON produced by the typed conversions.
+use crate::{
+    bytes::may_recognize_bytes,
+    generic_value::is_generic_value_type,
+    polyfill::idl_prog,
+    redact::is_redacted_type,
+    truncate::{element_typescript, text_typescript},
+    typed_conversion::default_value,
+    BytesFormat, Idl2JsonOptions,
+};
+use candid_parser::types::{Dec, IDLType, IDLTypes, PrimType, TypeField};
+use std::collections::{BTreeSet, VecDeque};
+
+/// Returns TypeScript declarations for the JSON produced by `idl2json_with_weak_names()` for the given type.
+///
+/// - The given type is declared with the given name.
+/// - Named types are resolved using the types in `options.prog` and are declared as well.
+/// - Named types that cannot be found are declared as `unknown`, as values of those types
+///   are converted without the benefit of type information.
+pub fn idl_type2typescript(name: &str, idl_type: &IDLType, options: &Idl2JsonOptions) -> String {
+    let mut builder = TypescriptBuilder::new(options);
+    builder.declared.insert(name.to_string());
+    let typescript = builder.typescript(idl_type, 0);
+    builder.declare(name, &typescript);
+    builder.finish()
+}
+
+/// Returns TypeScript declarations for the JSON produced by `idl_args2json_with_weak_names()` for the given types.
+pub fn idl_types2typescript(name: &str, idl_types: &IDLTypes, options: &Idl2JsonOptions) -> String {
+    let mut builder = TypescriptBuilder::new(options);
+    builder.declared.insert(name.to_string());
+    let typescript = format!(
+        "[{}]",
+        idl_types
+            .args
+            .iter()
+            .map(|idl_type| builder.typescript(idl_type, 0))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    builder.declare(name, &typescript);
+    builder.finish()
+}
+
+/// Returns TypeScript declarations for all the types defined in `options.prog`.
+///
+/// If a type is defined in several progs, the first definition is used, as in the conversions.
+pub fn idl_progs2typescript(options: &Idl2JsonOptions) -> String {
+    let mut builder = TypescriptBuilder::new(options);
+    for prog in &options.prog {
+        for dec in &prog.decs {
+            if let Dec::TypD(binding) = dec {
+                builder.enqueue(&binding.id);
+            }
+        }
+    }
+    builder.finish()
+}
+
+/// Creates TypeScript declarations, collecting the named types on the way.
+struct TypescriptBuilder<'a> {
+    options: &'a Idl2JsonOptions,
+    /// The declarations made so far.
+    declarations: Vec<String>,
+    /// The names of types that have been declared or are waiting to be declared.
+    declared: BTreeSet<String>,
+    /// Named types waiting to be declared.
+    queue: VecDeque<String>,
+}
+
+impl<'a> TypescriptBuilder<'a> {
+    fn new(options: &'a Idl2JsonOptions) -> Self {
+        TypescriptBuilder {
+            options,
+            declarations: vec![],
+            declared: BTreeSet::new(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Adds a type declaration.
+    fn declare(&mut self, name: &str, typescript: &str) {
+        self.declarations
+            .push(format!("export type {name} = {typescript};\n"));
+    }
+
+    /// Queues a named type for declaration, unless it has been seen already.
+    fn enqueue(&mut self, name: &str) {
+        if self.declared.insert(name.to_string()) {
+            self.queue.push_back(name.to_string());
+        }
+    }
+
+    /// Declares all the queued named types and returns all the declarations.
+    fn finish(mut self) -> String {
+        while let Some(name) = self.queue.pop_front() {
+            let typescript = self
+                .options
+                .prog
+                .iter()
+                .find_map(|prog| idl_prog::get_type(prog, &name))
+                .map(|idl_type| self.typescript(&idl_type, 0))
+                .unwrap_or_else(|| "unknown".to_string());
+            self.declare(&name, &typescript);
+        }
+        self.declarations.join("")
+    }
+
+    /// The TypeScript type for a type, indented by the given number of levels.
+    fn typescript(&mut self, idl_type: &IDLType, indent: usize) -> String {
+        if is_redacted_type(idl_type, self.options) {
+            // Redacted values are replaced by a placeholder or hash.
+            return "string".to_string();
+        }
+        if is_generic_value_type(idl_type, self.options) {
+            // Generic values rendered as plain JSON may be any JSON.
+            return "unknown".to_string();
+        }
+        match idl_type {
+            IDLType::VarT(type_name) => {
+                self.enqueue(type_name);
+                type_name.clone()
+            }
+            IDLType::PrimT(prim_type) => prim_typescript(prim_type, self.options).to_string(),
+            IDLType::OptT(item_type) => format!("[] | [{}]", self.typescript(item_type, indent)),
+            IDLType::VecT(item_type) => match &**item_type {
+                IDLType::PrimT(PrimType::Nat8) => self.bytes_typescript(),
+                _ => format!(
+                    "Array<{}>",
+                    element_typescript(self.typescript(item_type, indent), self.options)
+                ),
+            },
+            IDLType::RecordT(fields) => self.record_typescript(fields, indent),
+            IDLType::VariantT(fields) => {
+                if fields.is_empty() {
+                    return "never".to_string();
+                }
+                fields
+                    .iter()
+                    .map(|field| {
+                        format!(
+                            "{{ {}: {} }}",
+                            property_name(field),
+                            self.typescript(&field.typ, indent)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            }
+            IDLType::FuncT(_) => "{ principal: string; code: string }".to_string(),
+            IDLType::ServT(_) | IDLType::PrincipalT => "string".to_string(),
+            // A class is not the type of a value.
+            IDLType::ClassT(_, _) => "unknown".to_string(),
+        }
+    }
+
+    /// The TypeScript type for a record.
+    ///
+    /// Fields that Candid subtyping allows to be absent are optional, unless they are filled in.
+    fn record_typescript(&mut self, fields: &[TypeField], indent: usize) -> String {
+        if fields.is_empty() {
+            return "{}".to_string();
+        }
+        let field_indent = "  ".repeat(indent + 1);
+        let mut typescript = "{\n".to_string();
+        for field in fields {
+            let optional = !self.options.fill_missing_fields
+                && default_value(&field.typ, self.options).is_some();
+            typescript.push_str(&format!(
+                "{field_indent}{}{}: {};\n",
+                property_name(field),
+                if optional { "?" } else { "" },
+                self.typescript(&field.typ, indent + 1)
+            ));
+        }
+        typescript.push_str(&"  ".repeat(indent));
+        typescript.push('}');
+        typescript
+    }
+
+    /// The TypeScript type for `blob` and `vec nat8`.
+    ///
+    /// Bytes recognized as text or principals are strings.
+    fn bytes_typescript(&self) -> String {
+        let mut typescripts = vec![bytes_format_typescript(
+            &self.options.bytes_as.unwrap_or_default(),
+        )];
+        if let Some((_, long_bytes_format)) = self.options.long_bytes_as {
+            typescripts.push(bytes_format_typescript(&long_bytes_format));
+        }
+        if may_recognize_bytes(self.options) {
+            typescripts.push("string");
+        }
+        let mut unique_typescripts: Vec<&str> = Vec::new();
+        for typescript in typescripts {
+            if !unique_typescripts.contains(&typescript) {
+                unique_typescripts.push(typescript);
+            }
+        }
+        unique_typescripts.join(" | ")
+    }
+}
+
+/// The property name for a field, quoted if it is not a valid identifier.
+fn property_name(field: &TypeField) -> String {
+    let name = field.label.to_string();
+    let is_identifier = name
+        .chars()
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_' || first == '$')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if is_identifier {
+        name
+    } else {
+        serde_json::Value::String(name).to_string()
+    }
+}
+
+/// The TypeScript type for bytes in the given format.
+fn bytes_format_typescript(bytes_format: &BytesFormat) -> &'static str {
+    match bytes_format {
+        BytesFormat::Numbers => "Array<number>",
+        BytesFormat::Hex => "string",
+        #[cfg(feature = "crypto")]
+        BytesFormat::Sha256 => "string",
+    }
+}
+
+/// The TypeScript type for a primitive type.
+fn prim_typescript(prim_type: &PrimType, options: &Idl2JsonOptions) -> &'static str {
+    match prim_type {
+        // Big and 64 bit integers are given as decimal strings.
+        PrimType::Nat | PrimType::Int | PrimType::Nat64 | PrimType::Int64 => "string",
+        PrimType::Nat8
+        | PrimType::Nat16
+        | PrimType::Nat32
+        | PrimType::Int8
+        | PrimType::Int16
+        | PrimType::Int32 => "number",
+        PrimType::Float32 | PrimType::Float64 if options.non_finite_floats_as_null => {
+            "number | null"
+        }
+        PrimType::Float32 | PrimType::Float64 => r#"number | "NaN" | "Infinity" | "-Infinity""#,
+        PrimType::Bool => "boolean",
+        PrimType::Text => text_typescript(options),
+        PrimType::Null => "null",
+        PrimType::Reserved => r#""null : reserved""#,
+        PrimType::Empty => "never",
+    }
+}
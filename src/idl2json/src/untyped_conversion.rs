@@ -1,5 +1,11 @@
 use crate::{
     bytes::{convert_blob, convert_bytes},
+    generic_value::{generic_value2json, is_generic_value},
+    key_order::{func_object, json_object},
+    lossless::idl2lossless_json,
+    path::ValuePath,
+    redact::{is_redacted, redact},
+    truncate::{convert_elements, convert_text},
     Idl2JsonOptions,
 };
 use candid::types::value::IDLValue;
@@ -12,65 +18,124 @@ use serde_json::value::Value as JsonValue;
 /// zero or more IDLValues.  Unless you definitely wish to convert a single value
 /// you may wish to consider `idl_args2json` instead.
 pub fn idl2json(idl: &IDLValue, options: &Idl2JsonOptions) -> JsonValue {
+    idl2json_at(idl, &ValuePath::ROOT, options)
+}
+
+/// Converts a candid IDLValue at the given path to a serde JsonValue, without type information.
+pub(crate) fn idl2json_at(
+    idl: &IDLValue,
+    path: &ValuePath,
+    options: &Idl2JsonOptions,
+) -> JsonValue {
+    if is_redacted(None, path, options) {
+        return redact(idl, options);
+    }
+    if options.lossless {
+        return idl2lossless_json(idl, None, path, options);
+    }
+    if is_generic_value(idl, options) {
+        if let Some(json) = generic_value2json(idl, path, options) {
+            return json;
+        }
+    }
     match idl {
-        IDLValue::Blob(bytes) => convert_blob(bytes, options),
+        IDLValue::Blob(bytes) => convert_blob(bytes, path, options),
         IDLValue::Bool(bool) => JsonValue::Bool(*bool),
         IDLValue::Null => JsonValue::Null,
-        IDLValue::Text(s) => JsonValue::String(s.clone()),
-        IDLValue::Number(s) => JsonValue::String(s.clone()), // Unspecified number type
-        IDLValue::Float64(f) => serde_json::Number::from_f64(*f)
-            .map(JsonValue::Number)
-            .unwrap_or_else(|| JsonValue::String("NaN".to_string())),
-        IDLValue::Opt(value) => JsonValue::Array(vec![idl2json(value, options)]),
-        IDLValue::Vec(value) => convert_bytes(value, options)
-            .unwrap_or_else(|_| convert_non_bytes_array(value, options)),
-        IDLValue::Record(value) => JsonValue::Object(
+        IDLValue::Text(s) => convert_text(s, options),
+        IDLValue::Number(s) => convert_number(s.clone(), options), // Unspecified number type
+        IDLValue::Float64(f) => convert_float(*f, options),
+        IDLValue::Opt(value) => JsonValue::Array(vec![idl2json_at(value, path, options)]),
+        IDLValue::Vec(value) => convert_bytes(value, path, options)
+            .unwrap_or_else(|_| convert_non_bytes_array(value, path, options)),
+        IDLValue::Record(value) => json_object(
             value
                 .iter()
-                .map(|field| (format!("{}", field.id), idl2json(&field.val, options)))
-                .collect(),
-        ),
-        IDLValue::Variant(field) => JsonValue::Object(
-            vec![(format!("{}", field.0.id), idl2json(&field.0.val, options))]
-                .into_iter()
+                .map(|field| {
+                    let key = format!("{}", field.id);
+                    let value = idl2json_at(&field.val, &path.key(&key), options);
+                    (field.id.get_id(), key, value)
+                })
                 .collect(),
+            None,
+            options,
         ),
+        IDLValue::Variant(field) => {
+            let key = format!("{}", field.0.id);
+            let value = idl2json_at(&field.0.val, &path.key(&key), options);
+            JsonValue::Object(vec![(key, value)].into_iter().collect())
+        }
         IDLValue::Principal(p) => JsonValue::String(format!("{}", p)),
         IDLValue::Service(p) => JsonValue::String(format!("{}", p)),
-        IDLValue::Func(p, c) => JsonValue::Object(
-            vec![
-                ("principal".to_string(), JsonValue::String(format!("{}", p))),
-                ("code".to_string(), JsonValue::String(c.to_string())),
-            ]
-            .into_iter()
-            .collect(),
-        ),
+        IDLValue::Func(p, c) => func_object(format!("{}", p), c.to_string(), options),
         IDLValue::None => JsonValue::Array(vec![]),
-        IDLValue::Int(i) => JsonValue::String(format!("{}", i)),
-        IDLValue::Nat(i) => JsonValue::String(format!("{}", i)),
+        IDLValue::Int(i) => convert_number(i.to_string(), options),
+        IDLValue::Nat(i) => convert_number(i.to_string(), options),
         IDLValue::Nat8(i) => JsonValue::Number(serde_json::Number::from(*i)),
         IDLValue::Nat16(i) => JsonValue::Number(serde_json::Number::from(*i)),
         IDLValue::Nat32(i) => JsonValue::Number(serde_json::Number::from(*i)),
-        IDLValue::Nat64(i) => JsonValue::String(format!("{}", i)),
+        IDLValue::Nat64(i) => convert_number(i.to_string(), options),
         IDLValue::Int8(i) => JsonValue::Number(serde_json::Number::from(*i)),
         IDLValue::Int16(i) => JsonValue::Number(serde_json::Number::from(*i)),
         IDLValue::Int32(i) => JsonValue::Number(serde_json::Number::from(*i)),
-        IDLValue::Int64(i) => JsonValue::String(format!("{}", i)),
-        IDLValue::Float32(f) => serde_json::Number::from_f64(*f as f64)
-            .map(JsonValue::Number)
-            .unwrap_or_else(|| JsonValue::String("NaN".to_string())),
+        IDLValue::Int64(i) => convert_number(i.to_string(), options),
+        IDLValue::Float32(f) => convert_float(f64::from(*f), options),
         IDLValue::Reserved => JsonValue::String(idl.to_string()),
     }
 }
 
-/// Conver
-pub(crate) fn convert_non_bytes_array(value: &[IDLValue], options: &Idl2JsonOptions) -> JsonValue {
-    JsonValue::Array(value.iter().map(|item| idl2json(item, options)).collect())
+/// Converts an integer that may be too large for JSON parsers, so is given as a decimal string.
+///
+/// In YAML, integers that fit in 64 bits are numbers, as yaml2candid expects integer types other than `nat` and
+/// `int` to be.
+pub(crate) fn convert_number(number: String, options: &Idl2JsonOptions) -> JsonValue {
+    if options.output_format.is_yaml() {
+        if let Ok(number) = number.parse::<u64>() {
+            return JsonValue::Number(number.into());
+        }
+        if let Ok(number) = number.parse::<i64>() {
+            return JsonValue::Number(number.into());
+        }
+    }
+    JsonValue::String(number)
+}
+
+/// Converts a float.
+///
+/// JSON has no numbers that are not finite, so they are given as `"NaN"`, `"Infinity"` and
+/// `"-Infinity"` or, if `options.non_finite_floats_as_null` is set, as `null`.
+pub(crate) fn convert_float(float: f64, options: &Idl2JsonOptions) -> JsonValue {
+    serde_json::Number::from_f64(float)
+        .map(JsonValue::Number)
+        .unwrap_or_else(|| {
+            if options.non_finite_floats_as_null {
+                JsonValue::Null
+            } else if float.is_nan() {
+                JsonValue::String("NaN".to_string())
+            } else if float > 0.0 {
+                JsonValue::String("Infinity".to_string())
+            } else {
+                JsonValue::String("-Infinity".to_string())
+            }
+        })
+}
+
+/// Converts the elements of a vector at the given path, without type information.
+pub(crate) fn convert_non_bytes_array(
+    value: &[IDLValue],
+    path: &ValuePath,
+    options: &Idl2JsonOptions,
+) -> JsonValue {
+    convert_elements(value, options, |index, item| {
+        idl2json_at(item, &path.index(index), options)
+    })
 }
 
 /// Converts a candid IDLArgs to a serde JsonValue, without type information.
 ///
 /// Note: The textual format `( )` containing zero or more values represents an IDLArgs.
+///
+/// Each argument is a top level value, with its own paths.
 pub fn idl_args2json(args: &IDLArgs, options: &Idl2JsonOptions) -> JsonValue {
-    convert_non_bytes_array(&args.args, options)
+    JsonValue::Array(args.args.iter().map(|arg| idl2json(arg, options)).collect())
 }
@@ -0,0 +1,260 @@
+//! Converting many files in one process, with the .did files loaded once rather than for every file.
+use crate::{
+    check::check_args, convert_args, exit_code, idl2json_options, read_idl_args, Args,
+    Nonconforming, EXIT_FAILURE,
+};
+use anyhow::{anyhow, Context};
+use idl2json::{Idl2JsonOptions, OutputFormat};
+use std::{
+    collections::HashMap,
+    fmt,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+/// Options for converting many files.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Batch {
+    /// The candid files, text or binary, or directories of them, to convert
+    #[clap(required = true)]
+    inputs: Vec<PathBuf>,
+    /// Write the output to this directory, in a directory named after each input directory (default: alongside each
+    /// input)
+    #[clap(long)]
+    out_dir: Option<PathBuf>,
+    /// The extensions of the candid files to convert in directories
+    #[clap(long, default_values = ["idl", "candid", "bin"])]
+    extension: Vec<String>,
+    /// How many files to convert at once (default: 1)
+    #[clap(short, long)]
+    jobs: Option<NonZeroUsize>,
+}
+
+/// Files that could not be converted, by `batch()`.
+///
+/// This is displayed as the number of files, followed by each file and its error.
+#[derive(Debug)]
+pub struct BatchFailed {
+    /// The files that could not be converted, with their errors, in the order of the inputs.
+    failures: Vec<(PathBuf, anyhow::Error)>,
+    /// The number of files that were to be converted.
+    total: usize,
+}
+
+impl BatchFailed {
+    /// The exit code: that of the errors, if they all have the same one, otherwise `EXIT_FAILURE`.
+    pub(crate) fn exit_code(&self) -> i32 {
+        let mut exit_codes = self.failures.iter().map(|(_, err)| exit_code(err));
+        let first = exit_codes.next().unwrap_or(EXIT_FAILURE);
+        if exit_codes.all(|exit_code| exit_code == first) {
+            first
+        } else {
+            EXIT_FAILURE
+        }
+    }
+}
+
+impl fmt::Display for BatchFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} files could not be converted:",
+            self.failures.len(),
+            self.total
+        )?;
+        for (path, err) in &self.failures {
+            write!(f, "\n{}: {err:#}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BatchFailed {}
+
+impl Args {
+    /// Whether many files are converted, with `batch()`, rather than stdin.
+    pub fn batches(&self) -> bool {
+        matches!(self.command, Some(crate::Command::Batch(_)))
+    }
+}
+
+/// Converts the files given by the `batch` command, as stdin would be converted, writing each output to a file.
+///
+/// - Each file may be text or binary candid.  Directories are searched recursively for files with the given
+///   extensions.
+/// - The output of each file has the extension of the output format.  It is written alongside the input or,
+///   with an output directory, at its path in the directory that was searched, under the name of that directory.
+/// - No files are converted if two would have the same output, such as `x.idl` and `x.candid`.
+/// - With `--check`, the report for each file is its output, and files that do not conform are failures.
+/// - Files that cannot be converted do not stop the others from being converted.  They are listed in
+///   `BatchFailed`.
+///
+/// Returns the number of files converted.
+pub fn batch(args: &Args) -> anyhow::Result<usize> {
+    let Some(crate::Command::Batch(batch)) = &args.command else {
+        return Err(anyhow!("Please specify the files to convert with batch."));
+    };
+    if args.schema || args.typescript || !args.diff.is_empty() || args.stream {
+        return Err(anyhow!(
+            "--schema, --typescript, --diff and --stream may not be used with batch."
+        ));
+    }
+    let mut files = Vec::new();
+    for input in &batch.inputs {
+        find_files(input, input, &batch.extension, &mut files)
+            .with_context(|| anyhow!("Could not read '{}'.", input.display()))?;
+    }
+    let files = output_paths(files, batch.out_dir.as_deref(), file_extension(args))?;
+    let idl2json_options = idl2json_options(args)?;
+    let next_file = AtomicUsize::new(0);
+    let failures = Mutex::new(Vec::new());
+    let jobs = batch.jobs.map_or(1, NonZeroUsize::get).min(files.len());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| {
+                while let Some((input, output_path)) =
+                    files.get(next_file.fetch_add(1, Ordering::Relaxed))
+                {
+                    if let Err(err) = convert_file(args, input, output_path, &idl2json_options) {
+                        if let Ok(mut failures) = failures.lock() {
+                            failures.push((input.clone(), err));
+                        }
+                    }
+                }
+            });
+        }
+    });
+    let mut failures = failures
+        .into_inner()
+        .map_err(|_| anyhow!("A conversion failed unexpectedly."))?;
+    if failures.is_empty() {
+        return Ok(files.len());
+    }
+    failures.sort_by_key(|(input, _)| files.iter().position(|(file, _)| file == input));
+    Err(BatchFailed {
+        failures,
+        total: files.len(),
+    }
+    .into())
+}
+
+/// Adds the file, or the files in the directory and its subdirectories that have one of the extensions, to the
+/// list, with their paths relative to the parent of the root directory, or the file name of a file.
+///
+/// Directories are searched in alphabetical order, so that files are listed in the same order every time.
+fn find_files(
+    root: &Path,
+    path: &Path,
+    extensions: &[String],
+    files: &mut Vec<(PathBuf, PathBuf)>,
+) -> std::io::Result<()> {
+    if !path.is_dir() {
+        // The name of the root keeps the files from different roots apart.
+        let root_name = root.file_name().map(PathBuf::from).unwrap_or_default();
+        let relative_path = match path.strip_prefix(root) {
+            Ok(relative_path) if !relative_path.as_os_str().is_empty() => {
+                root_name.join(relative_path)
+            }
+            _ => root_name,
+        };
+        files.push((path.to_path_buf(), relative_path));
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        let has_extension = entry
+            .extension()
+            .is_some_and(|extension| extensions.iter().any(|wanted| extension == wanted.as_str()));
+        if entry.is_dir() || has_extension {
+            find_files(root, &entry, extensions, files)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the relative path of each file with the path of its output, in the output directory or alongside the
+/// file.
+///
+/// Returns an error if an output would replace an input, or two files would have the same output.
+fn output_paths(
+    files: Vec<(PathBuf, PathBuf)>,
+    out_dir: Option<&Path>,
+    extension: &str,
+) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut inputs_by_output: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let mut outputs = Vec::with_capacity(files.len());
+    for (input, relative_path) in files {
+        let output_path = match out_dir {
+            Some(out_dir) => out_dir.join(relative_path),
+            None => input.clone(),
+        }
+        .with_extension(extension);
+        if output_path == input {
+            return Err(anyhow!(
+                "The output of '{}' would replace it.  Please specify --out-dir.",
+                input.display()
+            ));
+        }
+        if let Some(other_input) = inputs_by_output.insert(output_path.clone(), input.clone()) {
+            return Err(anyhow!(
+                "'{}' and '{}' would both be converted to '{}'.",
+                other_input.display(),
+                input.display(),
+                output_path.display()
+            ));
+        }
+        outputs.push((input, output_path));
+    }
+    Ok(outputs)
+}
+
+/// Converts one file, text or binary candid, writing the output to the given path.
+fn convert_file(
+    args: &Args,
+    input: &Path,
+    output_path: &Path,
+    idl2json_options: &Idl2JsonOptions,
+) -> anyhow::Result<()> {
+    let idl_args = read_idl_args(input)?;
+    let (mut output, err) = if args.check {
+        match check_args(args, &idl_args, idl2json_options) {
+            Ok(report) => (report, None),
+            Err(err) => match err.downcast_ref::<Nonconforming>() {
+                Some(nonconforming) => (nonconforming.report().to_vec(), Some(err)),
+                None => return Err(err),
+            },
+        }
+    } else {
+        (convert_args(args, idl_args, idl2json_options)?, None)
+    };
+    if !idl2json_options.output_format.is_binary() {
+        output.push(b'\n');
+    }
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| anyhow!("Could not create directory '{}'.", parent.display()))?;
+    }
+    std::fs::write(output_path, output)
+        .with_context(|| anyhow!("Could not write '{}'.", output_path.display()))?;
+    err.map_or(Ok(()), Err)
+}
+
+/// The extension of the output files, as given by the output format.
+fn file_extension(args: &Args) -> &'static str {
+    match args.format.unwrap_or_default() {
+        OutputFormat::Json => "json",
+        OutputFormat::Yaml => "yaml",
+        OutputFormat::Cbor => "cbor",
+        OutputFormat::Msgpack => "msgpack",
+        OutputFormat::Toml => "toml",
+        OutputFormat::Csv => "csv",
+        OutputFormat::Tsv => "tsv",
+    }
+}
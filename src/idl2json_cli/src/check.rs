@@ -0,0 +1,114 @@
+//! Checking that candid conforms to a type, rather than converting it.
+use crate::{input_type, method_arg_types, parse_error::parse, print_all, Args, InputType};
+use anyhow::{anyhow, Context};
+use candid::IDLArgs;
+use candid_parser::{parse_idl_args, types::IDLTypes};
+use idl2json::{check2json, idl_args_check, Idl2JsonOptions};
+use std::fmt;
+
+/// The magic bytes at the start of binary candid, `DIDL`, as hex.
+const MAGIC_HEX: &str = "4449444c";
+
+/// Candid that does not conform to its type.
+///
+/// This is displayed as the number of values that do not conform.  The report lists them.
+#[derive(Debug)]
+pub struct Nonconforming {
+    /// The values that do not conform, in the requested format.
+    report: Vec<u8>,
+    /// The number of values that do not conform.
+    count: usize,
+}
+
+impl Nonconforming {
+    /// The values that do not conform, with their paths and the reasons, in the requested format.
+    pub fn report(&self) -> &[u8] {
+        &self.report
+    }
+}
+
+impl fmt::Display for Nonconforming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} values do not conform to the type", self.count)
+    }
+}
+
+impl std::error::Error for Nonconforming {}
+
+/// Checks that the candid conforms to the type given by the arguments.
+///
+/// - The candid may be text or hex-encoded binary.
+/// - With `--method`, the candid is checked against the arguments of the method, as it is to be sent to it.
+///
+/// Returns the empty report if the candid conforms, otherwise `Nonconforming`.
+pub(crate) fn check(
+    args: &Args,
+    idl_str: &str,
+    idl2json_options: &Idl2JsonOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let idl_args = parse_text_or_hex("stdin", idl_str).context("Malformed input")?;
+    check_args(args, &idl_args, idl2json_options)
+}
+
+/// Checks that parsed candid conforms to the type given by the arguments, as `check()`.
+pub(crate) fn check_args(
+    args: &Args,
+    idl_args: &IDLArgs,
+    idl2json_options: &Idl2JsonOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let idl_types = if let Some(method) = &args.method {
+        method_arg_types(method, idl2json_options)?
+    } else {
+        match input_type(args, idl2json_options)? {
+            InputType::Types(idl_types) => idl_types,
+            InputType::Type(idl_type) => IDLTypes {
+                args: vec![idl_type; idl_args.args.len()],
+            },
+            InputType::Untyped => {
+                return Err(anyhow!(
+                    "Please specify a type with --typ, --init or --method."
+                ))
+            }
+        }
+    };
+    let nonconformances = idl_args_check(idl_args, &idl_types, idl2json_options);
+    let report = print_all(
+        [check2json(&nonconformances)],
+        args.framing,
+        idl2json_options,
+    )?;
+    if nonconformances.is_empty() {
+        Ok(report)
+    } else {
+        Err(Nonconforming {
+            report,
+            count: nonconformances.len(),
+        }
+        .into())
+    }
+}
+
+/// Parses candid text or, if the input is hex starting with the magic bytes `DIDL`, binary candid.
+fn parse_text_or_hex(source_name: &str, input: &str) -> anyhow::Result<IDLArgs> {
+    parse_hex(input).unwrap_or_else(|| Ok(parse(source_name, input, parse_idl_args)?))
+}
+
+/// Parses hex-encoded binary candid, if the input is hex starting with the magic bytes `DIDL`.
+pub(crate) fn parse_hex(input: &str) -> Option<anyhow::Result<IDLArgs>> {
+    let hex = input.trim();
+    let is_hex = hex.len() % 2 == 0
+        && hex.get(..MAGIC_HEX.len()).map(str::to_ascii_lowercase) == Some(MAGIC_HEX.to_string())
+        && hex.bytes().all(|byte| byte.is_ascii_hexdigit());
+    if !is_hex {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16))
+        .collect::<Result<Vec<u8>, _>>();
+    Some(
+        bytes
+            .context("Malformed hex")
+            .and_then(|bytes| IDLArgs::from_bytes(&bytes).context("Malformed binary candid")),
+    )
+}
@@ -0,0 +1,259 @@
+//! Defaults for the command line options, from a `.idl2json.toml` configuration file.
+//!
+//! The file sets options by their long names, in snake or kebab case, with named profiles and per-type
+//! settings:
+//!
+//! ```toml
+//! did = ["governance.did"]
+//! bytes_as = "hex"
+//! compact = true
+//!
+//! [types]
+//! SessionKey = "redact"
+//! Value = "generic"
+//!
+//! [profiles.ledger]
+//! did = ["ledger.did"]
+//! method = "query_blocks"
+//! ```
+//!
+//! - Options given on the command line take precedence, and lists given on the command line replace
+//!   those in the file.
+//! - The options of a profile selected with `--profile` take precedence over those at the top level.
+//! - `.did` paths are relative to the directory of the configuration file.
+//! - Per-type settings may only redact a type, as with `--redact-type`, or render it as a generic value, as with
+//!   `--generic-value-type`.  Other options, such as `bytes_as`, apply to all types.
+use crate::Args;
+use clap::{error::ErrorKind, parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches};
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+use toml::{Table, Value};
+
+/// The name of the configuration file, which is looked for in the current directory and its ancestors.
+pub const CONFIG_FILE_NAME: &str = ".idl2json.toml";
+
+/// The options that give the type of the input.  If any is on the command line, the others are ignored in the file.
+const INPUT_TYPE_OPTIONS: [&str; 3] = ["typ", "init", "method"];
+
+/// The options that are only given on the command line.
+const COMMAND_LINE_OPTIONS: [&str; 2] = ["config", "profile"];
+
+/// Parses the command line, with defaults from the configuration file.
+///
+/// The file is given by `--config`, or else is the first `.idl2json.toml` in the current directory or above it.
+pub fn parse_args(argv: impl IntoIterator<Item = OsString>) -> Result<Args, clap::Error> {
+    let current_dir = std::env::current_dir().map_err(|err| {
+        Args::command().error(
+            ErrorKind::Io,
+            format!("Could not read the current directory: {err}"),
+        )
+    })?;
+    parse_args_in(argv.into_iter().collect(), &current_dir)
+}
+
+/// Parses the command line, with defaults from the configuration file in the given directory or above it.
+pub(crate) fn parse_args_in(argv: Vec<OsString>, current_dir: &Path) -> Result<Args, clap::Error> {
+    let mut command = Args::command();
+    // Errors, and requests for help, are reported when the complete command line is parsed below.
+    let Ok(matches) = command
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(&argv)
+    else {
+        return Args::from_arg_matches(&command.try_get_matches_from(argv)?);
+    };
+    let config_path = match matches.get_one::<PathBuf>("config") {
+        Some(config_path) => Some(current_dir.join(config_path)),
+        None => current_dir
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .find(|config_path| config_path.is_file()),
+    };
+    let profile = matches.get_one::<String>("profile");
+    let config_args = match &config_path {
+        Some(config_path) => config_args(config_path, profile.map(String::as_str), &matches)
+            .map_err(|message| command.error(ErrorKind::InvalidValue, message))?,
+        None if profile.is_some() => {
+            return Err(command.error(
+                ErrorKind::InvalidValue,
+                format!("A profile was given but there is no {CONFIG_FILE_NAME} file."),
+            ))
+        }
+        None => vec![],
+    };
+    let mut argv = argv.into_iter();
+    let argv: Vec<OsString> = argv
+        .next()
+        .into_iter()
+        .chain(config_args)
+        .chain(argv)
+        .collect();
+    let mut args = Args::from_arg_matches(&command.try_get_matches_from(&argv)?)?;
+    args.argv = argv;
+    Ok(args)
+}
+
+/// The options given on the command line that are not overridden, as arguments such as `--bytes-as=hex`.
+///
+/// Options that give the type of the input are all overridden by any one of them.
+pub(crate) fn unoverridden_args(matches: &ArgMatches, overrides: &ArgMatches) -> Vec<OsString> {
+    let mut args = Vec::new();
+    for arg in Args::command().get_arguments() {
+        let (Some(long), id) = (arg.get_long(), arg.get_id().as_str()) else {
+            continue;
+        };
+        if matches.value_source(id) != Some(ValueSource::CommandLine)
+            || is_overridden(id, overrides)
+        {
+            continue;
+        }
+        let flag = format!("--{long}");
+        let values = matches.get_raw(id).into_iter().flatten();
+        if !arg.get_action().takes_values() {
+            args.push(OsString::from(flag));
+        } else if arg
+            .get_num_args()
+            .is_some_and(|num_args| num_args.max_values() > 1)
+        {
+            args.push(OsString::from(flag));
+            args.extend(values.map(OsString::from));
+        } else {
+            args.extend(values.map(|value| {
+                let mut arg = OsString::from(format!("{flag}="));
+                arg.push(value);
+                arg
+            }));
+        }
+    }
+    args
+}
+
+/// Whether an option is given on the command line, or is one of the options that give the type of the input
+/// and another of those is.
+fn is_overridden(id: &str, overrides: &ArgMatches) -> bool {
+    let is_given = |id: &str| overrides.value_source(id) == Some(ValueSource::CommandLine);
+    is_given(id)
+        || (INPUT_TYPE_OPTIONS.contains(&id) && INPUT_TYPE_OPTIONS.into_iter().any(is_given))
+}
+
+/// The command line arguments for the options set in a configuration file and not on the command line.
+fn config_args(
+    config_path: &Path,
+    profile: Option<&str>,
+    matches: &ArgMatches,
+) -> Result<Vec<OsString>, String> {
+    let config = std::fs::read_to_string(config_path)
+        .map_err(|err| format!("Could not read '{}': {err}", config_path.display()))?;
+    let config: Table = config
+        .parse()
+        .map_err(|err| format!("Could not parse '{}': {err}", config_path.display()))?;
+    let mut options = config.clone();
+    options.remove("profiles");
+    if let Some(profile) = profile {
+        let profile_options = config
+            .get("profiles")
+            .and_then(|profiles| profiles.get(profile))
+            .and_then(Value::as_table)
+            .ok_or_else(|| {
+                format!(
+                    "There is no profile '{profile}' in '{}'.",
+                    config_path.display()
+                )
+            })?;
+        for (key, value) in profile_options {
+            match (options.get_mut(key), value) {
+                // Per-type settings are merged, the others replaced.
+                (Some(Value::Table(types)), Value::Table(profile_types)) if key == "types" => {
+                    types.extend(profile_types.clone());
+                }
+                _ => {
+                    options.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+    let config_dir = config_path.parent().unwrap_or(Path::new("."));
+    let mut args = Vec::new();
+    for (key, value) in options {
+        if key == "types" {
+            args.extend(type_args(&value, matches, config_path)?);
+            continue;
+        }
+        let id = key.replace('-', "_");
+        let is_known = Args::command()
+            .get_arguments()
+            .any(|arg| arg.get_id() == id.as_str() && arg.get_long().is_some());
+        if !is_known || COMMAND_LINE_OPTIONS.contains(&id.as_str()) {
+            return Err(format!(
+                "Unknown option '{key}' in '{}'.",
+                config_path.display()
+            ));
+        }
+        if is_overridden(&id, matches) {
+            continue;
+        }
+        let flag = format!("--{}", id.replace('_', "-"));
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                Value::Boolean(true) => args.push(OsString::from(&flag)),
+                Value::Boolean(false) => {}
+                Value::String(string) if id == "did" => {
+                    let mut arg = OsString::from(format!("{flag}="));
+                    arg.push(config_dir.join(string));
+                    args.push(arg);
+                }
+                Value::String(string) => args.push(OsString::from(format!("{flag}={string}"))),
+                Value::Integer(_) | Value::Float(_) => {
+                    args.push(OsString::from(format!("{flag}={value}")))
+                }
+                value => {
+                    return Err(format!(
+                        "'{key}' in '{}' should not be a {}.",
+                        config_path.display(),
+                        value.type_str()
+                    ))
+                }
+            }
+        }
+    }
+    Ok(args)
+}
+
+/// The command line arguments for per-type settings, such as `SessionKey = "redact"`.
+///
+/// Types are redacted or rendered as generic values, as with `--redact-type` and `--generic-value-type`.
+fn type_args(
+    types: &Value,
+    matches: &ArgMatches,
+    config_path: &Path,
+) -> Result<Vec<OsString>, String> {
+    let types = types
+        .as_table()
+        .ok_or_else(|| format!("'types' in '{}' should be a table.", config_path.display()))?;
+    let mut args = Vec::new();
+    for (type_name, setting) in types {
+        let id = match setting.as_str() {
+            Some("redact") => "redact_type",
+            Some("generic") => "generic_value_type",
+            _ => {
+                return Err(format!(
+                    "Type '{type_name}' in '{}' should be \"redact\" or \"generic\".",
+                    config_path.display()
+                ))
+            }
+        };
+        if !is_overridden(id, matches) {
+            args.push(OsString::from(format!(
+                "--{}={type_name}",
+                id.replace('_', "-")
+            )));
+        }
+    }
+    Ok(args)
+}
@@ -0,0 +1,86 @@
+//! Commands that describe the types and methods in .did files, rather than converting candid.
+use anyhow::{anyhow, Context};
+use candid_parser::{types::IDLType, IDLProg};
+use idl2json::polyfill::{idl_prog, idl_type};
+use std::path::PathBuf;
+
+/// Commands that describe the .did files given with --did.
+#[derive(clap::Subcommand, Clone, Debug)]
+pub enum Describe {
+    /// List the types declared in the .did files, one per line
+    ///
+    /// With several .did files, each type is followed by a tab and the file it is declared in.
+    Types,
+    /// List the methods of the services in the .did files, with their types, one per line
+    ///
+    /// With several .did files, each method is followed by a tab and the file it is declared in.
+    Methods,
+    /// Print a type declared in the .did files, with the names of other types replaced by their definitions
+    ShowType {
+        /// The name of the type
+        name: String,
+        /// Replace nested names to at most this depth; 0 prints the declaration as it is (default: all names,
+        /// except where a recursive type recurs)
+        #[clap(long)]
+        depth: Option<usize>,
+    },
+}
+
+/// Describes the .did files, as given by the command.
+///
+/// The progs are those parsed from the .did files at the given paths, in the same order.
+pub(crate) fn introspect(
+    describe: &Describe,
+    paths: &[PathBuf],
+    progs: &[IDLProg],
+) -> anyhow::Result<String> {
+    if progs.is_empty() {
+        return Err(anyhow!(
+            "Please specify which .did files to use with --did."
+        ));
+    }
+    // The file each line comes from, if there is more than one file.
+    let source = |path: &PathBuf| {
+        if progs.len() > 1 {
+            format!("\t{}", path.display())
+        } else {
+            String::new()
+        }
+    };
+    let mut lines = Vec::new();
+    match describe {
+        Describe::Types => {
+            for (path, prog) in paths.iter().zip(progs) {
+                for binding in idl_prog::get_types(prog) {
+                    lines.push(format!("{}{}", binding.id, source(path)));
+                }
+            }
+        }
+        Describe::Methods => {
+            for (path, prog) in paths.iter().zip(progs) {
+                for (name, func_type) in idl_prog::get_methods(prog) {
+                    let func_type = idl_type::func_to_string(&func_type);
+                    lines.push(format!("{name} : {func_type}{}", source(path)));
+                }
+            }
+        }
+        Describe::ShowType { name, depth } => {
+            let (path, prog) = paths
+                .iter()
+                .zip(progs)
+                .find(|(_, prog)| idl_prog::get_type(prog, name).is_some())
+                .with_context(|| anyhow!("Could not find a type called '{name}'."))?;
+            // The name itself is replaced by its declaration, before the names in the declaration.
+            let depth = depth.map(|depth| depth.saturating_add(1));
+            let idl_type = idl_prog::expand_type(prog, &IDLType::VarT(name.clone()), depth);
+            if progs.len() > 1 {
+                lines.push(format!("// {}", path.display()));
+            }
+            lines.push(format!(
+                "type {name} = {};",
+                idl_type::to_pretty_string(&idl_type)
+            ));
+        }
+    }
+    Ok(lines.join("\n"))
+}
@@ -5,47 +5,341 @@
 #![deny(clippy::expect_used)]
 #![deny(clippy::unimplemented)]
 
+mod batch;
+mod check;
+mod config;
+mod introspect;
+mod parse_error;
+mod serve;
 #[cfg(test)]
 mod tests;
+mod tuples;
+mod unwrap_result;
 
 use anyhow::{anyhow, Context};
+pub use batch::{batch, Batch, BatchFailed};
 use candid::types::value::IDLValue;
 use candid_parser::{
     parse_idl_args,
     types::{IDLProg, IDLType, IDLTypes},
     IDLArgs,
 };
+use check::check;
+pub use check::Nonconforming;
 use clap::Parser;
+pub use config::{parse_args, CONFIG_FILE_NAME};
 use idl2json::{
-    idl2json, idl2json_with_weak_names, idl_args2json_with_weak_names, polyfill, BytesFormat,
-    Idl2JsonOptions,
+    diff2json_patch, diff2text, idl2json, idl2json_select, idl2json_with_weak_names,
+    idl2json_with_weak_names_select, idl2summary, idl2summary_with_weak_names, idl2table,
+    idl2table_with_weak_names, idl_args_diff, idl_progs2typescript, idl_type2json_schema,
+    idl_type2typescript, idl_types2json_schema, idl_types2typescript, polyfill, serialize,
+    BytesFormat, DeclaredFieldOrder, Idl2JsonOptions, JsonValue, KeyOrder, OutputFormat,
+    PathPattern, RedactFormat,
 };
-use std::{path::PathBuf, str::FromStr};
-
-/// Reads IDL from stdin, writes JSON to stdout.
-pub fn main(args: &Args, idl_str: &str) -> anyhow::Result<String> {
-    let idl_args: IDLArgs = parse_idl_args(idl_str).with_context(|| anyhow!("Malformed input"))?;
-    let idl2json_options = {
-        let progs: anyhow::Result<Vec<IDLProg>> = args
-            .did
-            .iter()
-            .map(|did| {
-                let did_as_str = std::fs::read_to_string(did)
-                    .with_context(|| anyhow!("Could not read did file '{}'.", did.display()))?;
-                IDLProg::from_str(&did_as_str)
-                    .with_context(|| anyhow!("Failed to parse did file '{}'", did.display()))
-            })
-            .collect();
-        let progs = progs?;
-
-        Idl2JsonOptions {
-            prog: progs,
-            bytes_as: args.bytes_as,
-            compact: args.compact,
-            ..Idl2JsonOptions::default()
+use introspect::introspect;
+pub use introspect::Describe;
+pub use parse_error::ParseError;
+use parse_error::{parse, parse_from_line};
+pub use serve::{serve, Serve};
+use std::{
+    ffi::OsString,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use tuples::Tuples;
+use unwrap_result::unwrap_results;
+pub use unwrap_result::{ResultErr, DEFAULT_ERR_EXIT_CODE};
+
+/// Reads IDL from stdin, writes JSON, or the requested format, to stdout.
+pub fn main(args: &Args, idl_str: &str) -> anyhow::Result<Vec<u8>> {
+    main_with_options(args, idl_str, &idl2json_options(args)?)
+}
+
+/// As `main()`, with the .did files already loaded into the conversion options.
+fn main_with_options(
+    args: &Args,
+    idl_str: &str,
+    idl2json_options: &Idl2JsonOptions,
+) -> anyhow::Result<Vec<u8>> {
+    match &args.command {
+        Some(Command::Describe(describe)) => {
+            return introspect(describe, &args.did, &idl2json_options.prog).map(String::into_bytes)
+        }
+        Some(Command::Serve(_)) => return Err(anyhow!("The server is started with serve().")),
+        Some(Command::Batch(_)) => return Err(anyhow!("Files are converted with batch().")),
+        None => {}
+    }
+    if args.schema {
+        return schema(args, idl2json_options);
+    }
+    if args.typescript {
+        return typescript(args, idl2json_options).map(String::into_bytes);
+    }
+    if !args.diff.is_empty() {
+        return diff(args, idl2json_options);
+    }
+    if args.check {
+        return check(args, idl_str, idl2json_options);
+    }
+    convert(args, "stdin", idl_str, idl2json_options)
+}
+
+/// The exit code for malformed candid text or .did files: `EX_DATAERR` in `sysexits.h`.
+pub const EXIT_PARSE_ERROR: i32 = 65;
+/// The exit code for other errors.
+pub const EXIT_FAILURE: i32 = 1;
+/// The exit code for candid that does not conform to its type, with `--check`.
+pub const EXIT_NONCONFORMING: i32 = 3;
+
+/// The exit code for an error returned by `main()`, `stream()` or `batch()`.
+///
+/// An `Err` result, with `--unwrap-result`, has the exit code given by `--err-exit-code`.
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    if let Some(result_err) = err.downcast_ref::<ResultErr>() {
+        result_err.exit_code()
+    } else if let Some(batch_failed) = err.downcast_ref::<BatchFailed>() {
+        batch_failed.exit_code()
+    } else if err.is::<Nonconforming>() {
+        EXIT_NONCONFORMING
+    } else if err.chain().any(|cause| cause.is::<ParseError>()) {
+        EXIT_PARSE_ERROR
+    } else {
+        EXIT_FAILURE
+    }
+}
+
+/// Converts each candid tuple in the input, writing the output for each tuple as soon as it is converted.
+///
+/// - The tuples are split at balanced top level parentheses, so there may be several on a line, such as
+///   `(1 : nat8)(2 : nat8)`, or one over several lines, as `dfx canister call` prints them.
+/// - The .did files are read once, for all the tuples.
+/// - The output for each tuple is followed by a newline, unless it is binary.
+pub fn stream(args: &Args, mut input: impl BufRead, mut output: impl Write) -> anyhow::Result<()> {
+    let idl2json_options = idl2json_options(args)?;
+    let mut tuples = Tuples::default();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let ended = input.read_line(&mut line).context("Failed to read input")? == 0;
+        let tuples = if ended {
+            std::mem::take(&mut tuples).finish().into_iter().collect()
+        } else {
+            tuples.push(&line)
+        };
+        for tuple in tuples {
+            let mut converted =
+                parse_from_line("stdin", tuple.first_line, &tuple.text, parse_idl_args)
+                    .context("Malformed input")
+                    .and_then(|idl_args| convert_args(args, idl_args, &idl2json_options))
+                    .with_context(|| anyhow!("Failed to convert line {}", tuple.line))?;
+            if !args.writes_binary() {
+                converted.push(b'\n');
+            }
+            output
+                .write_all(&converted)
+                .and_then(|()| output.flush())
+                .context("Failed to write output")?;
+        }
+        if ended {
+            return Ok(());
+        }
+    }
+}
+
+/// Converts a candid tuple, or summarizes it or selects values in it, as given by the arguments.
+///
+/// The source name is used in the report of any error in the candid text.
+fn convert(
+    args: &Args,
+    source_name: &str,
+    idl_str: &str,
+    idl2json_options: &Idl2JsonOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let idl_args: IDLArgs =
+        parse(source_name, idl_str, parse_idl_args).with_context(|| anyhow!("Malformed input"))?;
+    convert_args(args, idl_args, idl2json_options)
+}
+
+/// Converts a parsed candid tuple, or summarizes it or selects values in it, as given by the arguments.
+fn convert_args(
+    args: &Args,
+    mut idl_args: IDLArgs,
+    idl2json_options: &Idl2JsonOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let input_type = input_type(args, idl2json_options)?;
+    // A tuple type gives a single array of the converted values, unless other framing is requested.
+    let conversion_framing = match input_type {
+        InputType::Types(_) => args.framing.or(Some(Framing::Array)),
+        _ => args.framing,
+    };
+    // The types of the values in the tuple, if known.
+    let mut idl_types = match input_type {
+        InputType::Types(idl_types) => idl_types.args.into_iter().map(Some).collect(),
+        InputType::Type(idl_type) => vec![Some(idl_type); idl_args.args.len()],
+        InputType::Untyped => vec![],
+    };
+    if args.unwrap_result {
+        let exit_code = args.err_exit_code.unwrap_or(DEFAULT_ERR_EXIT_CODE);
+        (idl_args, idl_types) = unwrap_results(idl_args, &idl_types, exit_code, idl2json_options)?;
+    }
+    if args.summary {
+        return summarize(&idl_args, &idl_types, args.framing, idl2json_options);
+    }
+    if let Some(pattern) = &args.select {
+        return select(
+            &idl_args,
+            &idl_types,
+            pattern,
+            args.framing,
+            idl2json_options,
+        );
+    }
+    convert_all(&idl_args, &idl_types, conversion_framing, idl2json_options)
+}
+
+/// Returns a JSON schema describing the JSON that would be produced with the given arguments.
+fn schema(args: &Args, idl2json_options: &Idl2JsonOptions) -> anyhow::Result<Vec<u8>> {
+    let schema = match input_type(args, idl2json_options)? {
+        InputType::Types(idl_types) => idl_types2json_schema(&idl_types, idl2json_options),
+        InputType::Type(idl_type) => idl_type2json_schema(&idl_type, idl2json_options),
+        InputType::Untyped => {
+            return Err(anyhow!(
+                "Please specify a type with --typ, --init or --method."
+            ))
+        }
+    };
+    print_all([schema], args.framing, idl2json_options)
+}
+
+/// Returns TypeScript declarations describing the JSON that would be produced with the given arguments.
+///
+/// If no type is specified, all the types in the .did files are declared.
+fn typescript(args: &Args, idl2json_options: &Idl2JsonOptions) -> anyhow::Result<String> {
+    /// The name of the declared type, if the type has no name of its own.
+    const OUTPUT_TYPE_NAME: &str = "Output";
+    Ok(match input_type(args, idl2json_options)? {
+        InputType::Types(idl_types) => {
+            idl_types2typescript(OUTPUT_TYPE_NAME, &idl_types, idl2json_options)
+        }
+        InputType::Type(IDLType::VarT(type_name)) => {
+            let idl_type = idl2json_options
+                .prog
+                .iter()
+                .find_map(|prog| polyfill::idl_prog::get_type(prog, &type_name))
+                .with_context(|| anyhow!("Could not find a type called '{type_name}'."))?;
+            idl_type2typescript(&type_name, &idl_type, idl2json_options)
         }
+        InputType::Type(idl_type) => {
+            idl_type2typescript(OUTPUT_TYPE_NAME, &idl_type, idl2json_options)
+        }
+        InputType::Untyped => idl_progs2typescript(idl2json_options),
+    })
+}
+
+/// Returns the differences between the values in two files, as JSON Patch or as text.
+fn diff(args: &Args, idl2json_options: &Idl2JsonOptions) -> anyhow::Result<Vec<u8>> {
+    let [before, after] = &args.diff[..] else {
+        return Err(anyhow!("Please specify two files to compare."));
+    };
+    let before = read_idl_args(before)?;
+    let after = read_idl_args(after)?;
+    let idl_types = match input_type(args, idl2json_options)? {
+        InputType::Types(idl_types) => idl_types,
+        InputType::Type(idl_type) => IDLTypes {
+            args: vec![idl_type; before.args.len().max(after.args.len())],
+        },
+        InputType::Untyped => IDLTypes { args: vec![] },
     };
-    // Decide what to do
+    let changes = idl_args_diff(&before, &after, &idl_types, idl2json_options);
+    match args.diff_as.unwrap_or_default() {
+        DiffFormat::Patch => print_all([diff2json_patch(&changes)], args.framing, idl2json_options),
+        DiffFormat::Text => Ok(diff2text(&changes).into_bytes()),
+    }
+}
+
+/// Reads candid from a file, either as binary, starting with the magic bytes `DIDL`, or as text.
+fn read_idl_args(path: &Path) -> anyhow::Result<IDLArgs> {
+    let bytes = std::fs::read(path)
+        .with_context(|| anyhow!("Could not read candid file '{}'.", path.display()))?;
+    if bytes.starts_with(b"DIDL") {
+        IDLArgs::from_bytes(&bytes)
+            .with_context(|| anyhow!("Malformed binary candid in '{}'", path.display()))
+    } else {
+        let text = std::str::from_utf8(&bytes).with_context(|| {
+            anyhow!("Candid in '{}' is neither binary nor text", path.display())
+        })?;
+        parse(&path.display().to_string(), text, parse_idl_args)
+            .with_context(|| anyhow!("Malformed input in '{}'", path.display()))
+    }
+}
+
+/// Creates the conversion options, loading any .did files.
+fn idl2json_options(args: &Args) -> anyhow::Result<Idl2JsonOptions> {
+    let (progs, declared_field_order) = load_did_files(&args.did)?;
+    Ok(idl2json_options_with(args, progs, declared_field_order))
+}
+
+/// Reads and parses .did files, noting the order in which record fields are declared.
+fn load_did_files(paths: &[PathBuf]) -> anyhow::Result<(Vec<IDLProg>, DeclaredFieldOrder)> {
+    let mut declared_field_order = DeclaredFieldOrder::default();
+    let progs: anyhow::Result<Vec<IDLProg>> = paths
+        .iter()
+        .map(|did| {
+            let did_as_str = std::fs::read_to_string(did)
+                .with_context(|| anyhow!("Could not read did file '{}'.", did.display()))?;
+            let prog = parse(&did.display().to_string(), &did_as_str, IDLProg::from_str)
+                .with_context(|| anyhow!("Failed to parse did file '{}'", did.display()))?;
+            declared_field_order.add_did(&did_as_str);
+            Ok(prog)
+        })
+        .collect();
+    Ok((progs?, declared_field_order))
+}
+
+/// Creates the conversion options, with .did files that have already been loaded.
+fn idl2json_options_with(
+    args: &Args,
+    progs: Vec<IDLProg>,
+    declared_field_order: DeclaredFieldOrder,
+) -> Idl2JsonOptions {
+    Idl2JsonOptions {
+        prog: progs,
+        bytes_as: args.bytes_as,
+        utf8_bytes_as_text: args.utf8_bytes_as_text,
+        principal_bytes_lengths: args.principal_bytes_length.clone(),
+        principal_bytes_paths: args.principal_bytes_path.clone(),
+        non_finite_floats_as_null: args.non_finite_floats_as_null,
+        output_format: args.format.unwrap_or_default(),
+        compact: args.compact || args.stream,
+        canonical: args.canonical,
+        fill_missing_fields: args.fill_missing_fields,
+        key_order: args.key_order.unwrap_or_default(),
+        declared_field_order,
+        lossless: args.lossless,
+        flatten_generic_values: args.flatten_generic_values,
+        generic_value_types: args.generic_value_type.clone(),
+        redact_paths: args.redact_path.clone(),
+        redact_types: args.redact_type.clone(),
+        redact_as: args.redact_as.unwrap_or_default(),
+        max_vec_elements: args.max_vec_elements,
+        max_text_length: args.max_text_length,
+        ..Idl2JsonOptions::default()
+    }
+}
+
+/// The type of the input, as specified on the command line.
+enum InputType {
+    /// The types of all the values in the input.
+    Types(IDLTypes),
+    /// The type of each value in the input.
+    Type(IDLType),
+    /// No type has been specified.
+    Untyped,
+}
+
+/// Determines the type of the input from the command line arguments.
+fn input_type(args: &Args, idl2json_options: &Idl2JsonOptions) -> anyhow::Result<InputType> {
     if args.init {
         // Use the type of the .did file init arg.
         // - If multiple did files are provided, the first is used.
@@ -57,69 +351,200 @@ pub fn main(args: &Args, idl_str: &str) -> anyhow::Result<String> {
                 .context("Please specify which .did file to use.")?,
         )
         .context("Failed to get the service argument from the did file.")?;
-        serde_json::to_string(&idl_args2json_with_weak_names(
-            &idl_args,
-            &idl_types,
-            &idl2json_options,
-        ))
-        .context("Failed to serialize to json")
+        Ok(InputType::Types(idl_types))
+    } else if let Some(method) = &args.method {
+        // Use the return type of the method, as that is what a canister call returns.
+        let method_type = idl2json_options
+            .prog
+            .iter()
+            .find_map(|prog| polyfill::idl_prog::get_method_type(prog, method))
+            .with_context(|| {
+                anyhow!("Could not find a method called '{method}' in the did files.")
+            })?;
+        Ok(InputType::Types(IDLTypes {
+            args: method_type.rets,
+        }))
     } else if let Some(idl_type) = &args.typ {
         if idl_type.trim().starts_with('(') {
-            let idl_types = IDLTypes::from_str(idl_type).context("Failed to parse type")?;
-            serde_json::to_string(&idl_args2json_with_weak_names(
-                &idl_args,
-                &idl_types,
-                &idl2json_options,
-            ))
-            .context("Failed to serialize to json")
+            let idl_types =
+                parse("--typ", idl_type, IDLTypes::from_str).context("Failed to parse type")?;
+            Ok(InputType::Types(idl_types))
         } else {
-            let idl_type = IDLType::from_str(idl_type).context("Failed to parse type")?;
-            convert_all(&idl_args, &Some(idl_type), &idl2json_options)
+            let idl_type =
+                parse("--typ", idl_type, IDLType::from_str).context("Failed to parse type")?;
+            Ok(InputType::Type(idl_type))
         }
     } else {
-        convert_all(&idl_args, &None, &idl2json_options)
+        Ok(InputType::Untyped)
     }
 }
 
+/// The types of the arguments of a method, as candid sent to the method should be.
+fn method_arg_types(method: &str, idl2json_options: &Idl2JsonOptions) -> anyhow::Result<IDLTypes> {
+    let method_type = idl2json_options
+        .prog
+        .iter()
+        .find_map(|prog| polyfill::idl_prog::get_method_type(prog, method))
+        .with_context(|| anyhow!("Could not find a method called '{method}' in the did files."))?;
+    Ok(IDLTypes {
+        args: method_type.args,
+    })
+}
+
 /// Candid typically comes as a tuple of values.  This converts a single value in such a tuple.
 fn convert_one(
     idl_value: &IDLValue,
     idl_type: &Option<IDLType>,
     idl2json_options: &Idl2JsonOptions,
-) -> anyhow::Result<String> {
-    let json_value = if let Some(idl_type) = idl_type {
-        idl2json_with_weak_names(idl_value, idl_type, idl2json_options)
-    } else {
-        idl2json(idl_value, idl2json_options)
+) -> JsonValue {
+    match (idl_type, idl2json_options.output_format.is_tabular()) {
+        (Some(idl_type), true) => idl2table_with_weak_names(idl_value, idl_type, idl2json_options),
+        (None, true) => idl2table(idl_value, idl2json_options),
+        (Some(idl_type), false) => idl2json_with_weak_names(idl_value, idl_type, idl2json_options),
+        (None, false) => idl2json(idl_value, idl2json_options),
+    }
+}
+
+/// Serializes values in the requested format and framing.
+///
+/// Without framing, text is separated by newlines, tables by blank lines and YAML by document separators,
+/// and binary values are concatenated.  Framing as lines is the same, but with compact JSON.
+///
+/// Framing tables as an array gives a single table, with the rows of all the values.
+fn print_all(
+    json_values: impl IntoIterator<Item = JsonValue>,
+    framing: Option<Framing>,
+    idl2json_options: &Idl2JsonOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let mut json_values = json_values.into_iter();
+    let separator: &[u8] = match idl2json_options.output_format {
+        format if format.is_binary() => b"",
+        OutputFormat::Yaml => b"\n---\n",
+        format if format.is_tabular() => b"\n\n",
+        _ => b"\n",
     };
-    (if idl2json_options.compact {
-        serde_json::to_string
-    } else {
-        serde_json::to_string_pretty
-    })(&json_value)
-    .with_context(|| anyhow!("Cannot print to stderr"))
+    match framing {
+        Some(Framing::Array) => {
+            let json_values = if idl2json_options.output_format.is_tabular() {
+                json_values
+                    .flat_map(|rows| match rows {
+                        JsonValue::Array(rows) => rows,
+                        row => vec![row],
+                    })
+                    .collect()
+            } else {
+                json_values.collect()
+            };
+            print_one(&JsonValue::Array(json_values), false, idl2json_options)
+        }
+        Some(Framing::First) => json_values.next().map_or(Ok(vec![]), |json_value| {
+            print_one(&json_value, false, idl2json_options)
+        }),
+        Some(Framing::Lines) | None => {
+            let compact = framing == Some(Framing::Lines);
+            let printed = json_values
+                .map(|json_value| print_one(&json_value, compact, idl2json_options))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            Ok(printed.join(separator))
+        }
+    }
+}
+
+/// Serializes a value in the requested format, without a trailing newline.
+///
+/// JSON is compact if requested here or in the options.
+fn print_one(
+    json_value: &JsonValue,
+    compact: bool,
+    idl2json_options: &Idl2JsonOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let mut printed = match idl2json_options.output_format {
+        OutputFormat::Json if compact && !idl2json_options.canonical => {
+            serde_json::to_vec(json_value).context("Failed to serialize to json")?
+        }
+        _ => serialize(json_value, idl2json_options)?,
+    };
+    if !idl2json_options.output_format.is_binary() && printed.ends_with(b"\n") {
+        printed.pop();
+    }
+    Ok(printed)
 }
 
-/// Candid typically comes as a tuple of values.  This converts all such tuples
+/// Converts the values at the paths matching a pattern, in each of the given values.
+///
+/// Values without a type are converted without type information.
+fn select(
+    idl_args: &IDLArgs,
+    idl_types: &[Option<IDLType>],
+    pattern: &PathPattern,
+    framing: Option<Framing>,
+    idl2json_options: &Idl2JsonOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let json_values =
+        idl_args
+            .args
+            .iter()
+            .enumerate()
+            .flat_map(|(index, idl_value)| match idl_types.get(index) {
+                Some(Some(idl_type)) => {
+                    idl2json_with_weak_names_select(idl_value, idl_type, pattern, idl2json_options)
+                }
+                _ => idl2json_select(idl_value, pattern, idl2json_options),
+            });
+    print_all(json_values, framing, idl2json_options)
+}
+
+/// Summarizes the shape of each of the given values.
+///
+/// Values without a type are summarized without type information.
+fn summarize(
+    idl_args: &IDLArgs,
+    idl_types: &[Option<IDLType>],
+    framing: Option<Framing>,
+    idl2json_options: &Idl2JsonOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let summaries =
+        idl_args
+            .args
+            .iter()
+            .enumerate()
+            .map(|(index, idl_value)| match idl_types.get(index) {
+                Some(Some(idl_type)) => {
+                    idl2summary_with_weak_names(idl_value, idl_type, idl2json_options)
+                }
+                _ => idl2summary(idl_value, idl2json_options),
+            });
+    print_all(summaries, framing, idl2json_options)
+}
+
+/// Candid typically comes as a tuple of values.  This converts all the values in such a tuple.
+///
+/// Values without a type are converted without type information.
 fn convert_all(
     idl_args: &IDLArgs,
-    idl_type: &Option<IDLType>,
+    idl_types: &[Option<IDLType>],
+    framing: Option<Framing>,
     idl2json_options: &Idl2JsonOptions,
-) -> anyhow::Result<String> {
-    let json_structures: anyhow::Result<Vec<String>> = idl_args
-        .args
-        .iter()
-        .map(|idl_value| convert_one(idl_value, idl_type, idl2json_options))
-        .collect();
-    Ok(json_structures?.join("\n"))
+) -> anyhow::Result<Vec<u8>> {
+    let json_values = idl_args.args.iter().enumerate().map(|(index, idl_value)| {
+        convert_one(
+            idl_value,
+            idl_types.get(index).unwrap_or(&None),
+            idl2json_options,
+        )
+    });
+    print_all(json_values, framing, idl2json_options)
 }
 
 /// Converts Candid on stdin to JSON on stdout.
 #[derive(Parser, Debug, Default)]
 #[clap(name("idl2json"), version = concat!(env!("CARGO_PKG_VERSION"), "\ncandid ", env!("CARGO_CANDID_VERSION")))]
 pub struct Args {
+    /// Describe the .did files, serve conversions over HTTP or convert files, instead of converting stdin
+    #[clap(subcommand)]
+    command: Option<Command>,
     /// A .did file containing type definitions
-    #[clap(short, long)]
+    #[clap(short, long, global = true)]
     did: Vec<PathBuf>,
     /// The name of a type in the provided .did file
     #[clap(short, long)]
@@ -127,10 +552,181 @@ pub struct Args {
     /// Use the service init argument type from the did file
     #[clap(short, long, requires("did"))]
     init: bool,
+    /// Use the return type of a service method in the did file
+    #[clap(short, long, requires("did"), conflicts_with_all(["init", "typ"]))]
+    method: Option<String>,
     /// How to display bytes
     #[clap(short, long, value_enum)]
     bytes_as: Option<BytesFormat>,
+    /// Display bytes that are UTF-8 text as strings
+    #[clap(long)]
+    utf8_bytes_as_text: bool,
+    /// Display bytes of this length as principals
+    #[clap(long)]
+    principal_bytes_length: Vec<usize>,
+    /// Display bytes at this path as principals, e.g. `accounts.*.owner`
+    #[clap(long)]
+    principal_bytes_path: Vec<PathPattern>,
+    /// Display NaN and infinite floats as `null` rather than as "NaN", "Infinity" and "-Infinity"
+    #[clap(long)]
+    non_finite_floats_as_null: bool,
+    /// Convert only the values at this path, e.g. `proposal.action.*.payload`
+    ///
+    /// Paths are made of record field names, variant tags and vec indices; `*` matches any one of them.
+    /// Each matching value is printed separately.  Values inside redacted values are not selected.
+    #[clap(long, conflicts_with_all(["schema", "typescript", "diff"]))]
+    select: Option<PathPattern>,
+    /// The output format (default: json)
+    #[clap(short, long, value_enum)]
+    format: Option<OutputFormat>,
+    /// How to frame the values in the output (default: an array for tuple types, otherwise one after the other)
+    #[clap(long, value_enum)]
+    framing: Option<Framing>,
     /// Print compact output
     #[clap(short, long)]
     compact: bool,
+    /// Print canonical JSON (RFC 8785), so that equal values always give identical output
+    #[clap(long, conflicts_with("key_order"))]
+    canonical: bool,
+    /// Add absent `opt`, `null` and `reserved` record fields declared in the type
+    #[clap(long)]
+    fill_missing_fields: bool,
+    /// How to order the keys of JSON objects (default: alphabetical)
+    #[clap(long, value_enum)]
+    key_order: Option<KeyOrder>,
+    /// Tag every value with its candid type, so that the output can be converted back to the same candid
+    #[clap(long, conflicts_with_all(["bytes_as", "schema", "typescript"]))]
+    lossless: bool,
+    /// Render generic values, such as ICRC-3 and ICRC-16 `Value`, as plain JSON
+    #[clap(long)]
+    flatten_generic_values: bool,
+    /// The name of a type to render as plain JSON, as with --flatten-generic-values
+    #[clap(long)]
+    generic_value_type: Vec<String>,
+    /// Redact the values at this path, e.g. `devices.*.pubkey`
+    #[clap(long)]
+    redact_path: Vec<PathPattern>,
+    /// Redact the values of this type, e.g. `SessionKey`
+    #[clap(long)]
+    redact_type: Vec<String>,
+    /// How to display redacted values (default: placeholder)
+    #[clap(long, value_enum)]
+    redact_as: Option<RedactFormat>,
+    /// Compare the candid in two files, text or binary, instead of converting stdin
+    #[clap(long, num_args(2), value_names(["BEFORE", "AFTER"]), conflicts_with_all(["schema", "typescript"]))]
+    diff: Vec<PathBuf>,
+    /// How to display differences (default: patch)
+    #[clap(long, value_enum, requires("diff"))]
+    diff_as: Option<DiffFormat>,
+    /// Show at most this many elements of each vec, followed by `{"…": "N more elements"}`
+    #[clap(long)]
+    max_vec_elements: Option<usize>,
+    /// Show at most this many characters of each text, as `{"text": "...", "…": "N more characters"}`
+    #[clap(long)]
+    max_text_length: Option<usize>,
+    /// Print the payload of `Ok` results, such as `variant { Ok : T; Err : E }`, rather than the result
+    ///
+    /// On `Err`, the error payload is printed to stderr and the exit code is given by --err-exit-code.
+    #[clap(long, conflicts_with_all(["schema", "typescript", "diff"]))]
+    unwrap_result: bool,
+    /// The exit code for `Err` results, with --unwrap-result (default: 2)
+    #[clap(long, requires("unwrap_result"), value_parser = clap::value_parser!(u8).range(1..))]
+    err_exit_code: Option<u8>,
+    /// Check that the candid conforms to the type given by --typ, --init or --method, instead of converting it
+    ///
+    /// The candid may be text or hex-encoded binary.  With --method, it is checked against the arguments of the
+    /// method, as it is to be sent to it.  Each value that does not conform is listed with its path and the
+    /// reason, and the exit code is then 3.
+    #[clap(long, conflicts_with_all(["schema", "typescript", "diff", "select", "summary", "unwrap_result", "stream"]))]
+    check: bool,
+    /// Print a summary of the shape of each value, instead of converting it
+    ///
+    /// The summary gives the number of values of each type, vec lengths, blob bytes and variant tags, per path.
+    #[clap(long, conflicts_with_all(["schema", "typescript", "diff", "select"]))]
+    summary: bool,
+    /// Convert each candid tuple in stdin separately, printing the output for each tuple as it is read
+    ///
+    /// This implies compact output, so that there is one line of JSON per value.
+    #[clap(long, conflicts_with_all(["schema", "typescript", "diff"]))]
+    stream: bool,
+    /// Print a JSON schema of the output for the given type, instead of converting stdin
+    #[clap(long)]
+    schema: bool,
+    /// Print TypeScript declarations of the output, instead of converting stdin
+    ///
+    /// Without a type, all the types in the did files are declared.
+    #[clap(long, conflicts_with("schema"))]
+    typescript: bool,
+    /// A configuration file with defaults for these options (default: .idl2json.toml in the current directory or above)
+    #[clap(long)]
+    config: Option<PathBuf>,
+    /// The profile to use from the configuration file, as in `[profiles.NAME]`
+    #[clap(long)]
+    profile: Option<String>,
+    /// The command line, with the options from any configuration file, as parsed by `parse_args()`.
+    #[clap(skip)]
+    argv: Vec<OsString>,
+}
+
+impl Args {
+    /// Whether Candid is read from stdin all at once.  In stream mode, it is read tuple by tuple.
+    pub fn reads_stdin(&self) -> bool {
+        !(self.command.is_some()
+            || self.schema
+            || self.typescript
+            || !self.diff.is_empty()
+            || self.stream)
+    }
+
+    /// Whether stdin is converted tuple by tuple, with `stream()`.
+    pub fn streams(&self) -> bool {
+        self.stream && self.command.is_none()
+    }
+
+    /// Whether the output is binary, rather than text.
+    pub fn writes_binary(&self) -> bool {
+        let writes_text = self.command.is_some()
+            || self.typescript
+            || (!self.diff.is_empty() && self.diff_as.unwrap_or_default() == DiffFormat::Text);
+        self.format.is_some_and(OutputFormat::is_binary) && !writes_text
+    }
+}
+
+/// Commands to run instead of converting stdin.
+#[derive(clap::Subcommand, Clone, Debug)]
+pub enum Command {
+    /// Commands that describe the .did files.
+    #[clap(flatten)]
+    Describe(Describe),
+    /// Serve conversions over HTTP, loading the .did files once
+    ///
+    /// Options given here are the defaults for every request.  See `serve()` for the endpoints.
+    Serve(Serve),
+    /// Convert many files, text or binary candid, loading the .did files once
+    ///
+    /// Each output is written to a file, alongside the input or in --out-dir.
+    Batch(Batch),
+}
+
+/// How to display the differences between two values.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug, clap::ValueEnum)]
+#[clap(rename_all = "kebab_case")]
+pub enum DiffFormat {
+    /// A JSON Patch (RFC 6902) that turns the JSON of the first value into that of the second
+    #[default]
+    Patch,
+    /// One line per change, with candid paths
+    Text,
+}
+
+/// How to frame the values in the output.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, clap::ValueEnum)]
+#[clap(rename_all = "kebab_case")]
+pub enum Framing {
+    /// A single array of all the values
+    Array,
+    /// One value after the other, as JSON Lines (NDJSON) for JSON
+    Lines,
+    /// Only the first value
+    First,
 }
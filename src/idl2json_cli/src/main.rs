@@ -1,14 +1,66 @@
-use clap::Parser;
+use anyhow::Context;
 use idl2json_cli as lib;
-use std::io::{self, Read};
+use std::{
+    io::{self, Read, Write},
+    net::TcpListener,
+};
 
-/// Reads IDL from stdin, writes JSON to stdout.
+/// Reads IDL from stdin, writes JSON, or the requested format, to stdout.
+///
+/// Errors are printed to stderr and the process exits with `lib::exit_code()`.
 fn main() {
-    let args = lib::Args::parse();
+    let args = lib::parse_args(std::env::args_os()).unwrap_or_else(|err| err.exit());
+    if let Err(err) = run(&args) {
+        if let Some(result_err) = err.downcast_ref::<lib::ResultErr>() {
+            // Only the error payload, so that it can be processed as the output would have been.
+            eprintln!("{result_err}");
+        } else if let Some(nonconforming) = err.downcast_ref::<lib::Nonconforming>() {
+            // The report is the output, as it would have been had the candid conformed.
+            if write_output(&args, nonconforming.report().to_vec()).is_err() {
+                eprintln!("Error: {err}");
+            }
+        } else {
+            eprintln!("Error: {err}");
+            for cause in err.chain().skip(1) {
+                eprintln!("\n{cause}");
+            }
+        }
+        std::process::exit(lib::exit_code(&err));
+    }
+}
+
+/// Converts stdin to stdout, as given by the arguments.
+fn run(args: &lib::Args) -> anyhow::Result<()> {
+    if let Some(address) = args.listen_address() {
+        let listener =
+            TcpListener::bind(address).with_context(|| format!("Failed to listen on {address}"))?;
+        eprintln!("Serving on http://{}", listener.local_addr()?);
+        return lib::serve(args, listener);
+    }
+    if args.batches() {
+        let count = lib::batch(args)?;
+        eprintln!("Converted {count} files");
+        return Ok(());
+    }
+    if args.streams() {
+        return lib::stream(args, io::stdin().lock(), io::stdout().lock());
+    }
     let mut buffer = String::new();
-    io::stdin()
-        .read_to_string(&mut buffer)
-        .expect("Failed to read string from stdin");
-    let json_str = lib::main(&args, &buffer).expect("Failed to convert IDL to JSON");
-    println!("{json_str}");
+    if args.reads_stdin() {
+        io::stdin()
+            .read_to_string(&mut buffer)
+            .context("Failed to read string from stdin")?;
+    }
+    let output = lib::main(args, &buffer).context("Failed to convert IDL to JSON")?;
+    write_output(args, output)
+}
+
+/// Writes the output to stdout, followed by a new line if it is text.
+fn write_output(args: &lib::Args, mut output: Vec<u8>) -> anyhow::Result<()> {
+    if !args.writes_binary() {
+        output.push(b'\n');
+    }
+    io::stdout()
+        .write_all(&output)
+        .context("Failed to write to stdout")
 }
@@ -0,0 +1,96 @@
+//! Errors in candid text, reported with their position in the text.
+use candid_parser::Error;
+use codespan_reporting::{
+    files::{self, Files, SimpleFile},
+    term::{self, termcolor::NoColor},
+};
+use std::{fmt, ops::Range};
+
+/// Candid text that could not be parsed.
+///
+/// The error is displayed as the parser reports it: the line and column, the offending text with
+/// a caret, and the tokens that the parser expected there.
+#[derive(Debug)]
+pub struct ParseError {
+    /// The report of the error, including the offending text.
+    report: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.report.trim_end())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses candid text, reporting any error with its position in the text.
+///
+/// The source name, such as a file name, is given in the report.
+pub(crate) fn parse<T>(
+    source_name: &str,
+    source: &str,
+    parse: impl FnOnce(&str) -> candid_parser::Result<T>,
+) -> Result<T, ParseError> {
+    parse_from_line(source_name, 1, source, parse)
+}
+
+/// Parses candid text that starts at the given line of its source, such as a tuple in a stream, reporting any
+/// error with its position in the source.
+pub(crate) fn parse_from_line<T>(
+    source_name: &str,
+    first_line: usize,
+    source: &str,
+    parse: impl FnOnce(&str) -> candid_parser::Result<T>,
+) -> Result<T, ParseError> {
+    parse(source).map_err(|err| ParseError {
+        report: report(source_name, first_line, source, &err),
+    })
+}
+
+/// Text that starts at a given line of its source.
+struct LinesFrom<'a> {
+    file: SimpleFile<&'a str, &'a str>,
+    /// The number of the first line of the text.
+    first_line: usize,
+}
+
+impl<'a> Files<'a> for LinesFrom<'a> {
+    type FileId = ();
+    type Name = &'a str;
+    type Source = &'a str;
+
+    fn name(&'a self, id: ()) -> Result<&'a str, files::Error> {
+        Files::name(&self.file, id)
+    }
+
+    fn source(&'a self, id: ()) -> Result<&'a str, files::Error> {
+        Files::source(&self.file, id)
+    }
+
+    fn line_index(&'a self, id: (), byte_index: usize) -> Result<usize, files::Error> {
+        Files::line_index(&self.file, id, byte_index)
+    }
+
+    fn line_number(&'a self, _: (), line_index: usize) -> Result<usize, files::Error> {
+        Ok(self.first_line + line_index)
+    }
+
+    fn line_range(&'a self, id: (), line_index: usize) -> Result<Range<usize>, files::Error> {
+        Files::line_range(&self.file, id, line_index)
+    }
+}
+
+/// The report of a parser error, as it would be printed by the candid tools, without colour.
+fn report(source_name: &str, first_line: usize, source: &str, err: &Error) -> String {
+    let file = LinesFrom {
+        file: SimpleFile::new(source_name, source),
+        first_line,
+    };
+    let mut writer = NoColor::new(Vec::new());
+    match term::emit(&mut writer, &term::Config::default(), &file, &err.report()) {
+        Ok(()) => String::from_utf8_lossy(&writer.into_inner()).into_owned(),
+        // The position is not in the text, so the error is given without it.
+        Err(_) => err.to_string(),
+    }
+}
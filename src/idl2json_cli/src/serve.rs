@@ -0,0 +1,528 @@
+//! Conversions served over HTTP, with the .did files loaded once rather than for every conversion.
+//!
+//! Endpoints:
+//! - `POST /idl2json`: converts the candid text in the body, as stdin would be converted.
+//! - `POST /binary2json`: converts the binary candid in the body, raw or as hex.
+//! - `POST /json2idl`: converts the JSON in the body, or YAML if the `Content-Type` contains `yaml`, to candid text.
+//!   The JSON is as produced with the same options, or lossless JSON with `lossless`.
+//! - `GET /types`, `GET /methods` and `GET /types/NAME?depth=N`: as the `types`, `methods` and `show-type` commands.
+//!
+//! Options are given per request as query parameters, with the long names of the command line options:
+//! `/idl2json?typ=Account&bytes-as=hex&compact`.  They override those given when the server was started, and
+//! flags may be turned off with `false`, as in `compact=false`.
+//! Options that read files, such as `did`, may not be given per request.
+//!
+//! The server is meant for local use: it reads one request per connection, with its body given by `Content-Length`.
+//! Connections beyond `MAX_CONNECTIONS` wait until one is finished.
+use crate::{
+    check::parse_hex, config::unoverridden_args, convert_args, idl2json_options_with, input_type,
+    introspect, load_did_files, main_with_options, method_arg_types, Args, Describe, DiffFormat,
+    InputType, Nonconforming, ResultErr,
+};
+use anyhow::{anyhow, bail, Context};
+use candid::IDLArgs;
+use clap::{CommandFactory, FromArgMatches};
+use idl2json::{
+    json2idl, json_args2idl, lossless_json2idl_args, polyfill, DeclaredFieldOrder, Idl2JsonOptions,
+    JsonValue, OutputFormat,
+};
+use std::{
+    ffi::OsString,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+/// The address the server listens on, unless another is given.
+const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:8080";
+/// The options that read files or change how input is read, so may not be given per request.
+const SERVER_OPTIONS: [&str; 5] = ["did", "config", "profile", "diff", "stream"];
+/// The largest request body that is accepted.
+const MAX_BODY_LENGTH: usize = 64 * 1024 * 1024;
+/// The number of connections handled at once.  Further connections wait until one is finished.
+const MAX_CONNECTIONS: usize = 16;
+/// How long to wait for a client to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// The content type of text responses.
+const TEXT: &str = "text/plain; charset=utf-8";
+
+/// Options for serving conversions over HTTP.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Serve {
+    /// The address to listen on
+    #[clap(long, default_value = DEFAULT_LISTEN_ADDRESS)]
+    listen: SocketAddr,
+}
+
+impl Args {
+    /// The address to listen on, with the `serve` command.
+    pub fn listen_address(&self) -> Option<SocketAddr> {
+        match &self.command {
+            Some(crate::Command::Serve(serve)) => Some(serve.listen),
+            _ => None,
+        }
+    }
+}
+
+/// Serves conversions on the listener, as described in the module documentation, until the listener fails.
+///
+/// The .did files are loaded once.  Each connection is handled in its own thread, with at most `MAX_CONNECTIONS`
+/// at once.
+pub fn serve(args: &Args, listener: TcpListener) -> anyhow::Result<()> {
+    let (progs, declared_field_order) = load_did_files(&args.did)?;
+    let server = Server {
+        command_line: Args::command()
+            .ignore_errors(true)
+            .try_get_matches_from(&args.argv)
+            .unwrap_or_default(),
+        progs,
+        declared_field_order,
+    };
+    let server = &server;
+    let connections = &Connections::default();
+    std::thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let stream = stream.context("Failed to accept a connection")?;
+            let connection = connections.start()?;
+            scope.spawn(move || {
+                if let Err(err) = server.handle(stream) {
+                    eprintln!("Error: {err:#}");
+                }
+                drop(connection);
+            });
+        }
+        Ok(())
+    })
+}
+
+/// The number of connections being handled.
+#[derive(Default)]
+struct Connections {
+    count: Mutex<usize>,
+    /// Notified when a connection is finished.
+    finished: Condvar,
+}
+
+impl Connections {
+    /// Waits until fewer than `MAX_CONNECTIONS` connections are being handled, then counts another one until the
+    /// returned guard is dropped.
+    fn start(&self) -> anyhow::Result<Connection<'_>> {
+        let poisoned = |_| anyhow!("A connection failed unexpectedly.");
+        let mut count = self.count.lock().map_err(poisoned)?;
+        while *count >= MAX_CONNECTIONS {
+            count = self.finished.wait(count).map_err(poisoned)?;
+        }
+        *count += 1;
+        Ok(Connection { connections: self })
+    }
+}
+
+/// A connection being handled, counted in `Connections` until it is dropped.
+struct Connection<'a> {
+    connections: &'a Connections,
+}
+
+impl Drop for Connection<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut count) = self.connections.count.lock() {
+            *count -= 1;
+        }
+        self.connections.finished.notify_one();
+    }
+}
+
+/// The state shared by all requests.
+struct Server {
+    /// The options given when the server was started.
+    command_line: clap::ArgMatches,
+    /// The parsed .did files.
+    progs: Vec<candid_parser::IDLProg>,
+    /// The order in which record fields are declared in the .did files.
+    declared_field_order: DeclaredFieldOrder,
+}
+
+/// An HTTP request.
+struct Request {
+    /// The method, such as `GET`.
+    method: String,
+    /// The path, without the query.
+    path: String,
+    /// The query parameters, decoded.
+    query: Vec<(String, String)>,
+    /// The value of the `Content-Type` header, if any.
+    content_type: Option<String>,
+    /// The body.
+    body: Vec<u8>,
+}
+
+/// An HTTP response.
+struct Response {
+    /// The status code, such as 200.
+    status: u16,
+    /// The value of the `Content-Type` header.
+    content_type: &'static str,
+    /// The body.
+    body: Vec<u8>,
+}
+
+impl Response {
+    /// A response with text, such as an error message.
+    fn text(status: u16, text: impl Into<String>) -> Self {
+        Response {
+            status,
+            content_type: TEXT,
+            body: text.into().into_bytes(),
+        }
+    }
+
+    /// Writes the response, closing the connection.
+    fn write_to(&self, mut stream: impl Write) -> std::io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            422 => "Unprocessable Entity",
+            _ => "Internal Server Error",
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.content_type,
+            self.body.len()
+        )?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+impl Server {
+    /// Reads a request from the connection and writes the response.
+    fn handle(&self, stream: TcpStream) -> anyhow::Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let response = match read_request(&mut BufReader::new(&stream)) {
+            Ok(request) => self.respond(&request),
+            Err(response) => response,
+        };
+        response
+            .write_to(&stream)
+            .context("Failed to write the response")
+    }
+
+    /// The response to a request.
+    fn respond(&self, request: &Request) -> Response {
+        let (method, path) = (request.method.as_str(), request.path.as_str());
+        let expected_method = match path {
+            "/idl2json" | "/binary2json" | "/json2idl" => "POST",
+            "/types" | "/methods" => "GET",
+            path if path.starts_with("/types/") => "GET",
+            _ => return Response::text(404, format!("There is no endpoint {path}.")),
+        };
+        if method != expected_method {
+            return Response::text(405, format!("Please use {expected_method} for {path}."));
+        }
+        let response = match path {
+            "/types" => self.describe(Describe::Types, request),
+            "/methods" => self.describe(Describe::Methods, request),
+            "/idl2json" | "/binary2json" | "/json2idl" => self.convert(path, request),
+            type_path => {
+                let name = percent_decode(type_path.trim_start_matches("/types/"));
+                self.show_type(name, request)
+            }
+        };
+        response.unwrap_or_else(|err| {
+            if let Some(nonconforming) = err.downcast_ref::<Nonconforming>() {
+                Response {
+                    status: 422,
+                    content_type: TEXT,
+                    body: nonconforming.report().to_vec(),
+                }
+            } else if let Some(result_err) = err.downcast_ref::<ResultErr>() {
+                Response::text(422, result_err.to_string())
+            } else {
+                Response::text(400, format!("Error: {err:#}"))
+            }
+        })
+    }
+
+    /// Converts the body of a request, as given by the endpoint and the options in the query.
+    fn convert(&self, path: &str, request: &Request) -> anyhow::Result<Response> {
+        let args = self.request_args(&request.query)?;
+        let idl2json_options = self.idl2json_options(&args);
+        let body = match path {
+            "/binary2json" => {
+                let idl_args = match request.body.starts_with(b"DIDL") {
+                    true => IDLArgs::from_bytes(&request.body).context("Malformed binary candid"),
+                    false => parse_hex(&body_text(request)?)
+                        .unwrap_or_else(|| Err(anyhow!("Expected binary candid, raw or as hex."))),
+                }?;
+                convert_args(&args, idl_args, &idl2json_options)?
+            }
+            "/json2idl" => {
+                let text = body_text(request)?;
+                let is_yaml = request
+                    .content_type
+                    .as_ref()
+                    .is_some_and(|content_type| content_type.contains("yaml"));
+                let json: JsonValue = if is_yaml {
+                    serde_yaml::from_str(&text).context("Malformed YAML")?
+                } else {
+                    serde_json::from_str(&text).context("Malformed JSON")?
+                };
+                let idl_args = json2candid(&args, &json, &idl2json_options)?;
+                return Ok(Response::text(200, idl_args.to_string()));
+            }
+            _ => main_with_options(&args, &body_text(request)?, &idl2json_options)?,
+        };
+        Ok(Response {
+            status: 200,
+            content_type: content_type(&args),
+            body,
+        })
+    }
+
+    /// Describes the .did files, as by the `types` and `methods` commands.  No options are accepted.
+    fn describe(&self, describe: Describe, request: &Request) -> anyhow::Result<Response> {
+        if let Some((key, _)) = request.query.first() {
+            bail!("Unknown option '{key}'.");
+        }
+        let paths = self.did_paths();
+        let text = introspect(&describe, &paths, &self.progs)?;
+        Ok(Response::text(200, text))
+    }
+
+    /// Shows a type, as by the `show-type` command.  The only option is `depth`.
+    fn show_type(&self, name: String, request: &Request) -> anyhow::Result<Response> {
+        let mut depth = None;
+        for (key, value) in &request.query {
+            match key.as_str() {
+                "depth" => depth = Some(value.parse().context("Malformed depth")?),
+                _ => bail!("Unknown option '{key}'."),
+            }
+        }
+        let paths = self.did_paths();
+        let text = introspect(&Describe::ShowType { name, depth }, &paths, &self.progs)?;
+        Ok(Response::text(200, text))
+    }
+
+    /// The options for a request: those in the query, and otherwise those given when the server was started.
+    fn request_args(&self, query: &[(String, String)]) -> anyhow::Result<Args> {
+        let command = Args::command();
+        let mut request_argv = vec![OsString::from("idl2json")];
+        // Flags given as false, which are dropped from the options given when the server was started.
+        let mut unset_flags = Vec::new();
+        for (key, value) in query {
+            let id = key.replace('-', "_");
+            let Some((arg, long)) = command.get_arguments().find_map(|arg| {
+                Some((arg, arg.get_long()?)).filter(|_| arg.get_id() == id.as_str())
+            }) else {
+                bail!("Unknown option '{key}'.");
+            };
+            if SERVER_OPTIONS.contains(&id.as_str()) {
+                bail!("'{key}' may only be given when the server is started.");
+            }
+            let flag = OsString::from(format!("--{long}"));
+            match (arg.get_action().takes_values(), value.as_str()) {
+                (true, value) => request_argv.push(OsString::from(format!("--{long}={value}"))),
+                (false, "" | "true") => request_argv.push(flag),
+                (false, "false") => unset_flags.push(flag),
+                (false, _) => bail!("'{key}' should be true or false."),
+            }
+        }
+        // The options in the query are validated together with those they do not override.
+        let overrides = command
+            .clone()
+            .ignore_errors(true)
+            .try_get_matches_from(&request_argv)
+            .unwrap_or_default();
+        let mut request_argv = request_argv.into_iter();
+        let argv: Vec<OsString> = request_argv
+            .next()
+            .into_iter()
+            .chain(
+                unoverridden_args(&self.command_line, &overrides)
+                    .into_iter()
+                    .filter(|arg| !unset_flags.contains(arg)),
+            )
+            .chain(request_argv)
+            .collect();
+        let matches = command.try_get_matches_from(argv).map_err(|err| {
+            anyhow!(err
+                .render()
+                .to_string()
+                .trim_start_matches("error: ")
+                .to_string())
+        })?;
+        Ok(Args::from_arg_matches(&matches)?)
+    }
+
+    /// The conversion options for a request, with the .did files loaded when the server was started.
+    fn idl2json_options(&self, args: &Args) -> Idl2JsonOptions {
+        let progs = self.progs.iter().map(polyfill::idl_prog::clone).collect();
+        idl2json_options_with(args, progs, self.declared_field_order.clone())
+    }
+
+    /// The paths of the .did files, as given when the server was started.
+    fn did_paths(&self) -> Vec<std::path::PathBuf> {
+        self.command_line
+            .get_many::<std::path::PathBuf>("did")
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
+
+/// Converts JSON back to candid, of the type given by the options, or as lossless JSON.
+///
+/// As for `--check`, a method gives the types of its arguments.
+fn json2candid(
+    args: &Args,
+    json: &JsonValue,
+    idl2json_options: &Idl2JsonOptions,
+) -> anyhow::Result<IDLArgs> {
+    if args.lossless {
+        return lossless_json2idl_args(json);
+    }
+    if let Some(method) = &args.method {
+        return json_args2idl(
+            json,
+            &method_arg_types(method, idl2json_options)?,
+            idl2json_options,
+        );
+    }
+    match input_type(args, idl2json_options)? {
+        InputType::Types(idl_types) => json_args2idl(json, &idl_types, idl2json_options),
+        InputType::Type(idl_type) => Ok(IDLArgs {
+            args: vec![json2idl(json, &idl_type, idl2json_options)?],
+        }),
+        InputType::Untyped => Err(anyhow!(
+            "Please specify a type with typ, init or method, or use lossless JSON."
+        )),
+    }
+}
+
+/// The content type of the output, as given by the options.
+fn content_type(args: &Args) -> &'static str {
+    let writes_text = args.typescript
+        || (!args.diff.is_empty() && args.diff_as.unwrap_or_default() == DiffFormat::Text);
+    if writes_text {
+        return TEXT;
+    }
+    match args.format.unwrap_or_default() {
+        OutputFormat::Json => "application/json",
+        OutputFormat::Yaml => "application/yaml",
+        OutputFormat::Cbor => "application/cbor",
+        OutputFormat::Msgpack => "application/msgpack",
+        OutputFormat::Toml => "application/toml",
+        OutputFormat::Csv => "text/csv; charset=utf-8",
+        OutputFormat::Tsv => "text/tab-separated-values; charset=utf-8",
+    }
+}
+
+/// The body of a request, as text.
+fn body_text(request: &Request) -> anyhow::Result<String> {
+    String::from_utf8(request.body.clone()).context("The body is not UTF-8 text")
+}
+
+/// Reads an HTTP request, or returns the response to a malformed one.
+fn read_request(reader: &mut impl BufRead) -> Result<Request, Response> {
+    let malformed = |what: &str| Response::text(400, format!("Malformed request: {what}"));
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|_| malformed("could not read the request line"))?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(malformed("no method and path"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query
+            .split('&')
+            .filter(|parameter| !parameter.is_empty())
+            .map(|parameter| {
+                let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+                (query_decode(key), query_decode(value))
+            })
+            .collect(),
+        content_type: None,
+        body: Vec::new(),
+    };
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        reader
+            .read_line(&mut line)
+            .map_err(|_| malformed("could not read the headers"))?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(malformed("a header without a value"));
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| malformed("the content length is not a number"))?;
+            }
+            "content-type" => request.content_type = Some(value.trim().to_ascii_lowercase()),
+            "transfer-encoding" => {
+                return Err(malformed("please give the body with a Content-Length"))
+            }
+            _ => {}
+        }
+    }
+    if content_length > MAX_BODY_LENGTH {
+        return Err(Response::text(
+            413,
+            format!("The body may be at most {MAX_BODY_LENGTH} bytes."),
+        ));
+    }
+    // The body is read as it arrives, rather than allocated from the Content-Length up front.
+    reader
+        .take(content_length as u64)
+        .read_to_end(&mut request.body)
+        .map_err(|_| malformed("could not read the body"))?;
+    if request.body.len() < content_length {
+        return Err(malformed("the body is shorter than its Content-Length"));
+    }
+    Ok(request)
+}
+
+/// Decodes a percent-encoded query parameter, with `+` for spaces.
+fn query_decode(encoded: &str) -> String {
+    // `+` is replaced before decoding, so that an encoded `%2B` remains a `+`.
+    percent_decode(&encoded.replace('+', " "))
+}
+
+/// Decodes a percent-encoded path segment.
+fn percent_decode(encoded: &str) -> String {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (byte, escaped) {
+            (b'%', Some(escaped)) => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            }
+            (byte, _) => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
@@ -4,9 +4,20 @@
 #![allow(clippy::unwrap_used)]
 #![allow(clippy::expect_used)]
 
-use super::{main, Args, BytesFormat};
+use super::{
+    batch, config::parse_args_in, exit_code, main, serve, stream, Args, BytesFormat, DiffFormat,
+    Framing, KeyOrder, Nonconforming, OutputFormat, PathPattern, CONFIG_FILE_NAME,
+    DEFAULT_ERR_EXIT_CODE, EXIT_FAILURE, EXIT_NONCONFORMING, EXIT_PARSE_ERROR,
+};
 use anyhow::anyhow;
-use std::path::Path;
+use clap::Parser;
+use std::{
+    ffi::OsString,
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::Path,
+    str::FromStr,
+};
 
 #[test]
 fn simple_conversion_should_be_correct() {
@@ -33,7 +44,7 @@ fn simple_conversion_should_be_correct() {
         let out = main(&args, vector.stdin)
             .map_err(|e| anyhow!("Failed to parse: {} due to: {e}", vector.stdin))
             .unwrap();
-        assert_eq!(vector.stdout, &out)
+        assert_eq!(vector.stdout, String::from_utf8(out).unwrap())
     }
 }
 
@@ -57,6 +68,7 @@ macro_rules! typed_arg {
             init: false,
             bytes_as: Some(BytesFormat::Numbers),
             compact: true,
+            ..Args::default()
         }
     };
 }
@@ -131,15 +143,324 @@ fn conversion_with_options_should_be_correct() {
                 init: true,
                 bytes_as: None,
                 compact: true,
+                ..Args::default()
             },
             stdout: r#"[[{"canister_creation_cycles_cost":["6_974"]}]]"#,
         },
+        // Keys may be ordered as declared in the did file.
+        // On the command line we should see:
+        // $ echo "(record{canister_creation_cycles_cost= opt 999; archive_module_hash = opt blob \"\\01\"})" | didc encode | didc decode | tee /dev/stderr | idl2json --did samples/internet_identity.did  --typ InternetIdentityInit --key-order declaration --fill-missing-fields
+        // (record { 451_920_964 = opt blob "\01"; 2_138_241_783 = opt (999 : int) })
+        // {"assigned_user_number_range":[],"archive_module_hash":[[1]],"canister_creation_cycles_cost":["999"]}
+        TestVector {
+            stdin: r#"(record { 451_920_964 = opt blob "\01"; 2_138_241_783 = opt (999 : int) })"#,
+            args: Args {
+                fill_missing_fields: true,
+                key_order: Some(KeyOrder::Declaration),
+                ..typed_arg!("internet_identity.did", "InternetIdentityInit")
+            },
+            stdout: r#"{"assigned_user_number_range":[],"archive_module_hash":[[1]],"canister_creation_cycles_cost":["999"]}"#,
+        },
+        // We should be able to use the return type of a service method.
+        // On the command line we should see:
+        // $ echo "(variant { success = principal \"aaaaa-aa\" })" | didc encode | didc decode | tee /dev/stderr | idl2json --did samples/internet_identity.did --method deploy_archive --compact
+        // [{"success":"aaaaa-aa"}]
+        TestVector {
+            stdin: r#"(variant { 3_092_129_219 = principal "aaaaa-aa" })"#,
+            args: Args {
+                did: vec![sample_file!("internet_identity.did")],
+                method: Some("deploy_archive".to_string()),
+                compact: true,
+                ..Args::default()
+            },
+            stdout: r#"[{"success":"aaaaa-aa"}]"#,
+        },
+        // We should be able to get a JSON schema of the output instead of converting.
+        // On the command line we should see:
+        // $ idl2json --did samples/internet_identity.did --typ 'record { expiration: Timestamp }' --schema --compact
+        // {"$schema":"https://json-schema.org/draft/2020-12/schema","type":"object","properties":{"expiration":{"$ref":"#/$defs/Timestamp"}},"required":["expiration"],"$defs":{"Timestamp":{"type":"string","pattern":"^[0-9]+$"}}}
+        TestVector {
+            stdin: "",
+            args: Args {
+                schema: true,
+                ..typed_arg!("internet_identity.did", "record { expiration: Timestamp }")
+            },
+            stdout: r##"{"$schema":"https://json-schema.org/draft/2020-12/schema","type":"object","properties":{"expiration":{"$ref":"#/$defs/Timestamp"}},"required":["expiration"],"$defs":{"Timestamp":{"type":"string","pattern":"^[0-9]+$"}}}"##,
+        },
+        // We should be able to get TypeScript declarations of the output instead of converting.
+        // On the command line we should see:
+        // $ idl2json --did samples/internet_identity.did --typ '(Purpose, opt Timestamp)' --typescript
+        // export type Output = [Purpose, [] | [Timestamp]];
+        // export type Purpose = { authentication: null } | { recovery: null };
+        // export type Timestamp = string;
+        TestVector {
+            stdin: "",
+            args: Args {
+                typescript: true,
+                ..typed_arg!("internet_identity.did", "(Purpose, opt Timestamp)")
+            },
+            stdout: "export type Output = [Purpose, [] | [Timestamp]];\nexport type Purpose = { authentication: null } | { recovery: null };\nexport type Timestamp = string;\n",
+        },
+        // Absent optional fields may be added.
+        // On the command line we should see:
+        // $ echo "(record{canister_creation_cycles_cost= opt 999;})" | didc encode | didc decode | tee /dev/stderr | idl2json --did samples/internet_identity.did  --typ InternetIdentityInit --fill-missing-fields
+        // (record { 2_138_241_783 = opt (999 : int) })
+        // {"archive_module_hash":[],"assigned_user_number_range":[],"canister_creation_cycles_cost":["999"]}
+        TestVector {
+            stdin: "(record { 2_138_241_783 = opt (999 : int) })",
+            args: Args {
+                fill_missing_fields: true,
+                ..typed_arg!("internet_identity.did", "InternetIdentityInit")
+            },
+            stdout: r#"{"archive_module_hash":[],"assigned_user_number_range":[],"canister_creation_cycles_cost":["999"]}"#,
+        },
+        // Output may be canonical JSON, with keys sorted and numbers formatted as in RFC 8785.
+        // On the command line we should see:
+        // $ echo '(record { b = 1e21 : float64; a = "\u{20ac}\n" }, 0.5 : float32)' | idl2json --typ '(record { b: float64; a: text }, float32)' --canonical
+        // [{"a":"€\n","b":1e+21},0.5]
+        TestVector {
+            stdin: r#"(record { b = 1e21 : float64; a = "\u{20ac}\n" }, 0.5 : float32)"#,
+            args: Args {
+                typ: Some("(record { b: float64; a: text }, float32)".to_string()),
+                canonical: true,
+                ..Args::default()
+            },
+            stdout: r#"[{"a":"€\n","b":1e+21},0.5]"#,
+        },
+        // ICRC-3 generic values may be rendered as plain JSON.
+        // On the command line we should see:
+        // $ idl2json --did samples/icrc3.did --method icrc3_get_blocks --flatten-generic-values --compact < samples/icrc3_block.idl
+        // [{"blocks":[{"block":{"btype":"1xfer","phash":[1,2,255],"ts":"1_722_000_000_000_000_000","tx":{"amt":"100_000","delta":"-5","from":[[0]]}},"id":"1"}],"log_length":"2"}]
+        TestVector {
+            stdin: include_str!("../../../samples/icrc3_block.idl"),
+            args: Args {
+                did: vec![sample_file!("icrc3.did")],
+                method: Some("icrc3_get_blocks".to_string()),
+                flatten_generic_values: true,
+                compact: true,
+                ..Args::default()
+            },
+            stdout: r#"[{"blocks":[{"block":{"btype":"1xfer","phash":[1,2,255],"ts":"1_722_000_000_000_000_000","tx":{"amt":"100_000","delta":"-5","from":[[0]]}},"id":"1"}],"log_length":"2"}]"#,
+        },
+        // Bytes may be displayed as text or principals.
+        // On the command line we should see:
+        // $ echo '(record { owner = blob "\00\00\00\00\00\00\00\01\01\01"; memo = blob "Hi" })' | idl2json --utf8-bytes-as-text --principal-bytes-path owner --typ 'record { owner: blob; memo: blob }' --compact
+        // {"memo":"Hi","owner":"rrkah-fqaaa-aaaaa-aaaaq-cai"}
+        TestVector {
+            stdin: r#"(record { owner = blob "\00\00\00\00\00\00\00\01\01\01"; memo = blob "Hi" })"#,
+            args: Args {
+                typ: Some("record { owner: blob; memo: blob }".to_string()),
+                utf8_bytes_as_text: true,
+                principal_bytes_path: vec![PathPattern::from_str("owner").unwrap()],
+                compact: true,
+                ..Args::default()
+            },
+            stdout: r#"{"memo":"Hi","owner":"rrkah-fqaaa-aaaaa-aaaaq-cai"}"#,
+        },
+        // Values may be tagged with their candid type, so that nothing is lost.
+        // On the command line we should see:
+        // $ echo "(record{canister_creation_cycles_cost= opt 999;})" | didc encode | didc decode | tee /dev/stderr | idl2json --did samples/internet_identity.did  --typ InternetIdentityInit --lossless
+        // (record { 2_138_241_783 = opt (999 : int) })
+        // {"record":{"canister_creation_cycles_cost":{"opt":[{"int":"999"}]}}}
+        TestVector {
+            stdin: "(record { 2_138_241_783 = opt (999 : int) })",
+            args: Args {
+                lossless: true,
+                ..typed_arg!("internet_identity.did", "InternetIdentityInit")
+            },
+            stdout: r#"{"record":{"canister_creation_cycles_cost":{"opt":[{"int":"999"}]}}}"#,
+        },
+        // Only the values at a path may be converted, with `*` matching any element or variant tag.
+        // On the command line we should see:
+        // $ idl2json --did samples/proposal.did --typ 'opt ProposalInfo' --select 'ballots.*.1.vote' -c < samples/proposal.idl
+        TestVector {
+            stdin: include_str!("../../../samples/proposal.idl"),
+            args: Args {
+                select: Some(PathPattern::from_str("ballots.*.1.vote").unwrap()),
+                ..typed_arg!("proposal.did", "opt ProposalInfo")
+            },
+            stdout: "\"0\"\n\"1\"\n\"1\"\n\"0\"",
+        },
+        // Secrets may be redacted by type or by path.
+        // On the command line we should see:
+        // $ echo '(record { pubkey = blob "\01\02"; alias = "phone" })' | idl2json --did samples/internet_identity.did --typ 'record { pubkey: DeviceKey; alias: text }' --redact-type PublicKey -c
+        // {"alias":"phone","pubkey":"[redacted]"}
+        TestVector {
+            stdin: r#"(record { pubkey = blob "\01\02"; alias = "phone" })"#,
+            args: Args {
+                redact_type: vec!["PublicKey".to_string()],
+                ..typed_arg!("internet_identity.did", "record { pubkey: DeviceKey; alias: text }")
+            },
+            stdout: r#"{"alias":"phone","pubkey":"[redacted]"}"#,
+        },
+        TestVector {
+            stdin: r#"(record { pubkey = blob "\01\02"; alias = "phone" })"#,
+            args: Args {
+                redact_path: vec![PathPattern::from_str("alias").unwrap()],
+                compact: true,
+                ..Args::default()
+            },
+            stdout: r#"{"alias":"[redacted]","pubkey":[1,2]}"#,
+        },
+        // Long vecs and texts may be truncated.
+        // On the command line we should see:
+        // $ idl2json --did samples/proposal.did --typ 'opt ProposalInfo' --select ballots --max-vec-elements 1 --max-text-length 3 -c < samples/proposal.idl
+        TestVector {
+            stdin: include_str!("../../../samples/proposal.idl"),
+            args: Args {
+                select: Some(PathPattern::from_str("ballots").unwrap()),
+                max_vec_elements: Some(1),
+                max_text_length: Some(3),
+                ..typed_arg!("proposal.did", "opt ProposalInfo")
+            },
+            stdout: r#"[{"0":"10393729187455219830","1":{"vote":"0","voting_power":"15117295952643213369"}},{"…":"3 more elements"}]"#,
+        },
+        // The shape of a value may be summarized, rather than its content.
+        // On the command line we should see:
+        // $ echo '(record { a = vec { 1 : nat8; 2 : nat8 }; b = vec { "x"; "y" } })' | idl2json --summary -c
+        TestVector {
+            stdin: r#"(record { a = vec { 1 : nat8; 2 : nat8 }; b = vec { "x"; "y" } })"#,
+            args: Args {
+                summary: true,
+                compact: true,
+                ..Args::default()
+            },
+            stdout: r#"{"max_depth":2,"types":{"blob":1,"record":1,"text":2,"vec":1},"paths":{".":{"types":{"record":1}},"a":{"types":{"blob":1},"bytes":2},"b":{"types":{"vec":1},"lengths":{"min":2,"max":2,"total":2}},"b.*":{"types":{"text":2}}}}"#,
+        },
+        // Values may be written as YAML, with integers as numbers rather than strings, for example to be edited and
+        // converted back with yaml2candid.
+        // On the command line we should see:
+        // $ echo '(record { name = "Alice"; tags = vec { "a"; "b" } }, record { n = 1 : nat8; fee = 10000 : nat64 })' | idl2json --format yaml
+        TestVector {
+            stdin: r#"(record { name = "Alice"; tags = vec { "a"; "b" } }, record { n = 1 : nat8; fee = 10000 : nat64 })"#,
+            args: Args {
+                format: Some(OutputFormat::Yaml),
+                ..Args::default()
+            },
+            stdout: "name: Alice\ntags:\n- a\n- b\n---\nfee: 10000\nn: 1",
+        },
+        // Vectors of records may be written as tables, with variants as a tag column and payload columns.
+        // On the command line we should see:
+        // $ echo '(vec { record { id = 1 : nat8; kind = variant { a = "x, y" } }; record { id = 2 : nat8; kind = variant { b } } })' | idl2json --format csv
+        TestVector {
+            stdin: r#"(vec { record { id = 1 : nat8; kind = variant { a = "x, y" } }; record { id = 2 : nat8; kind = variant { b } } })"#,
+            args: Args {
+                format: Some(OutputFormat::Csv),
+                ..Args::default()
+            },
+            stdout: "id,kind,kind.a\n1,a,\"x, y\"\n2,b,",
+        },
+        // Cells that are not flattened, such as truncated text, stay whole, and formulas are not evaluated.
+        // On the command line we should see:
+        // $ echo '(vec { record { id = -1 : int; note = "abcdef"; sum = "=1+2" } })' | idl2json --format csv --max-text-length 4
+        TestVector {
+            stdin: r#"(vec { record { id = -1 : int; note = "abcdef"; sum = "=1+2" } })"#,
+            args: Args {
+                format: Some(OutputFormat::Csv),
+                max_text_length: Some(4),
+                ..Args::default()
+            },
+            stdout: "id,note,sum\n-1,\"{\"\"text\"\":\"\"abcd\"\",\"\"…\"\":\"\"2 more characters\"\"}\",'=1+2",
+        },
+        // Values may be framed as an array, as JSON Lines or as just the first value.
+        // On the command line we should see:
+        // $ echo '(record { a = 1 }, record { b = 2 })' | idl2json --framing array -c
+        TestVector {
+            stdin: "(record { a = 1 }, record { b = 2 })",
+            args: Args {
+                framing: Some(Framing::Array),
+                compact: true,
+                ..Args::default()
+            },
+            stdout: r#"[{"a":"1"},{"b":"2"}]"#,
+        },
+        // $ echo '(record { a = 1 }, record { b = 2 })' | idl2json --framing lines
+        TestVector {
+            stdin: "(record { a = 1 }, record { b = 2 })",
+            args: Args {
+                framing: Some(Framing::Lines),
+                ..Args::default()
+            },
+            stdout: "{\"a\":\"1\"}\n{\"b\":\"2\"}",
+        },
+        // $ echo '(record { a = 1 }, record { b = 2 })' | idl2json --framing first -c
+        TestVector {
+            stdin: "(record { a = 1 }, record { b = 2 })",
+            args: Args {
+                framing: Some(Framing::First),
+                compact: true,
+                ..Args::default()
+            },
+            stdout: r#"{"a":"1"}"#,
+        },
+        // Tuple types give an array, which is pretty unless compact output is requested.
+        // $ echo '(record { a = 1 : nat8 }, "x")' | idl2json --typ '(record { a : nat8 }, text)'
+        TestVector {
+            stdin: r#"(record { a = 1 : nat8 }, "x")"#,
+            args: Args {
+                typ: Some("(record { a : nat8 }, text)".to_string()),
+                ..Args::default()
+            },
+            stdout: "[\n  {\n    \"a\": 1\n  },\n  \"x\"\n]",
+        },
+        // $ echo '(record { a = 1 : nat8 }, "x")' | idl2json --typ '(record { a : nat8 }, text)' --framing lines
+        TestVector {
+            stdin: r#"(record { a = 1 : nat8 }, "x")"#,
+            args: Args {
+                typ: Some("(record { a : nat8 }, text)".to_string()),
+                framing: Some(Framing::Lines),
+                ..Args::default()
+            },
+            stdout: "{\"a\":1}\n\"x\"",
+        },
+        // Results may be unwrapped, so that only the `Ok` payload is printed.
+        // On the command line we should see:
+        // $ echo '(variant { Ok = 5 : nat64 })' | idl2json --typ 'variant { Ok : nat64; Err : text }' --unwrap-result
+        TestVector {
+            stdin: "(variant { Ok = 5 : nat64 })",
+            args: Args {
+                typ: Some("variant { Ok : nat64; Err : text }".to_string()),
+                unwrap_result: true,
+                ..Args::default()
+            },
+            stdout: r#""5""#,
+        },
+        // Two files may be compared, with the changes given as JSON Patch or as text.
+        // On the command line we should see:
+        // $ idl2json --did samples/all_types.did --typ 'opt AllTypes' --diff samples/all_types.idl samples/all_types_changed.idl --diff-as text
+        TestVector {
+            stdin: "",
+            args: Args {
+                diff: vec![
+                    sample_file!("all_types.idl"),
+                    sample_file!("all_types_changed.idl"),
+                ],
+                diff_as: Some(DiffFormat::Text),
+                ..typed_arg!("all_types.did", "opt AllTypes")
+            },
+            stdout: r#"~ 0.maybe_number: "64" -> "65"
+~ 0.text_value: "I am Bob Geldorf" -> "I am Alice"
+- 0.variant_value.bob: "Geldorf"
++ 0.variant_value.alice: "Liddell"
++ 0.vec_nat8.2: 3"#,
+        },
+        TestVector {
+            stdin: "",
+            args: Args {
+                diff: vec![
+                    sample_file!("all_types.idl"),
+                    sample_file!("all_types_changed.idl"),
+                ],
+                ..typed_arg!("all_types.did", "opt AllTypes")
+            },
+            stdout: r#"[{"op":"replace","path":"/0/0/maybe_number/0","value":"65"},{"op":"replace","path":"/0/0/text_value","value":"I am Alice"},{"op":"remove","path":"/0/0/variant_value/bob"},{"op":"add","path":"/0/0/variant_value/alice","value":"Liddell"},{"op":"add","path":"/0/0/vec_nat8/2","value":3}]"#,
+        },
     ];
     for vector in vectors {
         let out = main(&vector.args, vector.stdin)
             .map_err(|e| anyhow!("Failed to parse: {} due to: {e}", vector.stdin))
             .unwrap();
-        assert_eq!(vector.stdout, &out)
+        assert_eq!(vector.stdout, String::from_utf8(out).unwrap())
     }
 }
 
@@ -171,8 +492,9 @@ fn error_handling_should_be_correct() {
     for (index, vector) in vectors.iter().enumerate() {
         match main(&vector.args, vector.stdin) {
             Ok(json) => panic!(
-                "#{index} ({}) should have caused an error but returned: {json}",
-                vector.name
+                "#{index} ({}) should have caused an error but returned: {}",
+                vector.name,
+                String::from_utf8_lossy(&json)
             ),
             Err(err) => {
                 let error_message = format!("{err:?}");
@@ -187,3 +509,562 @@ fn error_handling_should_be_correct() {
         }
     }
 }
+
+/// Verifies that binary candid can be compared.
+#[test]
+fn binary_candid_can_be_compared() {
+    let binary_file = |name: &str| {
+        let text = std::fs::read_to_string(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("../../samples/{name}.idl")),
+        )
+        .unwrap();
+        let binary = candid_parser::parse_idl_args(&text)
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("idl2json-{name}-{}.bin", std::process::id()));
+        std::fs::write(&path, binary).unwrap();
+        path
+    };
+    let before = binary_file("all_types");
+    let after = binary_file("all_types_changed");
+    let diff = |before: &Path, after: &Path| {
+        main(
+            &Args {
+                diff: vec![before.to_path_buf(), after.to_path_buf()],
+                diff_as: Some(DiffFormat::Text),
+                ..typed_arg!("all_types.did", "opt AllTypes")
+            },
+            "",
+        )
+        .map(String::from_utf8)
+        .unwrap()
+        .unwrap()
+    };
+    assert_eq!("", diff(&before, &before));
+    assert!(diff(&before, &after).contains(r#"~ 0.text_value: "I am Bob Geldorf" -> "I am Alice""#));
+    std::fs::remove_file(before).unwrap();
+    std::fs::remove_file(after).unwrap();
+}
+
+/// Verifies that each tuple of a stream is converted separately, wherever the lines break.
+#[test]
+fn streams_are_converted_tuple_by_tuple() {
+    let input = "(record { a = 1 })\n\n(\"x\", 2 : nat8)\n(1 : nat8)(2 : nat8) // (3)\n(\n  record {\n    b = \")\";\n  },\n)\n";
+    let mut output = Vec::new();
+    stream(
+        &Args {
+            stream: true,
+            framing: Some(Framing::Array),
+            ..Args::default()
+        },
+        input.as_bytes(),
+        &mut output,
+    )
+    .unwrap();
+    assert_eq!(
+        "[{\"a\":\"1\"}]\n[\"x\",2]\n[1]\n[2]\n[{\"b\":\")\"}]\n",
+        String::from_utf8(output).unwrap()
+    );
+    let err = stream(&Args::default(), "(1)\n(oops\n".as_bytes(), Vec::new()).unwrap_err();
+    assert!(format!("{err:?}").contains("Failed to convert line 2"));
+    let err = stream(
+        &Args::default(),
+        "(1)\n(2) (record { a = })".as_bytes(),
+        Vec::new(),
+    )
+    .unwrap_err();
+    let report = format!("{err:?}");
+    assert!(report.contains("stdin:2:19"), "{report}");
+    assert!(report.contains("2 │     (record { a = })"), "{report}");
+}
+
+/// Verifies that errors in candid text are reported with their position and give a distinct exit code.
+#[test]
+fn parse_errors_give_the_position() {
+    let err = main(&Args::default(), "(record { a = 1;\n  b = })").unwrap_err();
+    let report = format!("{err:?}");
+    assert!(report.contains("stdin:2:7"), "{report}");
+    assert!(report.contains("2 │   b = })"), "{report}");
+    assert!(report.contains("^ Unexpected token"), "{report}");
+    assert!(report.contains(r#"Expects one of "(", "blob""#), "{report}");
+    assert_eq!(EXIT_PARSE_ERROR, exit_code(&err));
+
+    let args = Args {
+        typ: Some("record { a : }".to_string()),
+        ..Args::default()
+    };
+    let err = main(&args, "(1)").unwrap_err();
+    assert!(format!("{err:?}").contains("--typ:1:14"), "{err:?}");
+    assert_eq!(EXIT_PARSE_ERROR, exit_code(&err));
+
+    let args = Args {
+        did: vec![Path::new("does-not-exist.did").to_path_buf()],
+        ..Args::default()
+    };
+    assert_eq!(EXIT_FAILURE, exit_code(&main(&args, "(1)").unwrap_err()));
+}
+
+/// Verifies that an `Err` result gives its payload and the requested exit code.
+#[test]
+fn err_results_give_an_exit_code() {
+    let args = Args {
+        unwrap_result: true,
+        compact: true,
+        ..Args::default()
+    };
+    let err = main(&args, r#"(variant { Err = variant { NotFound = "x" } })"#).unwrap_err();
+    assert_eq!(r#"{"NotFound":"x"}"#, err.to_string());
+    assert_eq!(i32::from(DEFAULT_ERR_EXIT_CODE), exit_code(&err));
+    let args = Args {
+        err_exit_code: Some(7),
+        ..args
+    };
+    let err = main(&args, r#"(variant { err = "x" })"#).unwrap_err();
+    assert_eq!(r#""x""#, err.to_string());
+    assert_eq!(7, exit_code(&err));
+    // Variants that are not results are printed as they are.
+    assert_eq!(
+        br#"{"Other":"x"}"#.to_vec(),
+        main(&args, r#"(variant { Other = "x" })"#).unwrap()
+    );
+    // With a type, only variants with exactly an Ok and an Err case are results.
+    let args = Args {
+        typ: Some("variant { ok; pending }".to_string()),
+        ..args
+    };
+    assert_eq!(
+        br#"{"ok":null}"#.to_vec(),
+        main(&args, "(variant { ok })").unwrap()
+    );
+    let args = Args {
+        typ: Some("variant { ok : nat8; err : text }".to_string()),
+        ..args
+    };
+    assert_eq!(
+        br#""1""#.to_vec(),
+        main(&args, "(variant { ok = 1 })").unwrap()
+    );
+}
+
+/// Verifies that options default to those in the configuration file and its profiles.
+#[test]
+fn config_files_give_defaults() {
+    let project_dir = std::env::temp_dir().join(format!("idl2json-config-{}", std::process::id()));
+    let current_dir = project_dir.join("src");
+    std::fs::create_dir_all(&current_dir).unwrap();
+    std::fs::write(
+        project_dir.join("types.did"),
+        "type Session = record { key : blob; user : text };",
+    )
+    .unwrap();
+    std::fs::write(
+        project_dir.join(CONFIG_FILE_NAME),
+        r#"did = ["types.did"]
+typ = "Session"
+compact = true
+bytes-as = "hex"
+
+[profiles.private]
+key_order = "declaration"
+
+[profiles.private.types]
+Session = "redact"
+"#,
+    )
+    .unwrap();
+    let parse = |argv: &[&str]| {
+        parse_args_in(
+            std::iter::once("idl2json")
+                .chain(argv.iter().copied())
+                .map(OsString::from)
+                .collect(),
+            &current_dir,
+        )
+    };
+    let input = r#"(record { key = blob "\01\02"; user = "alice" })"#;
+
+    let args = parse(&[]).unwrap();
+    assert_eq!(vec![project_dir.join("types.did")], args.did);
+    assert_eq!(
+        br#"{"key":"0102","user":"alice"}"#.to_vec(),
+        main(&args, input).unwrap()
+    );
+    // Options on the command line take precedence.
+    let args = parse(&["--bytes-as", "numbers", "--typ", "record { user : text }"]).unwrap();
+    assert_eq!(Some(BytesFormat::Numbers), args.bytes_as);
+    assert_eq!(
+        br#"{"user":"alice"}"#.to_vec(),
+        main(&args, r#"(record { user = "alice" })"#).unwrap()
+    );
+    // Profiles add to and override the top level options.
+    let args = parse(&["--profile", "private"]).unwrap();
+    assert_eq!(Some(KeyOrder::Declaration), args.key_order);
+    assert_eq!(vec!["Session".to_string()], args.redact_type);
+    assert!(parse(&["--profile", "public"])
+        .unwrap_err()
+        .to_string()
+        .contains("There is no profile 'public'"));
+
+    std::fs::write(project_dir.join("other.toml"), "colour = true").unwrap();
+    let err = parse(&["--config", "../other.toml"]).unwrap_err();
+    assert!(err.to_string().contains("Unknown option 'colour'"), "{err}");
+    std::fs::remove_dir_all(&project_dir).unwrap();
+}
+
+/// Verifies that the types and methods in .did files may be listed and shown.
+#[test]
+fn did_files_may_be_described() {
+    let icrc3 = format!("{}/../../samples/icrc3.did", env!("CARGO_MANIFEST_DIR"));
+    let identity = format!(
+        "{}/../../samples/internet_identity.did",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let run = |argv: &[&str]| {
+        let args =
+            Args::try_parse_from(std::iter::once("idl2json").chain(argv.iter().copied())).unwrap();
+        assert!(!args.reads_stdin());
+        String::from_utf8(main(&args, "").unwrap()).unwrap()
+    };
+    assert_eq!("Value\nGetBlocksResult", run(&["types", "--did", &icrc3]));
+    assert_eq!(
+        "icrc3_get_blocks : (vec record { start : nat; length : nat }) -> (GetBlocksResult) query",
+        run(&["--did", &icrc3, "methods"])
+    );
+    assert_eq!(
+        "type Value = variant {\n  Int : int;\n  Map : vec record { text; Value };\n  Nat : nat;\n  Blob : blob;\n  Text : text;\n  Array : vec Value;\n};",
+        run(&["show-type", "Value", "--did", &icrc3])
+    );
+    // With several files, the file of each type is shown.
+    let types = run(&["types", "--did", &icrc3, "--did", &identity]);
+    assert!(types.starts_with(&format!("Value\t{icrc3}\n")), "{types}");
+    assert!(
+        types.contains(&format!("\nDeviceData\t{identity}\n")),
+        "{types}"
+    );
+    assert_eq!(
+        format!("// {identity}\ntype UserNumber = nat64;"),
+        run(&[
+            "show-type",
+            "UserNumber",
+            "--depth",
+            "0",
+            "-d",
+            &icrc3,
+            "-d",
+            &identity
+        ])
+    );
+    let args = Args::try_parse_from(["idl2json", "show-type", "Nope", "--did", &icrc3]).unwrap();
+    assert!(main(&args, "")
+        .unwrap_err()
+        .to_string()
+        .contains("Could not find a type called 'Nope'"));
+}
+
+/// Verifies that candid, text or binary, may be checked against a type and gives a report of what does not conform.
+#[test]
+fn candid_may_be_checked_against_a_type() {
+    let args = Args {
+        check: true,
+        compact: true,
+        typ: Some("record { owner : principal; amount : nat8 }".to_string()),
+        ..Args::default()
+    };
+    let err = main(&args, r#"(record { owner = "x"; amount = 300 })"#).unwrap_err();
+    assert_eq!(EXIT_NONCONFORMING, exit_code(&err));
+    let report = err.downcast_ref::<Nonconforming>().unwrap().report();
+    assert_eq!(
+        r#"[{"path":"0.owner","reason":"expected principal, found \"x\""},{"path":"0.amount","reason":"300 is out of range for nat8"}]"#,
+        String::from_utf8_lossy(report)
+    );
+    assert_eq!(
+        b"[]".to_vec(),
+        main(
+            &args,
+            r#"(record { owner = principal "aaaaa-aa"; amount = 3 })"#
+        )
+        .unwrap()
+    );
+
+    // Binary candid may be given as hex.
+    let bytes = candid_parser::parse_idl_args(
+        r#"(record { owner = principal "aaaaa-aa"; amount = 3 : nat8 })"#,
+    )
+    .unwrap()
+    .to_bytes()
+    .unwrap();
+    let hex = bytes.iter().fold(String::new(), |mut hex, byte| {
+        hex.push_str(&format!("{byte:02x}"));
+        hex
+    });
+    assert_eq!(b"[]".to_vec(), main(&args, &format!("{hex}\n")).unwrap());
+    let args = Args {
+        typ: Some("record { owner : principal; amount : nat }".to_string()),
+        ..args
+    };
+    let err = main(&args, &hex).unwrap_err();
+    assert_eq!(EXIT_NONCONFORMING, exit_code(&err));
+
+    // With a method, the candid is checked against its arguments.
+    let args = Args {
+        did: vec![Path::new(&format!(
+            "{}/../../samples/internet_identity.did",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .to_path_buf()],
+        method: Some("remove".to_string()),
+        typ: None,
+        ..args
+    };
+    let err = main(&args, "(10000, \"key\")").unwrap_err();
+    assert_eq!(
+        r#"[{"path":"1","reason":"expected DeviceKey, found \"key\""}]"#,
+        String::from_utf8_lossy(err.downcast_ref::<Nonconforming>().unwrap().report())
+    );
+}
+
+/// Verifies that conversions may be served over HTTP, with options given per request.
+#[test]
+fn conversions_may_be_served() {
+    let identity = format!(
+        "{}/../../samples/internet_identity.did",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let args = parse_args_in(
+        ["idl2json", "--did", &identity, "--compact", "serve"]
+            .into_iter()
+            .map(OsString::from)
+            .collect(),
+        Path::new("/"),
+    )
+    .unwrap();
+    assert_eq!(
+        Some(SocketAddr::from(([127, 0, 0, 1], 8080))),
+        args.listen_address()
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || serve(&args, listener));
+    let request = |method: &str, target: &str, body: &[u8]| {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{method} {target} HTTP/1.1\r\nHost: {address}\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status: u16 = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, body.to_string())
+    };
+
+    assert_eq!(
+        (200, r#""10000""#.to_string()),
+        request("POST", "/idl2json?typ=UserNumber", b"(10000)")
+    );
+    // Options in the query override those given when the server was started.
+    assert_eq!(
+        (200, "[\n  \"10000\"\n]".to_string()),
+        request(
+            "POST",
+            "/idl2json?compact=false&typ=vec+UserNumber",
+            b"(vec { 10000 })"
+        )
+    );
+    let bytes = candid_parser::parse_idl_args("(blob \"\\01\\02\")")
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    assert_eq!(
+        (200, r#""0102""#.to_string()),
+        request("POST", "/binary2json?typ=DeviceKey&bytes_as=hex", &bytes)
+    );
+    assert_eq!(
+        (200, "(10_000 : nat64)".to_string()),
+        request("POST", "/json2idl?typ=UserNumber", br#""10000""#)
+    );
+    assert_eq!(
+        (200, "type DeviceKey = PublicKey;".to_string()),
+        request("GET", "/types/DeviceKey?depth=0", b"")
+    );
+    assert!(request("GET", "/types", b"").1.starts_with("UserNumber\n"));
+    // Paths are not decoded as queries, in which `+` is a space.
+    assert!(request("GET", "/types/No+Type", b"").1.contains("No+Type"));
+    // Values that do not conform give their report.
+    assert_eq!(
+        (
+            422,
+            r#"[{"path":"0","reason":"expected UserNumber, found \"x\""}]"#.to_string()
+        ),
+        request("POST", "/idl2json?check&typ=UserNumber", b"(\"x\")")
+    );
+    assert_eq!(400, request("POST", "/idl2json?did=other.did", b"(1)").0);
+    assert_eq!(400, request("POST", "/idl2json?colour=red", b"(1)").0);
+    assert_eq!(404, request("GET", "/nope", b"").0);
+    assert_eq!(405, request("GET", "/idl2json", b"").0);
+    // A body shorter than its Content-Length is rejected, however long the Content-Length.
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "POST /idl2json HTTP/1.1\r\nContent-Length: 1000000\r\n\r\n(1)"
+    )
+    .unwrap();
+    stream.shutdown(std::net::Shutdown::Write).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 400 "), "{response}");
+}
+
+/// Verifies that many files, text or binary, may be converted at once, with failures listed.
+#[test]
+fn files_may_be_converted_in_a_batch() {
+    let dir = std::env::temp_dir().join(format!("idl2json-batch-{}", std::process::id()));
+    let input_dir = dir.join("input");
+    std::fs::create_dir_all(input_dir.join("binary")).unwrap();
+    std::fs::write(input_dir.join("text.idl"), "(1, \"one\")").unwrap();
+    std::fs::write(input_dir.join("notes.txt"), "Not candid").unwrap();
+    let bytes = candid_parser::parse_idl_args("(2 : nat8)")
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    std::fs::write(input_dir.join("binary/value.bin"), bytes).unwrap();
+    std::fs::write(input_dir.join("binary/malformed.idl"), "(3").unwrap();
+    let parse = |argv: &[&str]| {
+        Args::try_parse_from(std::iter::once("idl2json").chain(argv.iter().copied())).unwrap()
+    };
+    let read = |path: &Path| std::fs::read_to_string(dir.join(path)).unwrap();
+    let input = input_dir.to_str().unwrap();
+    let output = dir.join("output");
+
+    let args = parse(&[
+        "--compact",
+        "batch",
+        input,
+        "--out-dir",
+        output.to_str().unwrap(),
+        "--jobs",
+        "2",
+    ]);
+    assert!(args.batches() && !args.reads_stdin());
+    let err = batch(&args).unwrap_err();
+    assert_eq!(EXIT_PARSE_ERROR, exit_code(&err));
+    let message = err.to_string();
+    assert!(
+        message.starts_with("1 of 3 files could not be converted:\n")
+            && message.contains("malformed.idl"),
+        "{message}"
+    );
+    assert_eq!(
+        "\"1\"\n\"one\"\n",
+        read(Path::new("output/input/text.json"))
+    );
+    assert_eq!("2\n", read(Path::new("output/input/binary/value.json")));
+    assert!(!output.join("input/notes.json").exists());
+
+    // Without an output directory, the output is written alongside the input.
+    let text = input_dir.join("text.idl");
+    let args = parse(&[
+        "--typ",
+        "(nat8, text)",
+        "-f",
+        "yaml",
+        "batch",
+        text.to_str().unwrap(),
+    ]);
+    assert_eq!(1, batch(&args).unwrap());
+    assert_eq!("- 1\n- one\n", read(Path::new("input/text.yaml")));
+
+    // Files that do not conform are failures, with their reports as output.
+    let args = parse(&[
+        "--check",
+        "-c",
+        "--typ",
+        "(nat8, nat8)",
+        "batch",
+        text.to_str().unwrap(),
+    ]);
+    let err = batch(&args).unwrap_err();
+    assert_eq!(EXIT_NONCONFORMING, exit_code(&err));
+    assert_eq!(
+        "[{\"path\":\"1\",\"reason\":\"expected nat8, found \\\"one\\\"\"}]\n",
+        read(Path::new("input/text.json"))
+    );
+
+    // Nothing is converted if two files would have the same output.
+    let other_dir = dir.join("other").join("input");
+    std::fs::create_dir_all(&other_dir).unwrap();
+    std::fs::write(other_dir.join("text.idl"), "(4)").unwrap();
+    std::fs::write(input_dir.join("text.candid"), "(5)").unwrap();
+    std::fs::remove_dir_all(&output).unwrap();
+    let out_dir = ["--out-dir", output.to_str().unwrap()];
+    for inputs in [
+        vec![input_dir.join("text.idl"), other_dir.join("text.idl")],
+        vec![input_dir.clone(), other_dir.clone()],
+        vec![input_dir.clone()],
+    ] {
+        let inputs: Vec<&str> = inputs.iter().map(|path| path.to_str().unwrap()).collect();
+        let args = parse(&[&["batch"], &inputs[..], &out_dir].concat());
+        let err = batch(&args).unwrap_err().to_string();
+        assert!(err.contains("would both be converted to"), "{err}");
+        assert!(!output.exists());
+    }
+    // Input directories are kept apart by name.
+    let args = parse(&[
+        "batch",
+        input_dir.join("binary/value.bin").to_str().unwrap(),
+        other_dir.to_str().unwrap(),
+        "--out-dir",
+        output.to_str().unwrap(),
+    ]);
+    assert_eq!(2, batch(&args).unwrap());
+    assert_eq!("2\n", read(Path::new("output/value.json")));
+    assert_eq!("\"4\"\n", read(Path::new("output/input/text.json")));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Verifies that YAML output may be converted back to the same candid with yaml2candid.
+#[test]
+fn yaml_may_be_converted_back_with_yaml2candid() {
+    let did =
+        "type Init = record { fee : nat64; memo : opt blob; none : opt nat64; owner : principal; \
+               amount : nat; delta : int64; count : nat8; kind : variant { a; b : int } };";
+    let did_file = std::env::temp_dir().join(format!("idl2json-yaml-{}.did", std::process::id()));
+    std::fs::write(&did_file, did).unwrap();
+    let candid = r#"(record { fee = 10000 : nat64; memo = opt blob "\01\02"; none = null; owner = principal "aaaaa-aa"; amount = 18446744073709551616 : nat; delta = -4 : int64; count = 3 : nat8; kind = variant { b = -7 : int } })"#;
+    let yaml2candid = yaml2candid::Yaml2Candid {
+        prog: did.parse().unwrap(),
+    };
+    let json_args = Args {
+        did: vec![did_file.clone()],
+        typ: Some("Init".to_string()),
+        compact: true,
+        ..Args::default()
+    };
+    let expected = main(&json_args, candid).unwrap();
+    for bytes_as in [BytesFormat::Numbers, BytesFormat::Hex] {
+        let args = Args {
+            did: vec![did_file.clone()],
+            typ: Some("Init".to_string()),
+            format: Some(OutputFormat::Yaml),
+            bytes_as: Some(bytes_as),
+            ..Args::default()
+        };
+        let yaml = String::from_utf8(main(&args, candid).unwrap()).unwrap();
+        let converted = yaml2candid
+            .convert(
+                &candid_parser::types::IDLType::VarT("Init".to_string()),
+                &serde_yaml::from_str(&yaml).unwrap(),
+            )
+            .unwrap_or_else(|err| panic!("{err:#}\n{yaml}"));
+        assert_eq!(
+            String::from_utf8(expected.clone()).unwrap(),
+            String::from_utf8(main(&json_args, &format!("({converted})")).unwrap()).unwrap(),
+            "{yaml}"
+        );
+    }
+    std::fs::remove_file(&did_file).unwrap();
+}
@@ -0,0 +1,140 @@
+//! Splitting a stream of candid text into tuples, such as `(1 : nat8)(2 : nat8)` or tuples printed over several
+//! lines, as `dfx canister call` prints them.
+
+/// A candid tuple read from a stream.
+pub(crate) struct Tuple {
+    /// The text of the tuple.  Any text before it on its first line is replaced by spaces, so that positions in
+    /// the text have the columns of the stream.
+    pub text: String,
+    /// The number of the first line of the text in the stream, counting from 1.
+    pub first_line: usize,
+    /// The number of the line on which the tuple starts.
+    pub line: usize,
+}
+
+/// Where the scanner is in the candid text.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Code,
+    /// In a string, such as `"a"` or `blob "\00"`.
+    Text,
+    /// After a backslash in a string.
+    Escape,
+    /// In a `//` comment.
+    LineComment,
+    /// In `/* */` comments, nested to the given depth.
+    BlockComment(usize),
+}
+
+/// Splits candid text, read line by line, into tuples at balanced top level parentheses.
+#[derive(Default)]
+pub(crate) struct Tuples {
+    /// The text that has not been returned as a tuple yet, from the start of a line.
+    text: String,
+    /// The number of the line before the first line of `text`.
+    lines_before: usize,
+    /// The length of the text that has been scanned.
+    scanned: usize,
+    /// Where the next tuple starts in the text, once it has started.
+    start: Option<usize>,
+    /// The number of open parentheses.
+    depth: usize,
+    state: State,
+}
+
+impl Tuples {
+    /// Adds a line, ending with its line break if it has one, and returns the tuples completed by it.
+    pub fn push(&mut self, line: &str) -> Vec<Tuple> {
+        self.text.push_str(line);
+        let mut tuples = Vec::new();
+        while let Some(end) = self.scan() {
+            tuples.push(self.take(end));
+        }
+        tuples
+    }
+
+    /// The rest of the text, if it is not just whitespace and comments, for the parser to report.
+    pub fn finish(mut self) -> Option<Tuple> {
+        self.start.map(|_| self.take(self.text.len()))
+    }
+
+    /// Scans the text until the end of a tuple, returning the end.
+    fn scan(&mut self) -> Option<usize> {
+        let mut chars = self.text[self.scanned..].char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            let index = self.scanned + index;
+            let next = chars.peek().map(|(_, next)| *next);
+            self.state = match (self.state, c) {
+                (State::Code, '/') if next == Some('/') => State::LineComment,
+                (State::Code, '/') if next == Some('*') => {
+                    chars.next();
+                    State::BlockComment(1)
+                }
+                (State::Code, c) if c.is_whitespace() => State::Code,
+                (State::Code, c) => {
+                    self.start.get_or_insert(index);
+                    match c {
+                        '"' => State::Text,
+                        '(' => {
+                            self.depth += 1;
+                            State::Code
+                        }
+                        ')' if self.depth > 1 => {
+                            self.depth -= 1;
+                            State::Code
+                        }
+                        ')' if self.depth == 1 => {
+                            self.depth = 0;
+                            self.scanned = index + 1;
+                            return Some(index + 1);
+                        }
+                        _ => State::Code,
+                    }
+                }
+                (State::Text, '"') => State::Code,
+                (State::Text, '\\') => State::Escape,
+                (State::Escape, _) => State::Text,
+                (State::LineComment, '\n') => State::Code,
+                (State::BlockComment(depth), '/') if next == Some('*') => {
+                    chars.next();
+                    State::BlockComment(depth + 1)
+                }
+                (State::BlockComment(depth), '*') if next == Some('/') => {
+                    chars.next();
+                    match depth {
+                        1 => State::Code,
+                        depth => State::BlockComment(depth - 1),
+                    }
+                }
+                (state, _) => state,
+            };
+        }
+        self.scanned = self.text.len();
+        None
+    }
+
+    /// Removes the tuple that ends at the given position from the text.
+    fn take(&mut self, end: usize) -> Tuple {
+        let start = self.start.take().unwrap_or(0);
+        // The line of the end is kept, with the tuple replaced by spaces, for the columns of the next tuple.
+        let end_line_start = self.text[..end].rfind('\n').map_or(0, |index| index + 1);
+        let columns = self.text[end_line_start..end].chars().count();
+        let rest = format!("{}{}", " ".repeat(columns), &self.text[end..]);
+        let first_line = self.lines_before + 1;
+        let line = first_line + self.text[..start].matches('\n').count();
+        let blank_start: String = self.text[..start]
+            .chars()
+            .map(|c| if c == '\n' { '\n' } else { ' ' })
+            .collect();
+        let text = format!("{blank_start}{}", &self.text[start..end]);
+        self.lines_before += self.text[..end_line_start].matches('\n').count();
+        self.scanned = columns;
+        self.text = rest;
+        Tuple {
+            text,
+            first_line,
+            line,
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Unwrapping of candid results, such as `variant { Ok : T; Err : E }`.
+use crate::{convert_one, print_one};
+use candid::{idl_hash, types::value::IDLValue, IDLArgs};
+use candid_parser::types::IDLType;
+use idl2json::{resolve_type, Idl2JsonOptions};
+use std::fmt;
+
+/// The tags of the successful case of a result, as in Rust and in Motoko.
+const OK_TAGS: [&str; 2] = ["Ok", "ok"];
+/// The tags of the error case of a result, as in Rust and in Motoko.
+const ERR_TAGS: [&str; 2] = ["Err", "err"];
+
+/// The exit code for an `Err` result, unless another is given.
+pub const DEFAULT_ERR_EXIT_CODE: u8 = 2;
+
+/// A result that is an error.
+///
+/// This is displayed as the error payload, converted as the payload of an `Ok` would have been.
+#[derive(Debug)]
+pub struct ResultErr {
+    /// The converted error payload.
+    payload: String,
+    /// The exit code requested for errors.
+    exit_code: u8,
+}
+
+impl ResultErr {
+    /// The exit code requested for errors.
+    pub fn exit_code(&self) -> i32 {
+        i32::from(self.exit_code)
+    }
+}
+
+impl fmt::Display for ResultErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.payload)
+    }
+}
+
+impl std::error::Error for ResultErr {}
+
+/// Replaces the results in a tuple with their `Ok` payloads, and their types with the payload types.
+///
+/// - Values that are not results are left as they are.  Variants of a known type are results only if the type has
+///   exactly an `Ok` and an `Err` case.
+/// - If any result is an `Err`, its payload is returned as a `ResultErr`.
+pub(crate) fn unwrap_results(
+    idl_args: IDLArgs,
+    idl_types: &[Option<IDLType>],
+    exit_code: u8,
+    idl2json_options: &Idl2JsonOptions,
+) -> Result<(IDLArgs, Vec<Option<IDLType>>), ResultErr> {
+    let mut args = Vec::new();
+    let mut types = Vec::new();
+    for (index, idl_value) in idl_args.args.into_iter().enumerate() {
+        let idl_type = idl_types.get(index).cloned().flatten();
+        let IDLValue::Variant(variant) = &idl_value else {
+            args.push(idl_value);
+            types.push(idl_type);
+            continue;
+        };
+        let field = &variant.0;
+        match result_tag(idl_type.as_ref(), field.id.get_id(), idl2json_options) {
+            Some((ResultTag::Ok, payload_type)) => {
+                args.push(field.val.clone());
+                types.push(payload_type);
+            }
+            Some((ResultTag::Err, payload_type)) => {
+                let json_value = convert_one(&field.val, &payload_type, idl2json_options);
+                let payload = print_one(&json_value, false, idl2json_options)
+                    .map(|printed| String::from_utf8_lossy(&printed).into_owned())
+                    .unwrap_or_else(|_| json_value.to_string());
+                return Err(ResultErr { payload, exit_code });
+            }
+            None => {
+                args.push(idl_value);
+                types.push(idl_type);
+            }
+        }
+    }
+    Ok((IDLArgs { args }, types))
+}
+
+/// The cases of a result.
+enum ResultTag {
+    /// The successful case.
+    Ok,
+    /// The error case.
+    Err,
+}
+
+/// The case of a result that a variant with the given tag is, with the type of its payload if known.
+///
+/// - If the type of the variant is known, it is a result only if its tags are exactly an `Ok` and an `Err` tag.
+/// - Otherwise any variant with an `Ok` or `Err` tag is taken to be a result.
+///
+/// Returns None if the variant is not a result.
+fn result_tag(
+    idl_type: Option<&IDLType>,
+    field_id: u32,
+    idl2json_options: &Idl2JsonOptions,
+) -> Option<(ResultTag, Option<IDLType>)> {
+    let is_tag = |tags: [&str; 2], id: u32| tags.iter().any(|tag| idl_hash(tag) == id);
+    let result_tag = |id: u32| {
+        if is_tag(OK_TAGS, id) {
+            Some(ResultTag::Ok)
+        } else if is_tag(ERR_TAGS, id) {
+            Some(ResultTag::Err)
+        } else {
+            None
+        }
+    };
+    let resolved_type = idl_type.and_then(|idl_type| resolve_type(idl_type, idl2json_options));
+    let Some(resolved_type) = resolved_type else {
+        return Some((result_tag(field_id)?, None));
+    };
+    let IDLType::VariantT(fields) = resolved_type else {
+        return None;
+    };
+    let tags: Vec<_> = fields
+        .iter()
+        .filter_map(|field| result_tag(field.label.get_id()))
+        .collect();
+    let is_result = fields.len() == 2
+        && tags.iter().any(|tag| matches!(tag, ResultTag::Ok))
+        && tags.iter().any(|tag| matches!(tag, ResultTag::Err));
+    if !is_result {
+        return None;
+    }
+    let payload_type = fields
+        .into_iter()
+        .find(|field| field.label.get_id() == field_id)
+        .map(|field| field.typ);
+    Some((result_tag(field_id)?, payload_type))
+}
@@ -201,6 +201,7 @@ impl Yaml2Candid {
                             .as_f64().map(|val| val as f32)
                             .with_context(|| "Could not parse number as f64: {number:?}")?,
                     )),
+                    YamlValue::String(value) => Ok(IDLValue::Float32(Self::parse_non_finite_float(value)? as f32)),
                     _ => bail!("Please express this value as a number: {data:?}"),
                 },
                 candid_parser::types::PrimType::Float64 => match data {
@@ -209,6 +210,7 @@ impl Yaml2Candid {
                             .as_f64()
                             .with_context(|| "Could not parse number as f64: {number:?}")?,
                     )),
+                    YamlValue::String(value) => Ok(IDLValue::Float64(Self::parse_non_finite_float(value)?)),
                     _ => bail!("Please express this value as a number: {data:?}"),
                 },
                 candid_parser::types::PrimType::Bool => match data {
@@ -369,6 +371,15 @@ impl Yaml2Candid {
             _ => bail!("Expected a string, got: {data:?}"),
         }
     }
+    /// Gets a float that is not finite from its name, as given by idl2json.
+    fn parse_non_finite_float(value: &str) -> anyhow::Result<f64> {
+        match value {
+            "NaN" => Ok(f64::NAN),
+            "Infinity" => Ok(f64::INFINITY),
+            "-Infinity" => Ok(f64::NEG_INFINITY),
+            _ => bail!("Please express this value as a number, or as one of \"NaN\", \"Infinity\" and \"-Infinity\": {value:?}"),
+        }
+    }
     /// Gets a principal from a YAML value.
     fn parse_principal(data: &YamlValue) -> anyhow::Result<Principal> {
         match data {
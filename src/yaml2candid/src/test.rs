@@ -336,6 +336,26 @@ fn can_convert_f32() {
     }
 }
 
+#[test]
+fn can_convert_non_finite_f32() {
+    let converter = Yaml2Candid::default();
+    let typ = IDLType::PrimT(candid_parser::types::PrimType::Float32);
+    for (name, value) in [
+        ("Infinity", f32::INFINITY),
+        ("-Infinity", f32::NEG_INFINITY),
+    ]
+    .iter()
+    {
+        let data = YamlValue::from(*name);
+        let expected_result = IDLValue::Float32(*value);
+        assert_conversion_is(&converter, &typ, &data, expected_result);
+    }
+    let value = converter
+        .convert(&typ, &YamlValue::from("NaN"))
+        .expect("Failed to convert YAML to Candid.");
+    assert!(matches!(value, IDLValue::Float32(value) if value.is_nan()));
+}
+
 #[test]
 fn conversion_to_f32_should_fail_for_some_inputs() {
     let converter = Yaml2Candid::default();
@@ -356,6 +376,26 @@ fn can_convert_f64() {
     }
 }
 
+#[test]
+fn can_convert_non_finite_f64() {
+    let converter = Yaml2Candid::default();
+    let typ = IDLType::PrimT(candid_parser::types::PrimType::Float64);
+    for (name, value) in [
+        ("Infinity", f64::INFINITY),
+        ("-Infinity", f64::NEG_INFINITY),
+    ]
+    .iter()
+    {
+        let data = YamlValue::from(*name);
+        let expected_result = IDLValue::Float64(*value);
+        assert_conversion_is(&converter, &typ, &data, expected_result);
+    }
+    let value = converter
+        .convert(&typ, &YamlValue::from("NaN"))
+        .expect("Failed to convert YAML to Candid.");
+    assert!(matches!(value, IDLValue::Float64(value) if value.is_nan()));
+}
+
 #[test]
 fn conversion_to_f64_should_fail_for_some_inputs() {
     let converter = Yaml2Candid::default();